@@ -0,0 +1,116 @@
+use std::rc::Rc;
+
+use crate::{
+    patterns::Pattern,
+    scanner::Token,
+    symbols::{self, SymbolMap},
+    values::Value,
+};
+
+// One VM instruction. Operands index the chunk's tables: `constants` for values,
+// `tokens` for names and for the operator or bracket an error is reported at,
+// `patterns` and `functions`. Jump targets are absolute offsets into `code`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Constant(u32),
+    Pop,
+    PopN(u32),
+    GetLocal(u32),
+    SetLocal(u32),
+    // Captured variables live in cells shared with the closures that capture them.
+    // Reads name the variable's token for the error raised before it is defined.
+    NewCell(u32),
+    GetCell(u32, u32),
+    SetCell(u32),
+    GetUpvalue(u32, u32),
+    SetUpvalue(u32),
+    GetGlobal(u32),
+    SetGlobal(u32),
+    DefineGlobal(u32),
+    DefineConstant(u32),
+    Binary(u32),
+    Unary(u32),
+    GetProperty(u32),
+    Index(u32),
+    // Argument count and the closing parenthesis.
+    Call(u32, u32),
+    Closure(u32),
+    List(u32),
+    Map(u32),
+    // The range operator, and whether a step was given.
+    Range(u32, bool),
+    Print,
+    Jump(u32),
+    // Starts iterating over the value on top of the stack.
+    Iterate(u32),
+    // Pushes the next item of the innermost loop, or ends it and jumps.
+    Next(u32),
+    // Subject slot, pattern, number of enums pushed for it, and where to jump when
+    // it does not match. A match pushes the values the pattern binds.
+    Match(u32, u32, u32, u32),
+    // The `match` keyword and the subject slot of a match no arm matched.
+    NoMatch(u32, u32),
+    Return,
+    Yield,
+}
+
+#[derive(Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    // The source line of each instruction.
+    pub lines: Vec<usize>,
+    pub constants: Vec<Value>,
+    pub tokens: Vec<Token>,
+    pub patterns: Vec<Pattern>,
+    pub functions: Vec<Rc<Prototype>>,
+    // Top level `print` statements as (start, end) offsets: the tree-walker reports
+    // their errors after running the rest of the program, and so does the VM.
+    pub recoverable: Vec<(usize, usize)>,
+    // The constant holding each string, so a string the function uses many times
+    // is stored once.
+    pub strings: SymbolMap<u32>,
+}
+
+// Where a closure gets each variable it captures when it is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    // A cell of the function creating the closure.
+    Cell(u32),
+    // A variable the creating function captured itself.
+    Upvalue(u32),
+}
+
+// A compiled function, shared by every closure created from it.
+pub struct Prototype {
+    pub name: String,
+    pub arity: usize,
+    pub chunk: Chunk,
+    pub cells: usize,
+    pub captures: Vec<Capture>,
+    pub is_generator: bool,
+}
+
+impl Chunk {
+    pub fn emit(self: &mut Self, op: Op, line: usize) -> usize {
+        self.code.push(op);
+        self.lines.push(line);
+        return self.code.len() - 1;
+    }
+
+    pub fn constant(self: &mut Self, value: Value) -> u32 {
+        if let Value::StringValue(text) = &value {
+            let symbol = symbols::intern(text);
+            if let Some(index) = self.strings.get(&symbol) {
+                return *index;
+            }
+            self.strings.insert(symbol, self.constants.len() as u32);
+        }
+        self.constants.push(value);
+        return (self.constants.len() - 1) as u32;
+    }
+
+    pub fn token(self: &mut Self, token: &Token) -> u32 {
+        self.tokens.push(token.clone());
+        return (self.tokens.len() - 1) as u32;
+    }
+}
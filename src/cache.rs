@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+
+use crate::{bytecode::Prototype, tpc};
+
+// Compiled programs kept between runs, one `.tpc` file per source. The file name is
+// a hash of the source together with everything else that changes what it compiles
+// to, so an edited source, a new interpreter or different options simply miss and
+// stale entries are never read. Caching is best effort: a cache that can not be
+// read or written only means compiling again.
+
+// Where cached programs live: `$TIMEPASS_CACHE_DIR`, else the user's cache directory.
+pub fn directory() -> PathBuf {
+    if let Some(directory) = std::env::var_os("TIMEPASS_CACHE_DIR") {
+        return PathBuf::from(directory);
+    }
+    if let Some(directory) = std::env::var_os("XDG_CACHE_HOME") {
+        return PathBuf::from(directory).join("timepass");
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        return PathBuf::from(home).join(".cache").join("timepass");
+    }
+    return std::env::temp_dir().join("timepass-cache");
+}
+
+// The cache key of `source` compiled with the given option flags.
+pub fn key(source: &str, flags: &[bool]) -> u64 {
+    let flags: Vec<&str> = flags
+        .iter()
+        .map(|flag| if *flag { "1" } else { "0" })
+        .collect();
+    return tpc::source_hash(&format!(
+        "{}\0{}\0{}\0{}",
+        env!("CARGO_PKG_VERSION"),
+        tpc::VERSION,
+        flags.join(""),
+        source
+    ));
+}
+
+fn entry(directory: &Path, key: u64) -> PathBuf {
+    return directory.join(format!("{:016x}.tpc", key));
+}
+
+// The program cached under `key`, if there is one compiled from `source`.
+pub fn load(directory: &Path, key: u64, source: &str) -> Option<Prototype> {
+    let bytes = std::fs::read(entry(directory, key)).ok()?;
+    let compiled = tpc::read(&bytes).ok()?;
+    if compiled.source_hash != Some(tpc::source_hash(source)) {
+        return None;
+    }
+    return Some(compiled.program);
+}
+
+pub fn store(directory: &Path, key: u64, source: &str, program: &Prototype) {
+    if std::fs::create_dir_all(directory).is_err() {
+        return;
+    }
+    // Written aside and renamed into place, so a run reading the entry never sees
+    // half of it.
+    let partial = directory.join(format!("{:016x}.{}.partial", key, std::process::id()));
+    let bytes = tpc::write(program, Some(tpc::source_hash(source)));
+    if std::fs::write(&partial, bytes).is_err()
+        || std::fs::rename(&partial, entry(directory, key)).is_err()
+    {
+        let _ = std::fs::remove_file(&partial);
+    }
+}
+
+// Removes every cached program, returning how many there were.
+pub fn clear(directory: &Path) -> Result<usize, String> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(0),
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if !(name.ends_with(".tpc") || name.ends_with(".partial")) {
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(_) => removed += 1,
+            Err(e) => return Err(format!("Unable to remove {} : {}", path.display(), e)),
+        }
+    }
+    return Ok(removed);
+}
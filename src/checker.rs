@@ -0,0 +1,395 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    errors::Error,
+    expressions::Expression,
+    filesystem::FileHandle,
+    generators::GeneratorRef,
+    natives::{self, Permissions},
+    scanner::{Token, TokenType},
+    statements::Statement,
+    strings,
+    types::{Signature, Type},
+    values::Value,
+};
+
+// The static pass that runs between parsing and execution. It only reports what
+// is certain to fail given the annotations in the program: everything it can not
+// pin down has type `Any`, so unannotated code is never rejected.
+pub struct Checker {
+    scopes: Vec<HashMap<String, Type>>,
+    // The name and declared return type of each function being checked, innermost last.
+    functions: Vec<(String, Type)>,
+    // Enums declared anywhere in the program; annotations may name any of them.
+    enums: HashSet<String>,
+    errors: Vec<Error>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        let globals = natives::globals(Permissions::default())
+            .iter()
+            .map(|(name, value)| (name.to_string(), Type::of(value)))
+            .collect();
+        return Checker {
+            scopes: vec![globals],
+            functions: Vec::new(),
+            enums: natives::enum_names(),
+            errors: Vec::new(),
+        };
+    }
+
+    // Checks a whole program, returning every error found.
+    pub fn check(mut self: Self, statements: &Vec<Statement>) -> Vec<Error> {
+        collect_enums(statements, &mut self.enums);
+        for statement in statements {
+            self.statement(statement);
+        }
+        return self.errors;
+    }
+
+    fn report(self: &mut Self, token: &Token, message: String) {
+        self.errors.push(Error::TypeError(token.clone(), message));
+    }
+
+    fn declare(self: &mut Self, name: &Token, ty: Type) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.lexeme.clone(), ty);
+    }
+
+    fn lookup(self: &Self, name: &Token) -> Type {
+        for scope in self.scopes.iter().rev() {
+            if let Some(ty) = scope.get(&name.lexeme) {
+                return ty.clone();
+            }
+        }
+        return Type::Any;
+    }
+
+    fn forget(self: &mut Self, name: &Token) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(ty) = scope.get_mut(&name.lexeme) {
+                *ty = Type::Any;
+                return;
+            }
+        }
+    }
+
+    // An annotation as the checker uses it: names that are neither built in nor a
+    // declared enum are reported and then treated as `Any`.
+    fn resolve(self: &mut Self, ty: &Type, token: &Token) -> Type {
+        match ty {
+            Type::Named(name) if !self.enums.contains(name) => {
+                self.report(token, format!("unknown type '{}'", name));
+                return Type::Any;
+            }
+            Type::Nullable(inner) => match self.resolve(inner, token) {
+                Type::Any => return Type::Any,
+                inner => return Type::Nullable(Box::new(inner)),
+            },
+            other => return other.clone(),
+        }
+    }
+
+    fn statement(self: &mut Self, statement: &Statement) {
+        match statement {
+            Statement::ExpressionStatement(expression) | Statement::PrintStatement(expression) => {
+                self.expression(expression);
+            }
+            Statement::Variable(name, annotation, initializer)
+            | Statement::Const(name, annotation, initializer) => {
+                let actual = self.expression(initializer);
+                match annotation {
+                    Some(declared) => {
+                        let declared = self.resolve(declared, name);
+                        if !declared.accepts(&actual) {
+                            self.report(
+                                name,
+                                format!(
+                                    "'{}' is declared {} but initialized with {}",
+                                    name.lexeme,
+                                    declared.to_string(),
+                                    actual.to_string()
+                                ),
+                            );
+                        }
+                        self.declare(name, declared);
+                    }
+                    // Constants keep the type of their value; variables may be
+                    // reassigned anything.
+                    None if matches!(statement, Statement::Const(..)) => self.declare(name, actual),
+                    None => self.declare(name, Type::Any),
+                }
+            }
+            Statement::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                for statement in statements.iter() {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+            }
+            Statement::Enum(name, _) => self.declare(name, Type::Any),
+            Statement::Match(_, subject, arms) => {
+                self.expression(subject);
+                for (pattern, body) in arms {
+                    self.scopes.push(HashMap::new());
+                    for name in pattern.bindings() {
+                        self.declare(&name, Type::Any);
+                    }
+                    self.statement(body);
+                    self.scopes.pop();
+                }
+            }
+            Statement::ForIn(_, variable, iterable, body) => {
+                let item = match self.expression(iterable) {
+                    Type::String => Type::String,
+                    Type::Range => Type::Int,
+                    _ => Type::Any,
+                };
+                self.scopes.push(HashMap::new());
+                self.declare(variable, item);
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Statement::Function(name, params, body, is_generator, signature) => {
+                let signature = Signature {
+                    params: signature
+                        .params
+                        .iter()
+                        .map(|ty| self.resolve(ty, name))
+                        .collect(),
+                    returns: self.resolve(&signature.returns, name),
+                };
+                let returns = if *is_generator {
+                    Type::Any
+                } else {
+                    signature.returns.clone()
+                };
+                self.declare(name, Type::Function(Some(Box::new(signature.clone()))));
+                self.scopes.push(HashMap::new());
+                for (param, ty) in params.iter().zip(&signature.params) {
+                    self.declare(param, ty.clone());
+                }
+                self.functions.push((name.lexeme.clone(), returns));
+                for statement in body.iter() {
+                    self.statement(statement);
+                }
+                self.functions.pop();
+                self.scopes.pop();
+            }
+            Statement::Return(keyword, value) => {
+                let actual = self.expression(value);
+                if let Some((name, declared)) = self.functions.last().cloned() {
+                    if !declared.accepts(&actual) {
+                        self.report(
+                            keyword,
+                            format!(
+                                "{}() is declared to return {} but returns {}",
+                                name,
+                                declared.to_string(),
+                                actual.to_string()
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn expression(self: &mut Self, expression: &Expression) -> Type {
+        match expression {
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                return Type::of_binary(&left, operator, &right);
+            }
+            Expression::Unary { operator, right } => {
+                let right = self.expression(right);
+                match operator.token_type {
+                    TokenType::BANG => return Type::Bool,
+                    _ if right.is_numeric() => return right,
+                    _ => return Type::Any,
+                }
+            }
+            Expression::Grouping { expression } => return self.expression(expression),
+            Expression::Literal { value } => return Type::of(value),
+            Expression::Variable { name, .. } => return self.lookup(name),
+            Expression::Assign { name, value, .. } => {
+                let actual = self.expression(value);
+                let declared = self.lookup(name);
+                if let Type::Function(Some(_)) | Type::Module(_) = declared {
+                    // Functions and modules are bound by declarations rather than
+                    // annotations, so reassigning them is allowed and forgets what
+                    // was known about them.
+                    self.forget(name);
+                } else if !declared.accepts(&actual) {
+                    self.report(
+                        name,
+                        format!(
+                            "can not assign {} to '{}', which is declared {}",
+                            actual.to_string(),
+                            name.lexeme,
+                            declared.to_string()
+                        ),
+                    );
+                }
+                return actual;
+            }
+            Expression::Get { object, name } => {
+                let object = self.expression(object);
+                return self.property(&object, name);
+            }
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let function = self.expression(callee);
+                let arguments: Vec<Type> = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect();
+                let signature = match function {
+                    Type::Function(Some(signature)) => signature,
+                    _ => return Type::Any,
+                };
+                if arguments.len() != signature.params.len() {
+                    self.errors.push(Error::ArityMismatch(
+                        paren.clone(),
+                        signature.params.len(),
+                        arguments.len(),
+                    ));
+                    return signature.returns;
+                }
+                for (position, (param, argument)) in
+                    signature.params.iter().zip(&arguments).enumerate()
+                {
+                    if !param.accepts(argument) {
+                        self.report(
+                            paren,
+                            format!(
+                                "argument {} of {}() must be {} but is {}",
+                                position + 1,
+                                callee_name(callee),
+                                param.to_string(),
+                                argument.to_string()
+                            ),
+                        );
+                    }
+                }
+                return signature.returns;
+            }
+            Expression::List { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+                return Type::List;
+            }
+            Expression::Map { entries } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                return Type::Map;
+            }
+            Expression::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+                return Type::Any;
+            }
+            Expression::Yield { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+                return Type::Any;
+            }
+            Expression::Range {
+                start, end, step, ..
+            } => {
+                self.expression(start);
+                self.expression(end);
+                if let Some(step) = step {
+                    self.expression(step);
+                }
+                return Type::Range;
+            }
+        }
+    }
+
+    // The type of `object.name`, reporting members that can not exist.
+    fn property(self: &mut Self, object: &Type, name: &Token) -> Type {
+        let method = |arity: usize| {
+            Type::Function(Some(Box::new(Signature {
+                params: vec![Type::Any; arity],
+                returns: Type::Any,
+            })))
+        };
+        match object {
+            Type::Module(module) => {
+                if let Some(native) = module.function(name.symbol) {
+                    return method(native.arity);
+                }
+                if module.constant(name.symbol).is_some() {
+                    return Type::Float;
+                }
+                // The same error the lookup would raise at runtime.
+                self.errors.push(Error::UndefinedProperty(
+                    Value::Module(module.clone()),
+                    name.clone(),
+                ));
+                return Type::Any;
+            }
+            Type::String => {
+                if let Some(arity) = strings::method_arity(name.symbol) {
+                    return method(arity);
+                }
+            }
+            Type::File if FileHandle::has_method(name.symbol) => return method(0),
+            Type::Generator if GeneratorRef::has_method(name.symbol) => {
+                return Type::Function(None)
+            }
+            Type::Any | Type::Named(_) | Type::Nullable(_) => return Type::Any,
+            _ => {}
+        }
+        self.report(
+            name,
+            format!("{} has no property '{}'", object.to_string(), name.lexeme),
+        );
+        return Type::Any;
+    }
+}
+
+pub(crate) fn callee_name(callee: &Expression) -> String {
+    match callee {
+        Expression::Variable { name, .. } | Expression::Get { name, .. } => {
+            return name.lexeme.clone()
+        }
+        _ => return "function".to_string(),
+    }
+}
+
+pub(crate) fn collect_enums(statements: &[Statement], enums: &mut HashSet<String>) {
+    for statement in statements {
+        match statement {
+            Statement::Enum(name, _) => {
+                enums.insert(name.lexeme.clone());
+            }
+            Statement::Block(body) | Statement::Function(_, _, body, _, _) => {
+                collect_enums(body, enums)
+            }
+            Statement::Match(_, _, arms) => {
+                for (_, body) in arms {
+                    collect_enums(std::slice::from_ref(&**body), enums);
+                }
+            }
+            Statement::ForIn(_, _, _, body) => collect_enums(std::slice::from_ref(&**body), enums),
+            _ => {}
+        }
+    }
+}
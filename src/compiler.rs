@@ -0,0 +1,714 @@
+use std::{collections::HashSet, rc::Rc};
+
+use crate::{
+    bytecode::{Capture, Chunk, Op, Prototype},
+    expressions::Expression,
+    patterns::Pattern,
+    resolver::Resolution,
+    scanner::Token,
+    statements::Statement,
+    values::Value,
+};
+
+// How the compiled code reaches a variable.
+#[derive(Clone, Copy)]
+enum Access {
+    Slot(u32),
+    Cell(u32),
+    Upvalue(u32),
+    Global,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    place: Access,
+    // A captured function or enum whose cell was made when its scope was entered,
+    // so that functions declared earlier in the scope can already refer to it.
+    pending: bool,
+}
+
+// A function being compiled. Its stack slots and locals mirror the environments
+// the tree-walker would create, one scope per block, loop iteration and match arm.
+struct FunctionState {
+    name: String,
+    arity: usize,
+    chunk: Chunk,
+    cells: usize,
+    captures: Vec<Capture>,
+    is_generator: bool,
+    locals: Vec<Local>,
+    // The scope depth of each stack slot in use.
+    slots: Vec<usize>,
+    depth: usize,
+    // Names used by functions nested in this one; locals with these names are
+    // kept in cells so the closures share them.
+    captured: HashSet<String>,
+}
+
+// Compiles checked statements to bytecode for the VM. Top level declarations are
+// globals looked up by name, exactly like the tree-walker's; everything else is a
+// stack slot, or a cell when an inner function captures it.
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+    line: usize,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        return Compiler {
+            functions: Vec::new(),
+            line: 0,
+        };
+    }
+
+    // The whole program as a function of no arguments.
+    pub fn compile(mut self: Self, statements: &[Statement]) -> Prototype {
+        // Top level declarations are globals, so the script's scope is never opened.
+        self.begin_function("script".to_string(), 0, false, statements);
+        for statement in statements {
+            let start = self.current().chunk.code.len();
+            self.statement(statement);
+            if let Statement::PrintStatement(_) = statement {
+                let end = self.current().chunk.code.len();
+                self.current().chunk.recoverable.push((start, end));
+            }
+        }
+        return self.end_function();
+    }
+
+    fn current(self: &mut Self) -> &mut FunctionState {
+        return self.functions.last_mut().unwrap();
+    }
+
+    fn emit(self: &mut Self, op: Op) -> usize {
+        let line = self.line;
+        return self.current().chunk.emit(op, line);
+    }
+
+    fn token(self: &mut Self, token: &Token) -> u32 {
+        self.line = token.line_number;
+        return self.current().chunk.token(token);
+    }
+
+    fn constant(self: &mut Self, value: Value) {
+        let index = self.current().chunk.constant(value);
+        self.emit(Op::Constant(index));
+    }
+
+    fn here(self: &mut Self) -> u32 {
+        return self.current().chunk.code.len() as u32;
+    }
+
+    // Points the jump at `at` to the next instruction.
+    fn patch(self: &mut Self, at: usize) {
+        let target = self.here();
+        let code = &mut self.current().chunk.code;
+        code[at] = match code[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::Next(_) => Op::Next(target),
+            Op::Match(slot, pattern, enums, _) => Op::Match(slot, pattern, enums, target),
+            other => other,
+        };
+    }
+
+    fn begin_function(
+        self: &mut Self,
+        name: String,
+        arity: usize,
+        is_generator: bool,
+        body: &[Statement],
+    ) {
+        let mut captured = HashSet::new();
+        nested_references(body, &mut captured);
+        self.functions.push(FunctionState {
+            name: name,
+            arity: arity,
+            chunk: Chunk::default(),
+            cells: 0,
+            captures: Vec::new(),
+            is_generator: is_generator,
+            locals: Vec::new(),
+            // Slot 0 holds the function being called.
+            slots: vec![0],
+            depth: 0,
+            captured: captured,
+        });
+    }
+
+    fn end_function(self: &mut Self) -> Prototype {
+        self.constant(Value::Nil);
+        self.emit(Op::Return);
+        let function = self.functions.pop().unwrap();
+        return Prototype {
+            name: function.name,
+            arity: function.arity,
+            chunk: function.chunk,
+            cells: function.cells,
+            captures: function.captures,
+            is_generator: function.is_generator,
+        };
+    }
+
+    // Opens a scope, making cells up front for the captured functions and enums it
+    // declares. Variables get theirs when they are declared, as functions declared
+    // before them do not see them.
+    fn begin_scope(self: &mut Self, statements: &[Statement]) {
+        self.current().depth += 1;
+        for statement in statements {
+            if let Statement::Function(name, ..) | Statement::Enum(name, _) = statement {
+                if self.current().captured.contains(&name.lexeme) {
+                    let cell = self.new_cell();
+                    let depth = self.current().depth;
+                    self.current().locals.push(Local {
+                        name: name.lexeme.clone(),
+                        depth: depth,
+                        place: Access::Cell(cell),
+                        pending: true,
+                    });
+                }
+            }
+        }
+    }
+
+    fn end_scope(self: &mut Self) {
+        let function = self.current();
+        let depth = function.depth;
+        function.depth -= 1;
+        function.locals.retain(|local| local.depth < depth);
+        let count = function.slots.iter().filter(|slot| **slot == depth).count();
+        function.slots.truncate(function.slots.len() - count);
+        match count {
+            0 => {}
+            1 => {
+                self.emit(Op::Pop);
+            }
+            count => {
+                self.emit(Op::PopN(count as u32));
+            }
+        }
+    }
+
+    fn new_cell(self: &mut Self) -> u32 {
+        let function = self.current();
+        function.cells += 1;
+        let cell = (function.cells - 1) as u32;
+        self.emit(Op::NewCell(cell));
+        return cell;
+    }
+
+    // Gives the value on top of the stack a slot, naming it `name` in the current scope.
+    fn declare_local(self: &mut Self, name: &Token) {
+        let slot = self.push_slot();
+        self.bind(name, slot, true);
+    }
+
+    fn push_slot(self: &mut Self) -> u32 {
+        let function = self.current();
+        function.slots.push(function.depth);
+        return (function.slots.len() - 1) as u32;
+    }
+
+    // Names the value in `slot`; a captured one is copied into its cell, which is
+    // where it is read from from now on.
+    fn bind(self: &mut Self, name: &Token, slot: u32, on_top: bool) {
+        let function = self.current();
+        let depth = function.depth;
+        if !function.captured.contains(&name.lexeme) {
+            function.locals.push(Local {
+                name: name.lexeme.clone(),
+                depth: depth,
+                place: Access::Slot(slot),
+                pending: false,
+            });
+            return;
+        }
+        let pending = function
+            .locals
+            .iter_mut()
+            .rev()
+            .find(|local| local.pending && local.depth == depth && local.name == name.lexeme);
+        let cell = match pending {
+            Some(local) => {
+                local.pending = false;
+                match local.place {
+                    Access::Cell(cell) => cell,
+                    _ => unreachable!(),
+                }
+            }
+            None => {
+                let cell = self.new_cell();
+                self.current().locals.push(Local {
+                    name: name.lexeme.clone(),
+                    depth: depth,
+                    place: Access::Cell(cell),
+                    pending: false,
+                });
+                cell
+            }
+        };
+        if on_top {
+            self.emit(Op::SetCell(cell));
+        } else {
+            self.emit(Op::GetLocal(slot));
+            self.emit(Op::SetCell(cell));
+            self.emit(Op::Pop);
+        }
+    }
+
+    // Binds the value on top of the stack to `name`: a global at the top level of
+    // the program, a local anywhere else.
+    fn declare(self: &mut Self, name: &Token, constant: bool) {
+        if self.functions.len() == 1 && self.current().depth == 0 {
+            let name = self.token(name);
+            match constant {
+                true => self.emit(Op::DefineConstant(name)),
+                false => self.emit(Op::DefineGlobal(name)),
+            };
+            return;
+        }
+        self.declare_local(name);
+    }
+
+    fn resolve(self: &mut Self, function: usize, name: &str) -> Access {
+        let found = self.functions[function]
+            .locals
+            .iter()
+            .rev()
+            .find(|local| local.name == name)
+            .map(|local| local.place);
+        if let Some(place) = found {
+            return place;
+        }
+        if function == 0 {
+            return Access::Global;
+        }
+        let capture = match self.resolve(function - 1, name) {
+            Access::Cell(cell) => Capture::Cell(cell),
+            Access::Upvalue(upvalue) => Capture::Upvalue(upvalue),
+            // Names used by inner functions are always cells, so a slot is never
+            // found here.
+            Access::Slot(_) | Access::Global => return Access::Global,
+        };
+        let captures = &mut self.functions[function].captures;
+        match captures.iter().position(|existing| *existing == capture) {
+            Some(index) => return Access::Upvalue(index as u32),
+            None => {
+                captures.push(capture);
+                return Access::Upvalue((captures.len() - 1) as u32);
+            }
+        }
+    }
+
+    // Names the resolver found to be globals are looked up by name, whatever
+    // locals of the same name come later.
+    fn access(self: &mut Self, name: &Token, resolution: Resolution) -> Access {
+        if resolution == Resolution::Global {
+            return Access::Global;
+        }
+        return self.resolve(self.functions.len() - 1, &name.lexeme);
+    }
+
+    fn get_variable(self: &mut Self, name: &Token, resolution: Resolution) {
+        let access = self.access(name, resolution);
+        let token = self.token(name);
+        match access {
+            Access::Slot(slot) => self.emit(Op::GetLocal(slot)),
+            Access::Cell(cell) => self.emit(Op::GetCell(cell, token)),
+            Access::Upvalue(upvalue) => self.emit(Op::GetUpvalue(upvalue, token)),
+            Access::Global => self.emit(Op::GetGlobal(token)),
+        };
+    }
+
+    fn set_variable(self: &mut Self, name: &Token, resolution: Resolution) {
+        let access = self.access(name, resolution);
+        let token = self.token(name);
+        match access {
+            Access::Slot(slot) => self.emit(Op::SetLocal(slot)),
+            Access::Cell(cell) => self.emit(Op::SetCell(cell)),
+            Access::Upvalue(upvalue) => self.emit(Op::SetUpvalue(upvalue)),
+            Access::Global => self.emit(Op::SetGlobal(token)),
+        };
+    }
+
+    fn statement(self: &mut Self, statement: &Statement) {
+        if let Some(line) = statement_line(statement) {
+            self.line = line;
+        }
+        match statement {
+            Statement::ExpressionStatement(expression) => {
+                self.expression(expression);
+                self.emit(Op::Pop);
+            }
+            Statement::PrintStatement(expression) => {
+                self.expression(expression);
+                self.emit(Op::Print);
+            }
+            Statement::Variable(name, _, initializer) => {
+                self.expression(initializer);
+                self.declare(name, false);
+            }
+            Statement::Const(name, _, initializer) => {
+                self.expression(initializer);
+                self.declare(name, true);
+            }
+            Statement::Block(statements) => {
+                self.begin_scope(statements);
+                for statement in statements.iter() {
+                    self.statement(statement);
+                }
+                self.end_scope();
+            }
+            Statement::Enum(name, variants) => {
+                let variants = variants
+                    .iter()
+                    .map(|(variant, fields)| (variant.symbol, fields.len()))
+                    .collect();
+                self.constant(Value::EnumType(name.symbol, variants));
+                self.declare(name, false);
+            }
+            Statement::Match(keyword, subject, arms) => {
+                self.match_statement(keyword, subject, arms)
+            }
+            Statement::ForIn(keyword, name, iterable, body) => {
+                self.expression(iterable);
+                let keyword = self.token(keyword);
+                self.emit(Op::Iterate(keyword));
+                let start = self.here();
+                let exit = self.emit(Op::Next(0));
+                self.begin_scope(&[]);
+                self.declare_local(name);
+                self.statement(body);
+                self.end_scope();
+                self.emit(Op::Jump(start));
+                self.patch(exit);
+            }
+            Statement::Function(name, params, body, is_generator, _) => {
+                self.function(name, params, body, *is_generator);
+                self.declare(name, false);
+            }
+            Statement::Return(keyword, value) => {
+                self.expression(value);
+                self.line = keyword.line_number;
+                self.emit(Op::Return);
+            }
+        }
+    }
+
+    fn function(
+        self: &mut Self,
+        name: &Token,
+        params: &Vec<Token>,
+        body: &Rc<Vec<Statement>>,
+        is_generator: bool,
+    ) {
+        self.line = name.line_number;
+        self.begin_function(name.lexeme.clone(), params.len(), is_generator, body);
+        // Parameters and the body share a scope, as they share an environment.
+        // Arguments arrive in the slots after the function itself.
+        self.begin_scope(body);
+        for param in params {
+            let slot = self.push_slot();
+            self.bind(param, slot, false);
+        }
+        for statement in body.iter() {
+            self.statement(statement);
+        }
+        let prototype = self.end_function();
+        self.line = name.line_number;
+        let chunk = &mut self.current().chunk;
+        chunk.functions.push(Rc::new(prototype));
+        let index = (chunk.functions.len() - 1) as u32;
+        self.emit(Op::Closure(index));
+    }
+
+    fn match_statement(
+        self: &mut Self,
+        keyword: &Token,
+        subject: &Expression,
+        arms: &Vec<(Pattern, Rc<Statement>)>,
+    ) {
+        self.begin_scope(&[]);
+        self.expression(subject);
+        let depth = self.current().depth;
+        self.current().slots.push(depth);
+        let slot = (self.current().slots.len() - 1) as u32;
+        let mut exits = Vec::new();
+        for (pattern, body) in arms {
+            let enums = pattern.enum_names();
+            for name in &enums {
+                self.get_variable(name, Resolution::Unresolved);
+            }
+            let chunk = &mut self.current().chunk;
+            chunk.patterns.push(pattern.clone());
+            let index = (chunk.patterns.len() - 1) as u32;
+            let next = self.emit(Op::Match(slot, index, enums.len() as u32, 0));
+            self.begin_scope(&[]);
+            let bindings = pattern.bindings();
+            let slots: Vec<u32> = bindings.iter().map(|_| self.push_slot()).collect();
+            for (name, slot) in bindings.iter().zip(slots) {
+                self.bind(name, slot, false);
+            }
+            self.statement(body);
+            self.end_scope();
+            exits.push(self.emit(Op::Jump(0)));
+            self.patch(next);
+        }
+        let keyword = self.token(keyword);
+        self.emit(Op::NoMatch(keyword, slot));
+        for exit in exits {
+            self.patch(exit);
+        }
+        self.end_scope();
+    }
+
+    fn expression(self: &mut Self, expression: &Expression) {
+        match expression {
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left);
+                self.expression(right);
+                let operator = self.token(operator);
+                self.emit(Op::Binary(operator));
+            }
+            Expression::Unary { operator, right } => {
+                self.expression(right);
+                let operator = self.token(operator);
+                self.emit(Op::Unary(operator));
+            }
+            Expression::Grouping { expression } => self.expression(expression),
+            Expression::Literal { value } => self.constant(value.clone()),
+            Expression::Variable { name, resolution } => self.get_variable(name, resolution.get()),
+            Expression::Assign {
+                name,
+                value,
+                resolution,
+            } => {
+                self.expression(value);
+                self.set_variable(name, resolution.get());
+            }
+            Expression::Get { object, name } => {
+                self.expression(object);
+                let name = self.token(name);
+                self.emit(Op::GetProperty(name));
+            }
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+                let paren = self.token(paren);
+                self.emit(Op::Call(arguments.len() as u32, paren));
+            }
+            Expression::List { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+                self.emit(Op::List(elements.len() as u32));
+            }
+            Expression::Map { entries } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                self.emit(Op::Map(entries.len() as u32));
+            }
+            Expression::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                self.expression(object);
+                self.expression(index);
+                let bracket = self.token(bracket);
+                self.emit(Op::Index(bracket));
+            }
+            Expression::Yield { keyword, value } => {
+                match value {
+                    Some(value) => self.expression(value),
+                    None => self.constant(Value::Nil),
+                }
+                self.line = keyword.line_number;
+                self.emit(Op::Yield);
+            }
+            Expression::Range {
+                start,
+                operator,
+                end,
+                step,
+            } => {
+                self.expression(start);
+                self.expression(end);
+                if let Some(step) = step {
+                    self.expression(step);
+                }
+                let operator = self.token(operator);
+                self.emit(Op::Range(operator, step.is_some()));
+            }
+        }
+    }
+}
+
+// The line a statement starts on, for the instructions compiled before any of its
+// tokens are reached. Literals carry no line, so some expressions have none.
+fn statement_line(statement: &Statement) -> Option<usize> {
+    match statement {
+        Statement::Variable(name, _, _)
+        | Statement::Const(name, _, _)
+        | Statement::Enum(name, _)
+        | Statement::Function(name, _, _, _, _) => return Some(name.line_number),
+        Statement::Match(keyword, _, _)
+        | Statement::ForIn(keyword, _, _, _)
+        | Statement::Return(keyword, _) => return Some(keyword.line_number),
+        Statement::ExpressionStatement(expression) | Statement::PrintStatement(expression) => {
+            return expression_line(expression)
+        }
+        Statement::Block(statements) => return statements.first().and_then(statement_line),
+    }
+}
+
+fn expression_line(expression: &Expression) -> Option<usize> {
+    match expression {
+        Expression::Binary { left, operator, .. } => {
+            return expression_line(left).or(Some(operator.line_number))
+        }
+        Expression::Unary { operator, .. } => return Some(operator.line_number),
+        Expression::Grouping { expression } => return expression_line(expression),
+        Expression::Literal { .. } => return None,
+        Expression::Variable { name, .. } | Expression::Assign { name, .. } => {
+            return Some(name.line_number)
+        }
+        Expression::Get { object, name } => {
+            return expression_line(object).or(Some(name.line_number))
+        }
+        Expression::Call { callee, paren, .. } => {
+            return expression_line(callee).or(Some(paren.line_number))
+        }
+        Expression::List { elements } => return elements.iter().find_map(expression_line),
+        Expression::Map { entries } => {
+            return entries.iter().find_map(|(key, value)| {
+                return expression_line(key).or_else(|| expression_line(value));
+            })
+        }
+        Expression::Index {
+            object, bracket, ..
+        } => return expression_line(object).or(Some(bracket.line_number)),
+        Expression::Yield { keyword, .. } => return Some(keyword.line_number),
+        Expression::Range {
+            start, operator, ..
+        } => return expression_line(start).or(Some(operator.line_number)),
+    }
+}
+
+// Every name referred to inside the functions declared in `statements`.
+fn nested_references(statements: &[Statement], names: &mut HashSet<String>) {
+    for statement in statements {
+        match statement {
+            Statement::Function(_, _, body, _, _) => references(body, names),
+            Statement::Block(body) => nested_references(body, names),
+            Statement::Match(_, _, arms) => {
+                for (_, body) in arms {
+                    nested_references(std::slice::from_ref(&**body), names);
+                }
+            }
+            Statement::ForIn(_, _, _, body) => {
+                nested_references(std::slice::from_ref(&**body), names)
+            }
+            _ => {}
+        }
+    }
+}
+
+// Every name referred to anywhere in `statements`.
+fn references(statements: &[Statement], names: &mut HashSet<String>) {
+    for statement in statements {
+        match statement {
+            Statement::ExpressionStatement(expression)
+            | Statement::PrintStatement(expression)
+            | Statement::Variable(_, _, expression)
+            | Statement::Const(_, _, expression)
+            | Statement::Return(_, expression) => expression_references(expression, names),
+            Statement::Block(body) | Statement::Function(_, _, body, _, _) => {
+                references(body, names)
+            }
+            Statement::Match(_, subject, arms) => {
+                expression_references(subject, names);
+                for (pattern, body) in arms {
+                    for name in pattern.enum_names() {
+                        names.insert(name.lexeme);
+                    }
+                    references(std::slice::from_ref(&**body), names);
+                }
+            }
+            Statement::ForIn(_, _, iterable, body) => {
+                expression_references(iterable, names);
+                references(std::slice::from_ref(&**body), names);
+            }
+            Statement::Enum(..) => {}
+        }
+    }
+}
+
+fn expression_references(expression: &Expression, names: &mut HashSet<String>) {
+    match expression {
+        Expression::Variable { name, .. } => {
+            names.insert(name.lexeme.clone());
+        }
+        Expression::Assign { name, value, .. } => {
+            names.insert(name.lexeme.clone());
+            expression_references(value, names);
+        }
+        Expression::Binary { left, right, .. } => {
+            expression_references(left, names);
+            expression_references(right, names);
+        }
+        Expression::Unary { right, .. } => expression_references(right, names),
+        Expression::Grouping { expression } => expression_references(expression, names),
+        Expression::Get { object, .. } => expression_references(object, names),
+        Expression::Call {
+            callee, arguments, ..
+        } => {
+            expression_references(callee, names);
+            for argument in arguments {
+                expression_references(argument, names);
+            }
+        }
+        Expression::List { elements } => {
+            for element in elements {
+                expression_references(element, names);
+            }
+        }
+        Expression::Map { entries } => {
+            for (key, value) in entries {
+                expression_references(key, names);
+                expression_references(value, names);
+            }
+        }
+        Expression::Index { object, index, .. } => {
+            expression_references(object, names);
+            expression_references(index, names);
+        }
+        Expression::Yield { value, .. } => {
+            if let Some(value) = value {
+                expression_references(value, names);
+            }
+        }
+        Expression::Range {
+            start, end, step, ..
+        } => {
+            expression_references(start, names);
+            expression_references(end, names);
+            if let Some(step) = step {
+                expression_references(step, names);
+            }
+        }
+        Expression::Literal { .. } => {}
+    }
+}
@@ -0,0 +1,120 @@
+use crate::bytecode::{Capture, Chunk, Op, Prototype};
+
+// Renders a compiled program for `--dump-bytecode`: one line per instruction with
+// its offset, source line, name and operands, followed by every nested function.
+// Operands that index a table are shown with what they refer to.
+pub fn disassemble(prototype: &Prototype) -> String {
+    let mut out = String::new();
+    function(prototype, &mut out);
+    return out;
+}
+
+fn function(prototype: &Prototype, out: &mut String) {
+    let captures: Vec<String> = prototype
+        .captures
+        .iter()
+        .map(|capture| match capture {
+            Capture::Cell(cell) => format!("cell {}", cell),
+            Capture::Upvalue(upvalue) => format!("upvalue {}", upvalue),
+        })
+        .collect();
+    out.push_str(&format!(
+        "== <fn {}> arity {}, {} cells, captures [{}]{} ==\n",
+        prototype.name,
+        prototype.arity,
+        prototype.cells,
+        captures.join(", "),
+        if prototype.is_generator {
+            ", generator"
+        } else {
+            ""
+        }
+    ));
+    let chunk = &prototype.chunk;
+    for offset in 0..chunk.code.len() {
+        // Like a listing, the line is only repeated when it changes.
+        let line = if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+            "   |".to_string()
+        } else {
+            format!("{:4}", chunk.lines[offset])
+        };
+        out.push_str(&format!(
+            "{:04} {} {}\n",
+            offset,
+            line,
+            instruction(chunk, chunk.code[offset])
+        ));
+    }
+    out.push('\n');
+    for nested in chunk.functions.iter() {
+        function(nested, out);
+    }
+}
+
+fn instruction(chunk: &Chunk, op: Op) -> String {
+    let constant = |index: u32| {
+        format!(
+            "{:4} '{}'",
+            index,
+            chunk.constants[index as usize].to_repr()
+        )
+    };
+    let token = |index: u32| format!("{:4} '{}'", index, chunk.tokens[index as usize].lexeme);
+    match op {
+        Op::Constant(index) => return format!("{:<16}{}", "CONSTANT", constant(index)),
+        Op::Pop => return "POP".to_string(),
+        Op::PopN(count) => return format!("{:<16}{:4}", "POP_N", count),
+        Op::GetLocal(slot) => return format!("{:<16}{:4}", "GET_LOCAL", slot),
+        Op::SetLocal(slot) => return format!("{:<16}{:4}", "SET_LOCAL", slot),
+        Op::NewCell(cell) => return format!("{:<16}{:4}", "NEW_CELL", cell),
+        Op::GetCell(cell, name) => {
+            return format!("{:<16}{:4} {}", "GET_CELL", cell, token(name).trim_start())
+        }
+        Op::SetCell(cell) => return format!("{:<16}{:4}", "SET_CELL", cell),
+        Op::GetUpvalue(upvalue, name) => {
+            return format!(
+                "{:<16}{:4} {}",
+                "GET_UPVALUE",
+                upvalue,
+                token(name).trim_start()
+            )
+        }
+        Op::SetUpvalue(upvalue) => return format!("{:<16}{:4}", "SET_UPVALUE", upvalue),
+        Op::GetGlobal(name) => return format!("{:<16}{}", "GET_GLOBAL", token(name)),
+        Op::SetGlobal(name) => return format!("{:<16}{}", "SET_GLOBAL", token(name)),
+        Op::DefineGlobal(name) => return format!("{:<16}{}", "DEFINE_GLOBAL", token(name)),
+        Op::DefineConstant(name) => return format!("{:<16}{}", "DEFINE_CONSTANT", token(name)),
+        Op::Binary(operator) => return format!("{:<16}{}", "BINARY", token(operator)),
+        Op::Unary(operator) => return format!("{:<16}{}", "UNARY", token(operator)),
+        Op::GetProperty(name) => return format!("{:<16}{}", "GET_PROPERTY", token(name)),
+        Op::Index(_) => return "INDEX".to_string(),
+        Op::Call(count, _) => return format!("{:<16}{:4}", "CALL", count),
+        Op::Closure(index) => {
+            let name = &chunk.functions[index as usize].name;
+            return format!("{:<16}{:4} <fn {}>", "CLOSURE", index, name);
+        }
+        Op::List(count) => return format!("{:<16}{:4}", "LIST", count),
+        Op::Map(count) => return format!("{:<16}{:4}", "MAP", count),
+        Op::Range(operator, has_step) => {
+            let step = if has_step { " step" } else { "" };
+            return format!("{:<16}{}{}", "RANGE", token(operator), step);
+        }
+        Op::Print => return "PRINT".to_string(),
+        Op::Jump(target) => return format!("{:<16}{:4}", "JUMP", target),
+        Op::Iterate(_) => return "ITERATE".to_string(),
+        Op::Next(exit) => return format!("{:<16}{:4}", "NEXT", exit),
+        Op::Match(slot, pattern, enums, next) => {
+            return format!(
+                "{:<16}{:4} '{}' {} enums, else {}",
+                "MATCH",
+                slot,
+                chunk.patterns[pattern as usize].to_string(),
+                enums,
+                next
+            )
+        }
+        Op::NoMatch(_, slot) => return format!("{:<16}{:4}", "NO_MATCH", slot),
+        Op::Return => return "RETURN".to_string(),
+        Op::Yield => return "YIELD".to_string(),
+    }
+}
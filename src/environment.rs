@@ -1,26 +1,186 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, rc::Rc};
 
-use crate::scanner::LiteralValue;
+use crate::{
+    errors::Error,
+    gc,
+    resolver::Resolution,
+    scanner::Token,
+    symbols::{Symbol, SymbolMap},
+    values::Value,
+};
 
+// Values are kept in the order their names were defined, so a reference the
+// resolver has placed reads its slot directly; names are looked up by their
+// interned symbol rather than their text.
 pub struct Environment {
-    values: HashMap<String, LiteralValue>,
+    values: Vec<(Symbol, Value)>,
+    slots: SymbolMap<usize>,
+    // Names bound with `const`, mapped to the line they were declared on.
+    constants: SymbolMap<usize>,
+    pub(crate) enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Self {
-            values: HashMap::new(),
+            values: Vec::new(),
+            slots: SymbolMap::default(),
+            constants: SymbolMap::default(),
+            enclosing: None,
         }
     }
 
-    pub fn define(self: &mut Self, name: String, value: LiteralValue) {
-        self.values.insert(name, value);
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: Vec::new(),
+            slots: SymbolMap::default(),
+            constants: SymbolMap::default(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(self: &mut Self, name: &Token, value: Value) -> Result<(), Error> {
+        if let Some(line) = self.constants.get(&name.symbol) {
+            return Err(Error::ConstantReassignment(name.clone(), *line));
+        }
+        match self.slots.get(&name.symbol) {
+            Some(slot) => self.values[*slot].1 = value,
+            None => {
+                self.slots.insert(name.symbol, self.values.len());
+                self.values.push((name.symbol, value));
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn define_constant(self: &mut Self, name: &Token, value: Value) -> Result<(), Error> {
+        self.define(name, value)?;
+        self.constants.insert(name.symbol, name.line_number);
+        return Ok(());
+    }
+
+    pub fn assign(self: &mut Self, name: &Token, value: Value) -> Result<(), Error> {
+        if let Some(line) = self.constants.get(&name.symbol) {
+            return Err(Error::ConstantReassignment(name.clone(), *line));
+        }
+        match self.slots.get(&name.symbol) {
+            Some(slot) => {
+                self.values[*slot].1 = value;
+                return Ok(());
+            }
+            None => match &self.enclosing {
+                Some(enclosing) => return enclosing.borrow_mut().assign(name, value),
+                None => return Err(Error::InvalidToken(name.clone())),
+            },
+        }
+    }
+
+    // Reads `name` where the resolver found it. A slot that does not hold the name
+    // yet belongs to a declaration that has not run.
+    pub fn lookup(self: &Self, name: &Token, resolution: Resolution) -> Result<Value, Error> {
+        let found = match resolution {
+            Resolution::Unresolved => self.get(name.symbol).ok(),
+            Resolution::Global => match &self.enclosing {
+                Some(enclosing) => return enclosing.borrow().lookup(name, resolution),
+                None => self.get(name.symbol).ok(),
+            },
+            Resolution::Local(0, slot) => match self.values.get(slot) {
+                Some((symbol, value)) if *symbol == name.symbol => Some(value.clone()),
+                _ => None,
+            },
+            Resolution::Local(depth, slot) => match &self.enclosing {
+                Some(enclosing) => {
+                    return enclosing
+                        .borrow()
+                        .lookup(name, Resolution::Local(depth - 1, slot))
+                }
+                None => None,
+            },
+        };
+        match found {
+            Some(value) => return Ok(value),
+            None => return Err(Error::InvalidToken(name.clone())),
+        }
+    }
+
+    // Assigns `name` where the resolver found it.
+    pub fn assign_resolved(
+        self: &mut Self,
+        name: &Token,
+        resolution: Resolution,
+        value: Value,
+    ) -> Result<(), Error> {
+        match resolution {
+            Resolution::Unresolved => return self.assign(name, value),
+            Resolution::Global => match &self.enclosing {
+                Some(enclosing) => {
+                    return enclosing
+                        .borrow_mut()
+                        .assign_resolved(name, resolution, value)
+                }
+                None => return self.assign(name, value),
+            },
+            Resolution::Local(0, slot) => {
+                if let Some(line) = self.constants.get(&name.symbol) {
+                    return Err(Error::ConstantReassignment(name.clone(), *line));
+                }
+                match self.values.get_mut(slot) {
+                    Some((symbol, current)) if *symbol == name.symbol => {
+                        *current = value;
+                        return Ok(());
+                    }
+                    _ => return Err(Error::InvalidToken(name.clone())),
+                }
+            }
+            Resolution::Local(depth, slot) => match &self.enclosing {
+                Some(enclosing) => {
+                    return enclosing.borrow_mut().assign_resolved(
+                        name,
+                        Resolution::Local(depth - 1, slot),
+                        value,
+                    )
+                }
+                None => return Err(Error::InvalidToken(name.clone())),
+            },
+        }
+    }
+
+    pub(crate) fn trace(self: &Self, visit: &mut dyn FnMut(usize)) {
+        for (_, value) in self.values.iter() {
+            gc::trace_value(value, visit);
+        }
+        if let Some(enclosing) = &self.enclosing {
+            gc::trace_environment(enclosing, visit);
+        }
+    }
+
+    // Empties an environment the collector found unreachable.
+    pub(crate) fn release(self: &mut Self) {
+        self.values.clear();
+        self.slots.clear();
+        self.enclosing = None;
+    }
+
+    // Every name defined in this environment and the ones enclosing it.
+    pub fn names(self: &Self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .values
+            .iter()
+            .map(|(symbol, _)| symbol.name().to_string())
+            .collect();
+        if let Some(enclosing) = &self.enclosing {
+            names.extend(enclosing.borrow().names());
+        }
+        return names;
     }
 
-    pub fn get(self: &mut Self, name: String) -> Result<LiteralValue, String> {
-        match self.values.get(&name) {
-            Some(value) => Ok(value.clone()),
-            None => Err("Variable declaration not found ".to_string()),
+    pub fn get(self: &Self, name: Symbol) -> Result<Value, String> {
+        match self.slots.get(&name) {
+            Some(slot) => Ok(self.values[*slot].1.clone()),
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow().get(name),
+                None => Err("Variable declaration not found ".to_string()),
+            },
         }
     }
 }
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use crate::scanner::{LiteralValue, Token};
+use crate::{scanner::Token, values::Value};
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -14,10 +14,28 @@ pub enum Error {
     // AFTER SCANNING TOKEN
     InvalidToken(Token),
     ZeroDivisionError(Token),
-    InvalidUnaryOperation(LiteralValue, Token),
+    OverflowError(Token, String),
+    InvalidUnaryOperation(Value, Token),
     UnterminatedParenthesis(Token),
-    InvalidBinaryOperation(LiteralValue, Token, LiteralValue),
+    InvalidBinaryOperation(Value, Token, Value),
+    UnorderedComparison(Value, Token, Value),
     ExpectedAToken(Token, String),
+    ConstantReassignment(Token, usize),
+    UndefinedProperty(Value, Token),
+    NotCallable(Value, Token),
+    ArityMismatch(Token, usize, usize),
+    InvalidPattern(Token, String),
+    NoMatchingArm(Token, Value),
+    NotIterable(Value, Token),
+    IndexError(Token, String),
+    GeneratorError(Token, String),
+    InvalidRange(Token, String),
+    InvalidArgument(Token, String),
+    IoError(Token, String),
+    PermissionDenied(Token, String),
+    TypeError(Token, String),
+    ResolutionError(Token, String),
+    StackOverflow(Token, usize),
 
     // PARSING ERROR
     ParsingError(String, i128),
@@ -47,6 +65,9 @@ impl Error {
             Error::ZeroDivisionError(token) => {
                 format!("ZERO DIVISION ERROR at line {}", token.line_number)
             }
+            Error::OverflowError(token, msg) => {
+                format!("OVERFLOW ERROR : {}, at line {}", msg, token.line_number)
+            }
             Error::ParsingError(msg, line_number) => {
                 format!("PARSING ERROR : {} at line {}", msg, line_number)
             }
@@ -73,6 +94,17 @@ impl Error {
                     right.to_string()
                 )
             }
+            Error::UnorderedComparison(left, operator, right) => {
+                format!(
+                    "UNORDERED COMPARISON : {} {} and {} {} can not be compared with {}, at line {}",
+                    left.type_name(),
+                    left.to_repr(),
+                    right.type_name(),
+                    right.to_repr(),
+                    operator.lexeme,
+                    operator.line_number
+                )
+            }
             Error::InvalidUnaryOperation(value, operation) => {
                 format!(
                     "OPERATION {} is not defined for operands of type {}.",
@@ -83,6 +115,80 @@ impl Error {
             Error::ExpectedAToken(token, msg) => {
                 format!("EXPECTED A TOKEN : {} on line {}", msg, token.line_number)
             }
+            Error::ConstantReassignment(token, declared_at) => {
+                format!(
+                    "CANNOT REASSIGN CONSTANT : {} (declared at line {}), at line {}",
+                    token.lexeme, declared_at, token.line_number
+                )
+            }
+            Error::UndefinedProperty(value, name) => {
+                format!(
+                    "UNDEFINED PROPERTY : {} on {}, at line {}",
+                    name.lexeme,
+                    value.to_string(),
+                    name.line_number
+                )
+            }
+            Error::NotCallable(value, paren) => {
+                format!(
+                    "NOT CALLABLE : {}, at line {}",
+                    value.to_string(),
+                    paren.line_number
+                )
+            }
+            Error::ArityMismatch(paren, expected, got) => {
+                format!(
+                    "EXPECTED {} ARGUMENTS BUT GOT {}, at line {}",
+                    expected, got, paren.line_number
+                )
+            }
+            Error::InvalidPattern(token, msg) => {
+                format!("INVALID PATTERN : {}, at line {}", msg, token.line_number)
+            }
+            Error::NoMatchingArm(keyword, value) => {
+                format!(
+                    "NO MATCH ARM FOR VALUE : {}, at line {}",
+                    value.to_string(),
+                    keyword.line_number
+                )
+            }
+            Error::NotIterable(value, token) => {
+                format!(
+                    "NOT ITERABLE : {}, at line {}",
+                    value.to_string(),
+                    token.line_number
+                )
+            }
+            Error::IndexError(token, msg) => {
+                format!("INDEX ERROR : {}, at line {}", msg, token.line_number)
+            }
+            Error::GeneratorError(token, msg) => {
+                format!("GENERATOR ERROR : {}, at line {}", msg, token.line_number)
+            }
+            Error::InvalidRange(token, msg) => {
+                format!("INVALID RANGE : {}, at line {}", msg, token.line_number)
+            }
+            Error::InvalidArgument(token, msg) => {
+                format!("INVALID ARGUMENT : {}, at line {}", msg, token.line_number)
+            }
+            Error::IoError(token, msg) => {
+                format!("IO ERROR : {}, at line {}", msg, token.line_number)
+            }
+            Error::PermissionDenied(token, msg) => {
+                format!("PERMISSION DENIED : {}, at line {}", msg, token.line_number)
+            }
+            Error::TypeError(token, msg) => {
+                format!("TYPE ERROR : {}, at line {}", msg, token.line_number)
+            }
+            Error::ResolutionError(token, msg) => {
+                format!("RESOLUTION ERROR : {}, at line {}", msg, token.line_number)
+            }
+            Error::StackOverflow(paren, limit) => {
+                format!(
+                    "STACK OVERFLOW : more than {} nested calls, at line {}",
+                    limit, paren.line_number
+                )
+            }
             Error::NullExpression(line) => {
                 format!("TRYING TO EVALUATE NULL EXPRESSION on line {line}")
             }
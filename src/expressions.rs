@@ -1,7 +1,20 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
 use crate::{
     environment::Environment,
     errors::Error,
-    scanner::{LiteralValue, Token, TokenType},
+    filesystem::FileHandle,
+    generators::GeneratorRef,
+    numbers,
+    ranges::Range,
+    resolver::Resolution,
+    scanner::{Token, TokenType},
+    strings,
+    values::Value,
+    vm,
 };
 
 #[derive(PartialEq)]
@@ -19,10 +32,47 @@ pub enum Expression {
         expression: Box<Expression>,
     },
     Literal {
-        value: LiteralValue,
+        value: Value,
     },
+    // Both record where the resolver found the name.
     Variable {
         name: Token,
+        resolution: Cell<Resolution>,
+    },
+    Assign {
+        name: Token,
+        value: Box<Expression>,
+        resolution: Cell<Resolution>,
+    },
+    Get {
+        object: Box<Expression>,
+        name: Token,
+    },
+    Call {
+        callee: Box<Expression>,
+        paren: Token,
+        arguments: Vec<Expression>,
+    },
+    List {
+        elements: Vec<Expression>,
+    },
+    Map {
+        entries: Vec<(Expression, Expression)>,
+    },
+    Index {
+        object: Box<Expression>,
+        bracket: Token,
+        index: Box<Expression>,
+    },
+    Yield {
+        keyword: Token,
+        value: Option<Box<Expression>>,
+    },
+    Range {
+        start: Box<Expression>,
+        operator: Token,
+        end: Box<Expression>,
+        step: Option<Box<Expression>>,
     },
 }
 
@@ -53,13 +103,67 @@ impl Expression {
             Expression::Literal { value } => {
                 return format!("{}", value.to_string()).to_string();
             }
-            Expression::Variable { name } => {
+            Expression::Variable { name, .. } => {
                 return format!("VARIABLE : {}", name.lexeme);
             }
+            Expression::Assign { name, value, .. } => {
+                return format!("(= {} {})", name.lexeme, value.to_string());
+            }
+            Expression::Get { object, name } => {
+                return format!("(. {} {})", object.to_string(), name.lexeme);
+            }
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                let arguments: Vec<String> = arguments.iter().map(|arg| arg.to_string()).collect();
+                return format!("(CALL {} {})", callee.to_string(), arguments.join(" "));
+            }
+            Expression::List { elements } => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                return format!("(LIST {})", elements.join(" "));
+            }
+            Expression::Map { entries } => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("({} : {})", key.to_string(), value.to_string()))
+                    .collect();
+                return format!("(MAP {})", entries.join(" "));
+            }
+            Expression::Index { object, index, .. } => {
+                return format!("([] {} {})", object.to_string(), index.to_string());
+            }
+            Expression::Yield { value, .. } => match value {
+                Some(value) => return format!("(YIELD {})", value.to_string()),
+                None => return "(YIELD)".to_string(),
+            },
+            Expression::Range {
+                start,
+                operator,
+                end,
+                step,
+            } => match step {
+                Some(step) => {
+                    return format!(
+                        "({} {} {} {})",
+                        operator.lexeme,
+                        start.to_string(),
+                        end.to_string(),
+                        step.to_string()
+                    )
+                }
+                None => {
+                    return format!(
+                        "({} {} {})",
+                        operator.lexeme,
+                        start.to_string(),
+                        end.to_string()
+                    )
+                }
+            },
         }
     }
 
-    pub fn evaluate(self: &Self, environment: &mut Environment) -> Result<LiteralValue, Error> {
+    pub fn evaluate(self: &Self, environment: &Rc<RefCell<Environment>>) -> Result<Value, Error> {
         match self {
             Expression::Binary {
                 left,
@@ -69,367 +173,11 @@ impl Expression {
                 let left = left.evaluate(environment)?;
                 let right = right.evaluate(environment)?;
 
-                match (left, operator.token_type.clone(), right) {
-                    // ARITHMETIC
-                    (LiteralValue::IntValue(x), TokenType::PLUS, LiteralValue::IntValue(y)) => {
-                        return Ok(LiteralValue::IntValue(x + y));
-                    }
-                    (LiteralValue::FValue(x), TokenType::PLUS, LiteralValue::IntValue(y)) => {
-                        return Ok(LiteralValue::FValue(x + (y as f64)));
-                    }
-                    (LiteralValue::IntValue(x), TokenType::PLUS, LiteralValue::FValue(y)) => {
-                        return Ok(LiteralValue::FValue((x as f64) + y));
-                    }
-                    (LiteralValue::FValue(x), TokenType::PLUS, LiteralValue::FValue(y)) => {
-                        return Ok(LiteralValue::FValue(x + y));
-                    }
-                    (LiteralValue::IntValue(x), TokenType::MINUS, LiteralValue::IntValue(y)) => {
-                        return Ok(LiteralValue::IntValue(x - y));
-                    }
-                    (LiteralValue::FValue(x), TokenType::MINUS, LiteralValue::IntValue(y)) => {
-                        return Ok(LiteralValue::FValue(x - (y as f64)));
-                    }
-                    (LiteralValue::IntValue(x), TokenType::MINUS, LiteralValue::FValue(y)) => {
-                        return Ok(LiteralValue::FValue((x as f64) - y));
-                    }
-                    (LiteralValue::FValue(x), TokenType::MINUS, LiteralValue::FValue(y)) => {
-                        return Ok(LiteralValue::FValue(x - y));
-                    }
-                    (LiteralValue::IntValue(x), TokenType::STAR, LiteralValue::IntValue(y)) => {
-                        return Ok(LiteralValue::IntValue(x * y));
-                    }
-                    (LiteralValue::FValue(x), TokenType::STAR, LiteralValue::IntValue(y)) => {
-                        return Ok(LiteralValue::FValue(x * (y as f64)));
-                    }
-                    (LiteralValue::IntValue(x), TokenType::STAR, LiteralValue::FValue(y)) => {
-                        return Ok(LiteralValue::FValue((x as f64) * y));
-                    }
-                    (LiteralValue::FValue(x), TokenType::STAR, LiteralValue::FValue(y)) => {
-                        return Ok(LiteralValue::FValue(x * y));
-                    }
-                    (LiteralValue::IntValue(x), TokenType::SLASH, LiteralValue::IntValue(y)) => {
-                        if y == 0 {
-                            return Err(Error::ZeroDivisionError(operator.clone()));
-                        }
-                        return Ok(LiteralValue::FValue((x as f64 / y as f64) as f64));
-                    }
-                    (LiteralValue::FValue(x), TokenType::SLASH, LiteralValue::IntValue(y)) => {
-                        if y == 0 {
-                            return Err(Error::ZeroDivisionError(operator.clone()));
-                        }
-                        return Ok(LiteralValue::FValue(x / (y as f64)));
-                    }
-                    (LiteralValue::IntValue(x), TokenType::SLASH, LiteralValue::FValue(y)) => {
-                        if y == 0.0 {
-                            return Err(Error::ZeroDivisionError(operator.clone()));
-                        }
-                        return Ok(LiteralValue::FValue((x as f64) / y));
-                    }
-                    (LiteralValue::FValue(x), TokenType::SLASH, LiteralValue::FValue(y)) => {
-                        if y == 0.0 {
-                            return Err(Error::ZeroDivisionError(operator.clone()));
-                        }
-                        return Ok(LiteralValue::FValue(x / y));
-                    }
-                    (LiteralValue::StringValue(x), TokenType::STAR, LiteralValue::IntValue(y)) => {
-                        let mut ans = String::new();
-                        for _ in 0..y {
-                            ans.push_str(&x);
-                        }
-                        return Ok(LiteralValue::StringValue(ans));
-                    }
-                    (LiteralValue::IntValue(x), TokenType::STAR, LiteralValue::StringValue(y)) => {
-                        let mut ans = String::new();
-                        for _ in 0..x {
-                            ans.push_str(&y);
-                        }
-                        return Ok(LiteralValue::StringValue(ans));
-                    }
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::PLUS,
-                        LiteralValue::StringValue(y),
-                    ) => {
-                        return Ok(LiteralValue::StringValue(format!("{}{}", x, y)));
-                    }
-                    (LiteralValue::IntValue(x), TokenType::GREATER, LiteralValue::IntValue(y)) => {
-                        if x > y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::FValue(x), TokenType::GREATER, LiteralValue::IntValue(y)) => {
-                        if x > (y as f64) {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::IntValue(x), TokenType::GREATER, LiteralValue::FValue(y)) => {
-                        if (x as f64) > y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::FValue(x), TokenType::GREATER, LiteralValue::FValue(y)) => {
-                        if x > y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::IntValue(x),
-                        TokenType::GREATER_EQUAL,
-                        LiteralValue::IntValue(y),
-                    ) => {
-                        if x >= y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::FValue(x),
-                        TokenType::GREATER_EQUAL,
-                        LiteralValue::IntValue(y),
-                    ) => {
-                        if x >= (y as f64) {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::IntValue(x),
-                        TokenType::GREATER_EQUAL,
-                        LiteralValue::FValue(y),
-                    ) => {
-                        if (x as f64) >= y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::FValue(x),
-                        TokenType::GREATER_EQUAL,
-                        LiteralValue::FValue(y),
-                    ) => {
-                        if x >= y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::IntValue(x), TokenType::LESS, LiteralValue::IntValue(y)) => {
-                        if x < y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::FValue(x), TokenType::LESS, LiteralValue::IntValue(y)) => {
-                        if x < (y as f64) {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::IntValue(x), TokenType::LESS, LiteralValue::FValue(y)) => {
-                        if (x as f64) < y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::FValue(x), TokenType::LESS, LiteralValue::FValue(y)) => {
-                        if x < y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::IntValue(x),
-                        TokenType::LESS_EQUAL,
-                        LiteralValue::IntValue(y),
-                    ) => {
-                        if x <= y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::FValue(x), TokenType::LESS_EQUAL, LiteralValue::IntValue(y)) => {
-                        if x <= (y as f64) {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::IntValue(x), TokenType::LESS_EQUAL, LiteralValue::FValue(y)) => {
-                        if (x as f64) <= y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::FValue(x), TokenType::LESS_EQUAL, LiteralValue::FValue(y)) => {
-                        if x <= y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::IntValue(x),
-                        TokenType::BANG_EQUAL,
-                        LiteralValue::IntValue(y),
-                    ) => {
-                        if x != y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::FValue(x), TokenType::BANG_EQUAL, LiteralValue::IntValue(y)) => {
-                        if x != (y as f64) {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::IntValue(x), TokenType::BANG_EQUAL, LiteralValue::FValue(y)) => {
-                        if (x as f64) != y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::FValue(x), TokenType::BANG_EQUAL, LiteralValue::FValue(y)) => {
-                        if x != y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::IntValue(x),
-                        TokenType::EQUAL_EQUAL,
-                        LiteralValue::IntValue(y),
-                    ) => {
-                        if x == y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::FValue(x),
-                        TokenType::EQUAL_EQUAL,
-                        LiteralValue::IntValue(y),
-                    ) => {
-                        if x == (y as f64) {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::IntValue(x),
-                        TokenType::EQUAL_EQUAL,
-                        LiteralValue::FValue(y),
-                    ) => {
-                        if (x as f64) == y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::FValue(x), TokenType::EQUAL_EQUAL, LiteralValue::FValue(y)) => {
-                        if x == y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::BANG_EQUAL,
-                        LiteralValue::StringValue(y),
-                    ) => {
-                        if x != y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::EQUAL_EQUAL,
-                        LiteralValue::StringValue(y),
-                    ) => {
-                        if x == y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::GREATER,
-                        LiteralValue::StringValue(y),
-                    ) => {
-                        if x > y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (
-                        LiteralValue::StringValue(x),
-                        TokenType::LESS,
-                        LiteralValue::StringValue(y),
-                    ) => {
-                        if x < y {
-                            return Ok(LiteralValue::True);
-                        } else {
-                            return Ok(LiteralValue::False);
-                        }
-                    }
-                    (LiteralValue::IntValue(x), TokenType::MODULO, LiteralValue::IntValue(y)) => {
-                        return Ok(LiteralValue::IntValue(x % y));
-                    }
-                    (LiteralValue::FValue(x), TokenType::MODULO, LiteralValue::FValue(y)) => {
-                        return Ok(LiteralValue::FValue(x % y));
-                    }
-                    (left, _, right) => {
-                        return Err(Error::InvalidBinaryOperation(left, operator.clone(), right));
-                    }
-                }
+                return binary_operation(left, operator, right);
             }
             Expression::Unary { operator, right } => {
                 let right = right.evaluate(environment)?;
-                match (right.clone(), operator.token_type.clone()) {
-                    (LiteralValue::IntValue(x), TokenType::MINUS) => {
-                        return Ok(LiteralValue::IntValue(-x));
-                    }
-                    (value, TokenType::MINUS) => {
-                        return Err(Error::InvalidUnaryOperation(value, operator.clone()));
-                    }
-                    (mut any, TokenType::BANG) => match any.is_falsy() {
-                        Ok(value) => {
-                            return Ok(value);
-                        }
-                        Err(err) => Err(err),
-                    },
-                    (value, _) => {
-                        return Err(Error::InvalidUnaryOperation(value, operator.clone()));
-                    }
-                }
+                return unary_operation(operator, right);
             }
             Expression::Grouping { expression } => {
                 return expression.evaluate(environment);
@@ -437,12 +185,382 @@ impl Expression {
             Expression::Literal { value } => {
                 return Ok(value.clone());
             }
-            Expression::Variable { name } => match (*environment).get(name.lexeme.clone()) {
-                Ok(value) => {
-                    return Ok(value);
+            Expression::Variable { name, resolution } => {
+                return environment.borrow().lookup(name, resolution.get());
+            }
+            Expression::Assign {
+                name,
+                value,
+                resolution,
+            } => {
+                let value = value.evaluate(environment)?;
+                environment
+                    .borrow_mut()
+                    .assign_resolved(name, resolution.get(), value.clone())?;
+                return Ok(value);
+            }
+            Expression::Get { object, name } => {
+                let object = object.evaluate(environment)?;
+                return get_property(object, name);
+            }
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee = callee.evaluate(environment)?;
+                let mut values = Vec::new();
+                for argument in arguments {
+                    values.push(argument.evaluate(environment)?);
                 }
-                Err(_) => return Err(Error::InvalidToken(name.clone())),
-            },
+                return call_value(callee, paren, values);
+            }
+            Expression::List { elements } => {
+                let mut values = Vec::new();
+                for element in elements {
+                    values.push(element.evaluate(environment)?);
+                }
+                return Ok(Value::list(values));
+            }
+            Expression::Map { entries } => {
+                let mut pairs = Vec::new();
+                for (key, value) in entries {
+                    pairs.push((key.evaluate(environment)?, value.evaluate(environment)?));
+                }
+                return Ok(map_value(pairs));
+            }
+            Expression::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = object.evaluate(environment)?;
+                let index = index.evaluate(environment)?;
+                return index_value(object, bracket, index);
+            }
+            Expression::Range {
+                start,
+                operator,
+                end,
+                step,
+            } => {
+                let start = start.evaluate(environment)?;
+                let end = end.evaluate(environment)?;
+                let step = match step {
+                    Some(step) => Some(step.evaluate(environment)?),
+                    None => None,
+                };
+                return range_value(start, operator, end, step);
+            }
+            // Generators suspend on `yield` before it is ever evaluated as an expression.
+            Expression::Yield { keyword, .. } => {
+                return Err(Error::GeneratorError(
+                    keyword.clone(),
+                    "'yield' used outside of a generator body".to_string(),
+                ));
+            }
+        }
+    }
+}
+
+// A map literal's value: a key given more than once keeps its last value but its
+// first position. Keys are the same when they are `==`, so `1` and `1.0` are one key.
+pub fn map_value(pairs: Vec<(Value, Value)>) -> Value {
+    let mut values: Vec<(Value, Value)> = Vec::new();
+    for (key, value) in pairs {
+        match values
+            .iter_mut()
+            .find(|(existing, _)| equals(existing, &key))
+        {
+            Some(entry) => entry.1 = value,
+            None => values.push((key, value)),
+        }
+    }
+    return Value::map(values);
+}
+
+pub fn unary_operation(operator: &Token, right: Value) -> Result<Value, Error> {
+    match (right.clone(), operator.token_type.clone()) {
+        (value, TokenType::MINUS) if numbers::rank(&value).is_some() => {
+            match numbers::negate(&value) {
+                Some(result) => return Ok(result),
+                None => return Err(Error::InvalidUnaryOperation(value, operator.clone())),
+            }
+        }
+        (value, TokenType::MINUS) => {
+            return Err(Error::InvalidUnaryOperation(value, operator.clone()));
+        }
+        (mut any, TokenType::BANG) => match any.is_falsy() {
+            Ok(value) => {
+                return Ok(value);
+            }
+            Err(err) => Err(err),
+        },
+        (value, _) => {
+            return Err(Error::InvalidUnaryOperation(value, operator.clone()));
+        }
+    }
+}
+
+pub fn binary_operation(left: Value, operator: &Token, right: Value) -> Result<Value, Error> {
+    match (left, operator.token_type.clone(), right) {
+        // NUMBERS
+        (x, _, y) if numbers::rank(&x).is_some() && numbers::rank(&y).is_some() => {
+            return numbers::binary(x, operator, y);
+        }
+        // STRINGS
+        (Value::StringValue(x), TokenType::STAR, Value::IntValue(y)) => {
+            return strings::repeated(operator, "repeating with *", &x, y);
+        }
+        (Value::IntValue(x), TokenType::STAR, Value::StringValue(y)) => {
+            return strings::repeated(operator, "repeating with *", &y, x);
+        }
+        (Value::StringValue(x), TokenType::PLUS, Value::StringValue(y)) => {
+            return Ok(Value::StringValue(format!("{}{}", x, y)));
+        }
+        (Value::StringValue(x), TokenType::GREATER, Value::StringValue(y)) => {
+            return Ok(Value::from_bool(x > y));
+        }
+        (Value::StringValue(x), TokenType::GREATER_EQUAL, Value::StringValue(y)) => {
+            return Ok(Value::from_bool(x >= y));
+        }
+        (Value::StringValue(x), TokenType::LESS, Value::StringValue(y)) => {
+            return Ok(Value::from_bool(x < y));
+        }
+        (Value::StringValue(x), TokenType::LESS_EQUAL, Value::StringValue(y)) => {
+            return Ok(Value::from_bool(x <= y));
+        }
+        // EQUALITY
+        (x, TokenType::EQUAL_EQUAL, y) => return Ok(Value::from_bool(equals(&x, &y))),
+        (x, TokenType::BANG_EQUAL, y) => return Ok(Value::from_bool(!equals(&x, &y))),
+        (
+            left,
+            TokenType::GREATER | TokenType::GREATER_EQUAL | TokenType::LESS | TokenType::LESS_EQUAL,
+            right,
+        ) => {
+            return Err(Error::UnorderedComparison(left, operator.clone(), right));
+        }
+        (left, _, right) => {
+            return Err(Error::InvalidBinaryOperation(left, operator.clone(), right));
+        }
+    }
+}
+
+// `==` for any two values: values of different types are simply unequal, numbers
+// compare by value across kinds, and lists, maps and enum payloads compare their
+// elements the same way. Maps are equal when they hold the same entries in any order.
+pub fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (x, y) if numbers::rank(x).is_some() || numbers::rank(y).is_some() => {
+            return numbers::equals(x, y);
+        }
+        (Value::ListValue(x), Value::ListValue(y)) => {
+            let (x, y) = (x.borrow(), y.borrow());
+            return x.len() == y.len() && x.iter().zip(y.iter()).all(|(x, y)| equals(x, y));
+        }
+        (Value::MapValue(x), Value::MapValue(y)) => {
+            let (x, y) = (x.borrow(), y.borrow());
+            return x.len() == y.len()
+                && x.iter().all(|(key, value)| {
+                    y.iter().any(|(other_key, other_value)| {
+                        equals(key, other_key) && equals(value, other_value)
+                    })
+                });
+        }
+        (
+            Value::EnumValue(enum_name, variant, fields),
+            Value::EnumValue(other_name, other_variant, other_fields),
+        ) => {
+            return enum_name == other_name
+                && variant == other_variant
+                && fields.len() == other_fields.len()
+                && fields.iter().zip(other_fields).all(|(x, y)| equals(x, y));
+        }
+        (x, y) => return x == y,
+    }
+}
+
+// The range `start..end step step`, or `..=` when `operator` is inclusive.
+pub fn range_value(
+    start: Value,
+    operator: &Token,
+    end: Value,
+    step: Option<Value>,
+) -> Result<Value, Error> {
+    let start = range_bound(start, operator, "start")?;
+    let end = range_bound(end, operator, "end")?;
+    let step = match step {
+        Some(step) => range_bound(step, operator, "step")?,
+        None => 1,
+    };
+    if step == 0 {
+        return Err(Error::InvalidRange(
+            operator.clone(),
+            "step cannot be zero".to_string(),
+        ));
+    }
+    let inclusive = operator.token_type == TokenType::DOT_DOT_EQUAL;
+    return Ok(Value::RangeValue(Range::new(start, end, step, inclusive)));
+}
+
+fn range_bound(value: Value, operator: &Token, part: &str) -> Result<i128, Error> {
+    match value {
+        Value::IntValue(x) => return Ok(x),
+        Value::BigIntValue(x) => {
+            return Err(Error::InvalidRange(
+                operator.clone(),
+                format!("{} {} is too large for a range", part, x),
+            ))
+        }
+        other => {
+            return Err(Error::InvalidRange(
+                operator.clone(),
+                format!("{} must be an integer, got {}", part, other.to_repr()),
+            ))
+        }
+    }
+}
+
+pub fn get_property(object: Value, name: &Token) -> Result<Value, Error> {
+    match &object {
+        Value::EnumType(enum_name, variants) => {
+            match variants.iter().find(|(variant, _)| *variant == name.symbol) {
+                Some((variant, 0)) => {
+                    return Ok(Value::EnumValue(*enum_name, *variant, vec![]));
+                }
+                Some((variant, arity)) => {
+                    return Ok(Value::EnumConstructor(*enum_name, *variant, *arity));
+                }
+                None => return Err(Error::UndefinedProperty(object, name.clone())),
+            }
+        }
+        Value::Generator(_) if GeneratorRef::has_method(name.symbol) => {
+            return Ok(Value::BoundMethod(Box::new(object), name.symbol));
+        }
+        Value::Module(module) => return module.get(name),
+        Value::File(_) if FileHandle::has_method(name.symbol) => {
+            return Ok(Value::BoundMethod(Box::new(object), name.symbol));
+        }
+        Value::StringValue(_) if strings::has_method(name.symbol) => {
+            return Ok(Value::BoundMethod(Box::new(object), name.symbol));
+        }
+        _ => return Err(Error::UndefinedProperty(object, name.clone())),
+    }
+}
+
+pub fn call_value(callee: Value, paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    match callee {
+        Value::EnumConstructor(enum_name, variant, arity) => {
+            if arity != arguments.len() {
+                return Err(Error::ArityMismatch(paren.clone(), arity, arguments.len()));
+            }
+            return Ok(Value::EnumValue(enum_name, variant, arguments));
+        }
+        Value::Function(function) => return function.call(paren, arguments),
+        Value::Closure(closure) => return vm::call(closure, paren, arguments),
+        Value::NativeFunction(native) => return native.call(paren, arguments),
+        Value::BoundMethod(receiver, name) => match *receiver {
+            Value::Generator(generator) => return generator.call_method(name, paren, arguments),
+            Value::StringValue(receiver) => {
+                return strings::call_method(&receiver, name, paren, arguments)
+            }
+            Value::File(file) => return file.call_method(name, paren, arguments),
+            other => return Err(Error::NotCallable(other, paren.clone())),
+        },
+        other => return Err(Error::NotCallable(other, paren.clone())),
+    }
+}
+
+pub fn index_value(object: Value, bracket: &Token, index: Value) -> Result<Value, Error> {
+    match (&object, &index) {
+        (Value::ListValue(items), Value::IntValue(i)) => {
+            let items = items.borrow();
+            // Negative indices count back from the end of the list.
+            let position = if *i < 0 { items.len() as i128 + i } else { *i };
+            if position < 0 || position >= items.len() as i128 {
+                return Err(Error::IndexError(
+                    bracket.clone(),
+                    format!(
+                        "index {} out of range for list of length {}",
+                        i,
+                        items.len()
+                    ),
+                ));
+            }
+            return Ok(items[position as usize].clone());
+        }
+        (Value::StringValue(x), Value::IntValue(i)) => {
+            let chars: Vec<char> = x.chars().collect();
+            let position = if *i < 0 { chars.len() as i128 + i } else { *i };
+            if position < 0 || position >= chars.len() as i128 {
+                return Err(Error::IndexError(
+                    bracket.clone(),
+                    format!(
+                        "index {} out of range for string of length {}",
+                        i,
+                        chars.len()
+                    ),
+                ));
+            }
+            return Ok(Value::StringValue(chars[position as usize].to_string()));
+        }
+        (Value::ListValue(items), Value::RangeValue(range)) => {
+            let items = items.borrow();
+            let positions = slice_positions(range, items.len(), bracket)?;
+            return Ok(Value::list(
+                positions.into_iter().map(|i| items[i].clone()).collect(),
+            ));
+        }
+        (Value::StringValue(x), Value::RangeValue(range)) => {
+            let chars: Vec<char> = x.chars().collect();
+            let positions = slice_positions(range, chars.len(), bracket)?;
+            return Ok(Value::StringValue(
+                positions.into_iter().map(|i| chars[i]).collect(),
+            ));
+        }
+        (Value::MapValue(entries), key) => {
+            match entries
+                .borrow()
+                .iter()
+                .find(|(existing, _)| equals(existing, key))
+            {
+                Some((_, value)) => return Ok(value.clone()),
+                None => {
+                    return Err(Error::IndexError(
+                        bracket.clone(),
+                        format!("key {} not found", key.to_repr()),
+                    ))
+                }
+            }
+        }
+        _ => {
+            return Err(Error::IndexError(
+                bracket.clone(),
+                format!("cannot index {} with {}", object.to_repr(), index.to_repr()),
+            ))
+        }
+    }
+}
+
+// Every position a slicing range selects, each of which must lie within the sequence.
+fn slice_positions(range: &Range, length: usize, bracket: &Token) -> Result<Vec<usize>, Error> {
+    let mut positions = Vec::new();
+    for position in range.iter() {
+        match position {
+            Value::IntValue(i) if i >= 0 && i < length as i128 => {
+                positions.push(i as usize);
+            }
+            _ => {
+                return Err(Error::IndexError(
+                    bracket.clone(),
+                    format!(
+                        "slice {} out of range for length {}",
+                        range.to_string(),
+                        length
+                    ),
+                ))
+            }
         }
     }
+    return Ok(positions);
 }
@@ -0,0 +1,248 @@
+use std::{
+    cell::RefCell,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    rc::Rc,
+};
+
+use crate::{
+    errors::Error,
+    natives::{self, invalid_argument, Module, NativeFunction},
+    scanner::Token,
+    symbols::{self, Symbol},
+    values::Value,
+};
+
+// The `fs` module. The interpreter only hands it out when the host grants file
+// access; otherwise every member lookup fails with a permission error. Calls that
+// touch the file system return a `Result`, whose `Err` names the call and the path
+// involved, so a missing file is something a script can handle.
+pub const FS: Module = Module {
+    name: "fs",
+    functions: &[
+        NativeFunction {
+            name: "read_text",
+            arity: 1,
+            function: read_text,
+        },
+        NativeFunction {
+            name: "write_text",
+            arity: 2,
+            function: write_text,
+        },
+        NativeFunction {
+            name: "append",
+            arity: 2,
+            function: append,
+        },
+        NativeFunction {
+            name: "exists",
+            arity: 1,
+            function: exists,
+        },
+        NativeFunction {
+            name: "list_dir",
+            arity: 1,
+            function: list_dir,
+        },
+        NativeFunction {
+            name: "mkdir",
+            arity: 1,
+            function: mkdir,
+        },
+        NativeFunction {
+            name: "remove",
+            arity: 1,
+            function: remove,
+        },
+        NativeFunction {
+            name: "open",
+            arity: 1,
+            function: open,
+        },
+    ],
+    constants: &[],
+    denied: None,
+};
+
+// A file opened for reading with `fs.open`. Iterating it yields its lines without
+// their line endings; the file is closed once it is exhausted or `close()`d.
+#[derive(Clone)]
+pub struct FileHandle {
+    pub(crate) path: String,
+    reader: Rc<RefCell<Option<BufReader<File>>>>,
+}
+
+impl PartialEq for FileHandle {
+    fn eq(&self, other: &Self) -> bool {
+        return Rc::ptr_eq(&self.reader, &other.reader);
+    }
+}
+
+impl std::fmt::Debug for FileHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "<file {}>", self.path);
+    }
+}
+
+impl FileHandle {
+    pub fn has_method(name: Symbol) -> bool {
+        return [symbols::READ_LINE, symbols::CLOSE].contains(&name);
+    }
+
+    pub fn read_line(self: &Self, token: &Token) -> Result<Option<String>, Error> {
+        let mut reader = self.reader.borrow_mut();
+        let file = match reader.as_mut() {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let mut line = String::new();
+        match file.read_line(&mut line) {
+            Ok(0) => {
+                *reader = None;
+                return Ok(None);
+            }
+            Ok(_) => {
+                let line = line.trim_end_matches(['\n', '\r']).to_string();
+                return Ok(Some(line));
+            }
+            Err(err) => return Err(io_error(token, "read_line", &self.path, err)),
+        }
+    }
+
+    pub fn call_method(
+        self: &Self,
+        name: Symbol,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Error> {
+        if arguments.len() != 0 {
+            return Err(Error::ArityMismatch(paren.clone(), 0, arguments.len()));
+        }
+        match name {
+            symbols::READ_LINE => match self.read_line(paren) {
+                Ok(Some(line)) => return Ok(natives::ok(Value::StringValue(line))),
+                Ok(None) => return Ok(natives::ok(Value::Nil)),
+                Err(Error::IoError(_, message)) => return Ok(natives::err(message)),
+                Err(err) => return Err(err),
+            },
+            _ => {
+                *self.reader.borrow_mut() = None;
+                return Ok(Value::Nil);
+            }
+        }
+    }
+}
+
+fn io_message(function: &str, path: &str, err: std::io::Error) -> String {
+    return format!("fs.{}(\"{}\") failed: {}", function, path, err);
+}
+
+// For reading lines, which `for` loops do with nowhere to return a `Result` to.
+fn io_error(paren: &Token, function: &str, path: &str, err: std::io::Error) -> Error {
+    return Error::IoError(paren.clone(), io_message(function, path, err));
+}
+
+// The `Result.Err` a call returns when the file system refuses it.
+fn failure(function: &str, path: &str, err: std::io::Error) -> Value {
+    return natives::err(io_message(function, path, err));
+}
+
+fn string_argument(paren: &Token, function: &str, argument: &Value) -> Result<String, Error> {
+    match argument {
+        Value::StringValue(x) => return Ok(x.clone()),
+        other => return Err(invalid_argument(paren, function, "a string", other)),
+    }
+}
+
+fn read_text(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let path = string_argument(paren, "read_text", &arguments[0])?;
+    match fs::read_to_string(&path) {
+        Ok(text) => return Ok(natives::ok(Value::StringValue(text))),
+        Err(err) => return Ok(failure("read_text", &path, err)),
+    }
+}
+
+fn write_text(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let path = string_argument(paren, "write_text", &arguments[0])?;
+    let text = string_argument(paren, "write_text", &arguments[1])?;
+    match fs::write(&path, text) {
+        Ok(_) => return Ok(natives::ok(Value::Nil)),
+        Err(err) => return Ok(failure("write_text", &path, err)),
+    }
+}
+
+fn append(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let path = string_argument(paren, "append", &arguments[0])?;
+    let text = string_argument(paren, "append", &arguments[1])?;
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()));
+    match result {
+        Ok(_) => return Ok(natives::ok(Value::Nil)),
+        Err(err) => return Ok(failure("append", &path, err)),
+    }
+}
+
+fn exists(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let path = string_argument(paren, "exists", &arguments[0])?;
+    return Ok(Value::from_bool(std::path::Path::new(&path).exists()));
+}
+
+// Entry names (not full paths), sorted so the order does not depend on the platform.
+fn list_dir(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let path = string_argument(paren, "list_dir", &arguments[0])?;
+    let entries = match fs::read_dir(&path) {
+        Ok(entries) => entries,
+        Err(err) => return Ok(failure("list_dir", &path, err)),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(entry) => names.push(entry.file_name().to_string_lossy().to_string()),
+            Err(err) => return Ok(failure("list_dir", &path, err)),
+        }
+    }
+    names.sort();
+    return Ok(natives::ok(Value::list(
+        names.into_iter().map(Value::StringValue).collect(),
+    )));
+}
+
+// Creates the directory along with any missing parents.
+fn mkdir(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let path = string_argument(paren, "mkdir", &arguments[0])?;
+    match fs::create_dir_all(&path) {
+        Ok(_) => return Ok(natives::ok(Value::Nil)),
+        Err(err) => return Ok(failure("mkdir", &path, err)),
+    }
+}
+
+// Removes a file or an empty directory; non-empty directories are an error.
+fn remove(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let path = string_argument(paren, "remove", &arguments[0])?;
+    let result = if std::path::Path::new(&path).is_dir() {
+        fs::remove_dir(&path)
+    } else {
+        fs::remove_file(&path)
+    };
+    match result {
+        Ok(_) => return Ok(natives::ok(Value::Nil)),
+        Err(err) => return Ok(failure("remove", &path, err)),
+    }
+}
+
+fn open(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let path = string_argument(paren, "open", &arguments[0])?;
+    match File::open(&path) {
+        Ok(file) => {
+            return Ok(natives::ok(Value::File(FileHandle {
+                path,
+                reader: Rc::new(RefCell::new(Some(BufReader::new(file)))),
+            })))
+        }
+        Err(err) => return Ok(failure("open", &path, err)),
+    }
+}
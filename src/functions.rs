@@ -0,0 +1,97 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use crate::{
+    environment::Environment,
+    errors::Error,
+    gc,
+    generators::Generator,
+    interpreter::{Completion, Interpreter},
+    scanner::Token,
+    statements::Statement,
+    values::Value,
+};
+
+// Calls nested deeper than this raise `STACK OVERFLOW` rather than exhausting the
+// native stack of the tree-walker or the memory of the VM. The count is shared by
+// both engines, so calls made through natives and generators are included.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+thread_local! {
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+// The number of calls in progress on this thread.
+pub fn call_depth() -> usize {
+    return CALL_DEPTH.with(|depth| depth.get());
+}
+
+pub fn set_call_depth(depth: usize) {
+    CALL_DEPTH.with(|cell| cell.set(depth));
+}
+
+// Counts one more call made at `paren`, failing if that is too many.
+pub fn enter_call(paren: &Token) -> Result<(), Error> {
+    let depth = call_depth();
+    if depth >= MAX_CALL_DEPTH {
+        return Err(Error::StackOverflow(paren.clone(), MAX_CALL_DEPTH));
+    }
+    set_call_depth(depth + 1);
+    return Ok(());
+}
+
+pub struct Function {
+    pub(crate) name: Token,
+    pub(crate) params: Vec<Token>,
+    pub(crate) body: Rc<Vec<Statement>>,
+    pub(crate) closure: Rc<RefCell<Environment>>,
+    // Functions whose body contains `yield` return a generator instead of running.
+    pub(crate) is_generator: bool,
+}
+
+impl Function {
+    pub fn call(self: &Rc<Self>, paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+        if arguments.len() != self.params.len() {
+            return Err(Error::ArityMismatch(
+                paren.clone(),
+                self.params.len(),
+                arguments.len(),
+            ));
+        }
+        let mut environment = Environment::new_enclosed(self.closure.clone());
+        for (param, argument) in self.params.iter().zip(arguments) {
+            environment.define(param, argument)?;
+        }
+        let environment = gc::environment(environment);
+        if self.is_generator {
+            return Ok(Value::Generator(gc::generator(Generator::new(
+                self.clone(),
+                environment,
+            ))));
+        }
+        let depth = call_depth();
+        enter_call(paren)?;
+        let mut interpreter = Interpreter::with_environment(environment);
+        let completion = interpreter.execute_block(&self.body);
+        set_call_depth(depth);
+        match completion? {
+            Completion::Return(value) => return Ok(value),
+            Completion::Normal => return Ok(Value::Nil),
+        }
+    }
+}
+
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "<fn {}>", self.name.lexeme);
+    }
+}
+
+// Functions are only ever equal to themselves.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        return std::ptr::eq(self, other);
+    }
+}
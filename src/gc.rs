@@ -0,0 +1,420 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    environment::Environment,
+    functions::Function,
+    generators::{Generator, GeneratorRef},
+    iteration::ValueIterator,
+    values::Value,
+    vm::Closure,
+};
+
+// Values are reference counted, which frees everything except cycles, like a
+// closure stored in the environment it captures. Every heap object that can take
+// part in a cycle is allocated here and tracked, and a mark-and-sweep collection
+// reclaims the cycles that can no longer be reached.
+//
+// The roots are the objects referred to from outside the heap: the global
+// environment and open environments held by running interpreters, and the values
+// on VM stacks. Rather than registering them, a collection finds them from the
+// reference counts, as objects counted more often than other tracked objects refer
+// to them. Everything reachable from a root is marked; the rest is garbage, and
+// emptying it breaks its cycles so reference counting frees it.
+//
+// An object that is in use while collecting, like the generator running `gc()`,
+// can not be looked into. What it refers to is then treated as a root, so a
+// collection may miss garbage but never frees anything live.
+
+pub type Cell = Rc<RefCell<Option<Value>>>;
+
+#[derive(Clone)]
+enum Object {
+    Environment(Rc<RefCell<Environment>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Vec<(Value, Value)>>>),
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    Cell(Cell),
+    Generator(Rc<RefCell<Generator>>),
+}
+
+enum Tracked {
+    Environment(Weak<RefCell<Environment>>),
+    List(Weak<RefCell<Vec<Value>>>),
+    Map(Weak<RefCell<Vec<(Value, Value)>>>),
+    Function(Weak<Function>),
+    Closure(Weak<Closure>),
+    Cell(Weak<RefCell<Option<Value>>>),
+    Generator(Weak<RefCell<Generator>>),
+}
+
+pub struct Stats {
+    pub collections: usize,
+    // Objects reclaimed by all collections so far.
+    pub freed: usize,
+    // Objects alive now.
+    pub live: usize,
+    // How many tracked objects start the next collection.
+    pub threshold: usize,
+}
+
+struct Heap {
+    objects: Vec<Tracked>,
+    threshold: usize,
+    // The threshold never drops below this, so small programs are not collected
+    // over and over.
+    minimum: usize,
+    // After a collection, the threshold becomes the surviving objects times this.
+    growth: f64,
+    collections: usize,
+    freed: usize,
+}
+
+pub const DEFAULT_THRESHOLD: usize = 10_000;
+pub const DEFAULT_GROWTH: f64 = 2.0;
+
+thread_local! {
+    static HEAP: RefCell<Heap> = const {
+        RefCell::new(Heap {
+            objects: Vec::new(),
+            threshold: DEFAULT_THRESHOLD,
+            minimum: DEFAULT_THRESHOLD,
+            growth: DEFAULT_GROWTH,
+            collections: 0,
+            freed: 0,
+        })
+    };
+}
+
+// Sets when collections run automatically: once `threshold` objects are tracked,
+// and after that once the heap has grown by `growth` times what survived.
+pub fn configure(threshold: usize, growth: f64) {
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.threshold = threshold;
+        heap.minimum = threshold;
+        heap.growth = growth;
+    });
+}
+
+pub fn environment(environment: Environment) -> Rc<RefCell<Environment>> {
+    let environment = Rc::new(RefCell::new(environment));
+    track(Tracked::Environment(Rc::downgrade(&environment)));
+    return environment;
+}
+
+pub fn list(items: Vec<Value>) -> Rc<RefCell<Vec<Value>>> {
+    let list = Rc::new(RefCell::new(items));
+    track(Tracked::List(Rc::downgrade(&list)));
+    return list;
+}
+
+pub fn map(entries: Vec<(Value, Value)>) -> Rc<RefCell<Vec<(Value, Value)>>> {
+    let map = Rc::new(RefCell::new(entries));
+    track(Tracked::Map(Rc::downgrade(&map)));
+    return map;
+}
+
+pub fn function(function: Function) -> Rc<Function> {
+    let function = Rc::new(function);
+    track(Tracked::Function(Rc::downgrade(&function)));
+    return function;
+}
+
+pub fn closure(closure: Closure) -> Rc<Closure> {
+    let closure = Rc::new(closure);
+    track(Tracked::Closure(Rc::downgrade(&closure)));
+    return closure;
+}
+
+pub fn cell() -> Cell {
+    let cell = Rc::new(RefCell::new(None));
+    track(Tracked::Cell(Rc::downgrade(&cell)));
+    return cell;
+}
+
+pub fn generator(generator: Generator) -> GeneratorRef {
+    let generator = Rc::new(RefCell::new(generator));
+    track(Tracked::Generator(Rc::downgrade(&generator)));
+    return GeneratorRef(generator);
+}
+
+// The new object is held by its caller, so collecting here never frees it.
+fn track(object: Tracked) {
+    let full = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects.push(object);
+        return heap.objects.len() >= heap.threshold;
+    });
+    if full {
+        collect();
+    }
+}
+
+pub fn stats() -> Stats {
+    return HEAP.with(|heap| {
+        let heap = heap.borrow();
+        return Stats {
+            collections: heap.collections,
+            freed: heap.freed,
+            live: heap
+                .objects
+                .iter()
+                .filter(|object| object.is_alive())
+                .count(),
+            threshold: heap.threshold,
+        };
+    });
+}
+
+// Runs a collection now, returning how many objects it reclaimed.
+pub fn collect() -> usize {
+    let tracked = HEAP.with(|heap| std::mem::take(&mut heap.borrow_mut().objects));
+    let objects: Vec<Object> = tracked.iter().filter_map(Tracked::upgrade).collect();
+    let index: HashMap<usize, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, object)| (object.address(), i))
+        .collect();
+
+    // How often each object is referred to from other tracked objects.
+    let mut internal = vec![0; objects.len()];
+    for object in objects.iter() {
+        object.trace(&mut |address| {
+            if let Some(i) = index.get(&address) {
+                internal[*i] += 1;
+            }
+        });
+    }
+    // `objects` holds one reference to each; any others come from a root.
+    let mut marked: Vec<bool> = objects
+        .iter()
+        .zip(internal.iter())
+        .map(|(object, internal)| object.strong_count() - 1 > *internal)
+        .collect();
+    let mut pending: Vec<usize> = (0..objects.len()).filter(|i| marked[*i]).collect();
+    while let Some(i) = pending.pop() {
+        objects[i].trace(&mut |address| {
+            if let Some(j) = index.get(&address) {
+                if !marked[*j] {
+                    marked[*j] = true;
+                    pending.push(*j);
+                }
+            }
+        });
+    }
+
+    let mut freed = 0;
+    for (object, marked) in objects.iter().zip(marked.iter()) {
+        if !marked {
+            object.release();
+            freed += 1;
+        }
+    }
+    let survivors: Vec<Tracked> = objects
+        .iter()
+        .zip(marked.iter())
+        .filter(|(_, marked)| **marked)
+        .map(|(object, _)| object.downgrade())
+        .collect();
+    // Dropping the last references to the garbage frees it.
+    drop(objects);
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects = survivors;
+        let grown = (heap.objects.len() as f64 * heap.growth) as usize;
+        heap.threshold = grown.max(heap.minimum);
+        heap.collections += 1;
+        heap.freed += freed;
+    });
+    return freed;
+}
+
+fn address<T: ?Sized>(rc: &Rc<T>) -> usize {
+    return Rc::as_ptr(rc) as *const () as usize;
+}
+
+// Calls `visit` with every tracked object `value` refers to, once per reference.
+pub fn trace_value(value: &Value, visit: &mut dyn FnMut(usize)) {
+    match value {
+        Value::ListValue(items) => visit(address(items)),
+        Value::MapValue(entries) => visit(address(entries)),
+        Value::Function(function) => visit(address(function)),
+        Value::Closure(closure) => visit(address(closure)),
+        Value::Generator(generator) => visit(address(&generator.0)),
+        Value::EnumValue(_, _, payload) => {
+            for value in payload {
+                trace_value(value, visit);
+            }
+        }
+        Value::BoundMethod(receiver, _) => trace_value(receiver, visit),
+        _ => {}
+    }
+}
+
+pub fn trace_environment(environment: &Rc<RefCell<Environment>>, visit: &mut dyn FnMut(usize)) {
+    visit(address(environment));
+}
+
+pub fn trace_cell(cell: &Cell, visit: &mut dyn FnMut(usize)) {
+    visit(address(cell));
+}
+
+pub fn trace_closure(closure: &Rc<Closure>, visit: &mut dyn FnMut(usize)) {
+    visit(address(closure));
+}
+
+pub fn trace_iterator(iterator: &ValueIterator, visit: &mut dyn FnMut(usize)) {
+    match iterator {
+        ValueIterator::Items(items) => {
+            for value in items.as_slice() {
+                trace_value(value, visit);
+            }
+        }
+        ValueIterator::Generator(generator, _) => visit(address(&generator.0)),
+        ValueIterator::Protocol(value, _) => trace_value(value, visit),
+        ValueIterator::Range(_) | ValueIterator::Lines(..) => {}
+    }
+}
+
+impl Tracked {
+    fn upgrade(self: &Self) -> Option<Object> {
+        match self {
+            Tracked::Environment(weak) => return weak.upgrade().map(Object::Environment),
+            Tracked::List(weak) => return weak.upgrade().map(Object::List),
+            Tracked::Map(weak) => return weak.upgrade().map(Object::Map),
+            Tracked::Function(weak) => return weak.upgrade().map(Object::Function),
+            Tracked::Closure(weak) => return weak.upgrade().map(Object::Closure),
+            Tracked::Cell(weak) => return weak.upgrade().map(Object::Cell),
+            Tracked::Generator(weak) => return weak.upgrade().map(Object::Generator),
+        }
+    }
+
+    fn is_alive(self: &Self) -> bool {
+        match self {
+            Tracked::Environment(weak) => return weak.strong_count() > 0,
+            Tracked::List(weak) => return weak.strong_count() > 0,
+            Tracked::Map(weak) => return weak.strong_count() > 0,
+            Tracked::Function(weak) => return weak.strong_count() > 0,
+            Tracked::Closure(weak) => return weak.strong_count() > 0,
+            Tracked::Cell(weak) => return weak.strong_count() > 0,
+            Tracked::Generator(weak) => return weak.strong_count() > 0,
+        }
+    }
+}
+
+impl Object {
+    fn address(self: &Self) -> usize {
+        match self {
+            Object::Environment(rc) => return address(rc),
+            Object::List(rc) => return address(rc),
+            Object::Map(rc) => return address(rc),
+            Object::Function(rc) => return address(rc),
+            Object::Closure(rc) => return address(rc),
+            Object::Cell(rc) => return address(rc),
+            Object::Generator(rc) => return address(rc),
+        }
+    }
+
+    fn strong_count(self: &Self) -> usize {
+        match self {
+            Object::Environment(rc) => return Rc::strong_count(rc),
+            Object::List(rc) => return Rc::strong_count(rc),
+            Object::Map(rc) => return Rc::strong_count(rc),
+            Object::Function(rc) => return Rc::strong_count(rc),
+            Object::Closure(rc) => return Rc::strong_count(rc),
+            Object::Cell(rc) => return Rc::strong_count(rc),
+            Object::Generator(rc) => return Rc::strong_count(rc),
+        }
+    }
+
+    fn downgrade(self: &Self) -> Tracked {
+        match self {
+            Object::Environment(rc) => return Tracked::Environment(Rc::downgrade(rc)),
+            Object::List(rc) => return Tracked::List(Rc::downgrade(rc)),
+            Object::Map(rc) => return Tracked::Map(Rc::downgrade(rc)),
+            Object::Function(rc) => return Tracked::Function(Rc::downgrade(rc)),
+            Object::Closure(rc) => return Tracked::Closure(Rc::downgrade(rc)),
+            Object::Cell(rc) => return Tracked::Cell(Rc::downgrade(rc)),
+            Object::Generator(rc) => return Tracked::Generator(Rc::downgrade(rc)),
+        }
+    }
+
+    // Visits what the object refers to. One that is borrowed for writing is in use,
+    // so it is skipped, which makes what it refers to look like roots.
+    fn trace(self: &Self, visit: &mut dyn FnMut(usize)) {
+        match self {
+            Object::Environment(environment) => {
+                if let Ok(environment) = environment.try_borrow() {
+                    environment.trace(visit);
+                }
+            }
+            Object::List(items) => {
+                if let Ok(items) = items.try_borrow() {
+                    for value in items.iter() {
+                        trace_value(value, visit);
+                    }
+                }
+            }
+            Object::Map(entries) => {
+                if let Ok(entries) = entries.try_borrow() {
+                    for (key, value) in entries.iter() {
+                        trace_value(key, visit);
+                        trace_value(value, visit);
+                    }
+                }
+            }
+            Object::Function(function) => trace_environment(&function.closure, visit),
+            Object::Closure(closure) => closure.trace(visit),
+            Object::Cell(cell) => {
+                if let Ok(cell) = cell.try_borrow() {
+                    if let Some(value) = &*cell {
+                        trace_value(value, visit);
+                    }
+                }
+            }
+            Object::Generator(generator) => {
+                if let Ok(generator) = generator.try_borrow() {
+                    generator.trace(visit);
+                }
+            }
+        }
+    }
+
+    // Empties unreachable garbage. Functions and closures can not be changed, but
+    // every cycle through them also runs through something that can.
+    fn release(self: &Self) {
+        match self {
+            Object::Environment(environment) => {
+                if let Ok(mut environment) = environment.try_borrow_mut() {
+                    environment.release();
+                }
+            }
+            Object::List(items) => {
+                if let Ok(mut items) = items.try_borrow_mut() {
+                    items.clear();
+                }
+            }
+            Object::Map(entries) => {
+                if let Ok(mut entries) = entries.try_borrow_mut() {
+                    entries.clear();
+                }
+            }
+            Object::Cell(cell) => {
+                if let Ok(mut cell) = cell.try_borrow_mut() {
+                    *cell = None;
+                }
+            }
+            Object::Generator(generator) => {
+                if let Ok(mut generator) = generator.try_borrow_mut() {
+                    generator.close();
+                }
+            }
+            Object::Function(_) | Object::Closure(_) => {}
+        }
+    }
+}
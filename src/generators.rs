@@ -0,0 +1,387 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    errors::Error,
+    expressions::Expression,
+    functions::{self, Function},
+    gc,
+    interpreter::Interpreter,
+    iteration::{iterate, ValueIterator},
+    resolver::Resolution,
+    scanner::Token,
+    statements::Statement,
+    symbols::{self, Symbol},
+    values::Value,
+    vm::Coroutine,
+};
+
+// The tree-walker cannot pause in the middle of `Interpreter::execute`, so a generator
+// runs its body through an explicit stack of frames instead. Blocks, `for` loops and
+// `match` arms are unrolled onto the stack, which lets `yield` suspend at statement
+// granularity; every other statement is handed to a regular interpreter.
+
+#[derive(Clone)]
+pub struct GeneratorRef(pub Rc<RefCell<Generator>>);
+
+impl PartialEq for GeneratorRef {
+    fn eq(&self, other: &Self) -> bool {
+        return Rc::ptr_eq(&self.0, &other.0);
+    }
+}
+
+impl std::fmt::Debug for GeneratorRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "<generator>");
+    }
+}
+
+#[derive(PartialEq)]
+enum State {
+    Created,
+    Suspended,
+    Running,
+    Done,
+}
+
+enum Frame {
+    Sequence(Rc<Vec<Statement>>, usize),
+    Single(Rc<Statement>),
+    // Restores the enclosing environment once the scope above it is finished.
+    Scope(Rc<RefCell<Environment>>),
+    Loop(Box<(Token, Rc<Statement>, ValueIterator)>),
+}
+
+// Where the value passed to `send` goes when a suspended generator resumes.
+enum Resume {
+    Discard,
+    Define(Token),
+    DefineConstant(Token),
+    Assign(Token, Resolution),
+}
+
+enum Step {
+    Continue,
+    Yield(Value),
+    Finish,
+}
+
+pub struct Generator {
+    name: String,
+    state: State,
+    body: Body,
+}
+
+enum Body {
+    Walked(Walker),
+    // A generator function run by the bytecode VM, which can suspend anywhere.
+    Compiled(Coroutine),
+}
+
+// The suspended state of a tree-walked generator body.
+struct Walker {
+    frames: Vec<Frame>,
+    environment: Rc<RefCell<Environment>>,
+    resume: Resume,
+}
+
+impl Generator {
+    pub fn new(function: Rc<Function>, environment: Rc<RefCell<Environment>>) -> Self {
+        return Generator {
+            name: function.name.lexeme.clone(),
+            state: State::Created,
+            body: Body::Walked(Walker {
+                frames: vec![Frame::Sequence(function.body.clone(), 0)],
+                environment: environment,
+                resume: Resume::Discard,
+            }),
+        };
+    }
+
+    pub fn compiled(name: String, coroutine: Coroutine) -> Self {
+        return Generator {
+            name: name,
+            state: State::Created,
+            body: Body::Compiled(coroutine),
+        };
+    }
+
+    pub fn name(self: &Self) -> String {
+        return self.name.clone();
+    }
+
+    // Runs the body until the next `yield`, returning None once the body has finished.
+    fn resume(self: &mut Self, sent: Value, token: &Token) -> Result<Option<Value>, Error> {
+        let started = match self.state {
+            State::Done => return Ok(None),
+            State::Running => {
+                return Err(Error::GeneratorError(
+                    token.clone(),
+                    "generator is already running".to_string(),
+                ))
+            }
+            State::Created => {
+                if sent != Value::Nil {
+                    return Err(Error::GeneratorError(
+                        token.clone(),
+                        "cannot send a non-nil value to a just-started generator".to_string(),
+                    ));
+                }
+                false
+            }
+            State::Suspended => true,
+        };
+        // Running the body is a call, as far as the call depth limit is concerned.
+        let depth = functions::call_depth();
+        functions::enter_call(token)?;
+        self.state = State::Running;
+        let result = match &mut self.body {
+            Body::Walked(walker) => walker.resume(sent, started),
+            Body::Compiled(coroutine) => coroutine.resume(sent, started),
+        };
+        functions::set_call_depth(depth);
+        match result {
+            Ok(Some(value)) => {
+                self.state = State::Suspended;
+                return Ok(Some(value));
+            }
+            Ok(None) => {
+                self.close();
+                return Ok(None);
+            }
+            Err(err) => {
+                self.close();
+                return Err(err);
+            }
+        }
+    }
+
+    // Drops every suspended frame and scope; later calls to `next` return nil.
+    pub(crate) fn close(self: &mut Self) {
+        self.state = State::Done;
+        match &mut self.body {
+            Body::Walked(walker) => walker.frames.clear(),
+            Body::Compiled(coroutine) => coroutine.close(),
+        }
+    }
+}
+
+impl Generator {
+    pub(crate) fn trace(self: &Self, visit: &mut dyn FnMut(usize)) {
+        match &self.body {
+            Body::Walked(walker) => {
+                gc::trace_environment(&walker.environment, visit);
+                for frame in walker.frames.iter() {
+                    match frame {
+                        Frame::Scope(environment) => gc::trace_environment(environment, visit),
+                        Frame::Loop(state) => gc::trace_iterator(&state.2, visit),
+                        Frame::Sequence(..) | Frame::Single(_) => {}
+                    }
+                }
+            }
+            Body::Compiled(coroutine) => coroutine.trace(visit),
+        }
+    }
+}
+
+impl Walker {
+    // Stores the value sent to a suspended generator where its `yield` asked for it,
+    // then runs on to the next `yield`.
+    fn resume(self: &mut Self, sent: Value, started: bool) -> Result<Option<Value>, Error> {
+        if started {
+            let target = std::mem::replace(&mut self.resume, Resume::Discard);
+            match target {
+                Resume::Discard => {}
+                Resume::Define(name) => self.environment.borrow_mut().define(&name, sent)?,
+                Resume::DefineConstant(name) => {
+                    self.environment.borrow_mut().define_constant(&name, sent)?
+                }
+                Resume::Assign(name, resolution) => self
+                    .environment
+                    .borrow_mut()
+                    .assign_resolved(&name, resolution, sent)?,
+            }
+        }
+        return self.run();
+    }
+
+    fn run(self: &mut Self) -> Result<Option<Value>, Error> {
+        loop {
+            let frame = match self.frames.pop() {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+            let step = match frame {
+                Frame::Sequence(statements, index) => {
+                    if index >= statements.len() {
+                        continue;
+                    }
+                    self.frames
+                        .push(Frame::Sequence(statements.clone(), index + 1));
+                    self.step(&statements[index])?
+                }
+                Frame::Single(statement) => self.step(&statement)?,
+                Frame::Scope(enclosing) => {
+                    self.environment = enclosing;
+                    continue;
+                }
+                Frame::Loop(state) => {
+                    let (name, body, mut iterator) = *state;
+                    let item = match iterator.next()? {
+                        Some(item) => item,
+                        None => continue,
+                    };
+                    self.frames.push(Frame::Loop(Box::new((
+                        name.clone(),
+                        body.clone(),
+                        iterator,
+                    ))));
+                    self.enter_scope(vec![(name, item)])?;
+                    self.frames.push(Frame::Single(body));
+                    continue;
+                }
+            };
+            match step {
+                Step::Continue => {}
+                Step::Yield(value) => return Ok(Some(value)),
+                Step::Finish => return Ok(None),
+            }
+        }
+    }
+
+    fn step(self: &mut Self, st: &Statement) -> Result<Step, Error> {
+        match st {
+            Statement::ExpressionStatement(Expression::Yield { value, .. }) => {
+                return self.suspend(value, Resume::Discard);
+            }
+            Statement::ExpressionStatement(Expression::Assign {
+                name,
+                value,
+                resolution,
+            }) => {
+                if let Expression::Yield { value, .. } = &**value {
+                    let resume = Resume::Assign(name.clone(), resolution.get());
+                    return self.suspend(value, resume);
+                }
+            }
+            Statement::Variable(name, _, Expression::Yield { value, .. }) => {
+                return self.suspend(value, Resume::Define(name.clone()));
+            }
+            Statement::Const(name, _, Expression::Yield { value, .. }) => {
+                return self.suspend(value, Resume::DefineConstant(name.clone()));
+            }
+            Statement::Block(statements) => {
+                self.enter_scope(vec![])?;
+                self.frames.push(Frame::Sequence(statements.clone(), 0));
+                return Ok(Step::Continue);
+            }
+            Statement::ForIn(keyword, name, iterable, body) => {
+                let iterable = iterable.evaluate(&self.environment)?;
+                let iterator = iterate(iterable, keyword)?;
+                self.frames.push(Frame::Loop(Box::new((
+                    name.clone(),
+                    body.clone(),
+                    iterator,
+                ))));
+                return Ok(Step::Continue);
+            }
+            Statement::Match(keyword, subject, arms) => {
+                let value = subject.evaluate(&self.environment)?;
+                for (pattern, body) in arms {
+                    let mut bindings = Vec::new();
+                    if pattern.matches(&value, &self.environment, &mut bindings)? {
+                        self.enter_scope(bindings)?;
+                        self.frames.push(Frame::Single(body.clone()));
+                        return Ok(Step::Continue);
+                    }
+                }
+                return Err(Error::NoMatchingArm(keyword.clone(), value));
+            }
+            // The parser only lets generators use a bare `return;`.
+            Statement::Return(..) => return Ok(Step::Finish),
+            _ => {}
+        }
+        let mut interpreter = Interpreter::with_environment(self.environment.clone());
+        interpreter.execute(st)?;
+        return Ok(Step::Continue);
+    }
+
+    fn suspend(
+        self: &mut Self,
+        value: &Option<Box<Expression>>,
+        resume: Resume,
+    ) -> Result<Step, Error> {
+        let value = match value {
+            Some(value) => value.evaluate(&self.environment)?,
+            None => Value::Nil,
+        };
+        self.resume = resume;
+        return Ok(Step::Yield(value));
+    }
+
+    fn enter_scope(self: &mut Self, bindings: Vec<(Token, Value)>) -> Result<(), Error> {
+        let mut environment = Environment::new_enclosed(self.environment.clone());
+        for (name, value) in bindings {
+            environment.define(&name, value)?;
+        }
+        let enclosing = std::mem::replace(&mut self.environment, gc::environment(environment));
+        self.frames.push(Frame::Scope(enclosing));
+        return Ok(());
+    }
+}
+
+impl GeneratorRef {
+    pub fn has_method(name: Symbol) -> bool {
+        return [symbols::NEXT, symbols::SEND, symbols::CLOSE, symbols::ITER].contains(&name);
+    }
+
+    pub fn resume(self: &Self, sent: Value, token: &Token) -> Result<Option<Value>, Error> {
+        match self.0.try_borrow_mut() {
+            Ok(mut generator) => return generator.resume(sent, token),
+            Err(_) => {
+                return Err(Error::GeneratorError(
+                    token.clone(),
+                    "generator is already running".to_string(),
+                ))
+            }
+        }
+    }
+
+    pub fn call_method(
+        self: &Self,
+        name: Symbol,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Error> {
+        let expected = if name == symbols::SEND { 1 } else { 0 };
+        if arguments.len() != expected {
+            return Err(Error::ArityMismatch(
+                paren.clone(),
+                expected,
+                arguments.len(),
+            ));
+        }
+        match name {
+            symbols::NEXT | symbols::SEND => {
+                let sent = arguments.into_iter().next().unwrap_or(Value::Nil);
+                match self.resume(sent, paren)? {
+                    Some(value) => return Ok(value),
+                    None => return Ok(Value::Nil),
+                }
+            }
+            symbols::CLOSE => match self.0.try_borrow_mut() {
+                Ok(mut generator) => {
+                    generator.close();
+                    return Ok(Value::Nil);
+                }
+                Err(_) => {
+                    return Err(Error::GeneratorError(
+                        paren.clone(),
+                        "cannot close a running generator".to_string(),
+                    ))
+                }
+            },
+            _ => return Ok(Value::Generator(self.clone())),
+        }
+    }
+}
@@ -0,0 +1,838 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    checker::{callee_name, collect_enums},
+    errors::Error,
+    expressions::Expression,
+    filesystem::FileHandle,
+    generators::GeneratorRef,
+    natives::{self, Permissions},
+    scanner::{Token, TokenType},
+    statements::Statement,
+    strings,
+    types::Type,
+    values::Value,
+};
+
+// A type while it is being inferred: a known type, a variable still to be
+// solved, or a function whose parts may themselves be variables.
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Known(Type),
+    Var(usize),
+    Function(Vec<Term>, Box<Term>),
+}
+
+// A term with `vars` quantified, so every use of a function gets fresh copies
+// of them and `fun id(x) { return x; }` can be used at any type.
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars: Vec<usize>,
+    term: Term,
+}
+
+impl Scheme {
+    fn mono(term: Term) -> Scheme {
+        return Scheme {
+            vars: Vec::new(),
+            term: term,
+        };
+    }
+}
+
+// Hindley–Milner style inference for `--infer`. Unannotated bindings and
+// functions get inferred types, and anything that would certainly fail at runtime,
+// like `"a" - 1`, is reported. Variables that are ever reassigned stay dynamic
+// unless annotated, and numbers of different kinds unify with each other, since
+// the language converts between them freely.
+pub struct Inferer {
+    // What each type variable has been solved to so far.
+    solutions: Vec<Option<Term>>,
+    scopes: Vec<HashMap<String, Scheme>>,
+    // The name and return term of each function being inferred, innermost last.
+    functions: Vec<(String, Term)>,
+    enums: HashSet<String>,
+    // Every name that is the target of an assignment somewhere in the program.
+    reassigned: HashSet<String>,
+    // Top level names in declaration order, for `signatures`.
+    declared: Vec<String>,
+    errors: Vec<Error>,
+}
+
+impl Inferer {
+    pub fn new() -> Self {
+        let globals = natives::globals(Permissions::default())
+            .iter()
+            .map(|(name, value)| (name.to_string(), Scheme::mono(from_type(&Type::of(value)))))
+            .collect();
+        return Inferer {
+            solutions: Vec::new(),
+            scopes: vec![globals],
+            functions: Vec::new(),
+            enums: natives::enum_names(),
+            reassigned: HashSet::new(),
+            declared: Vec::new(),
+            errors: Vec::new(),
+        };
+    }
+
+    pub fn infer(self: &mut Self, statements: &Vec<Statement>) -> Vec<Error> {
+        collect_enums(statements, &mut self.enums);
+        for statement in statements {
+            collect_assigned(statement, &mut self.reassigned);
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+        return std::mem::take(&mut self.errors);
+    }
+
+    // `name : type` for every top level binding, once `infer` has run.
+    pub fn signatures(self: &Self) -> Vec<String> {
+        let mut signatures = Vec::new();
+        for name in &self.declared {
+            if let Some(scheme) = self.scopes[0].get(name) {
+                let mut names = HashMap::new();
+                let term = self.zonk(&scheme.term);
+                signatures.push(format!("{} : {}", name, self.describe(&term, &mut names)));
+            }
+        }
+        return signatures;
+    }
+
+    fn report(self: &mut Self, token: &Token, message: String) {
+        self.errors.push(Error::TypeError(token.clone(), message));
+    }
+
+    fn fresh(self: &mut Self) -> Term {
+        self.solutions.push(None);
+        return Term::Var(self.solutions.len() - 1);
+    }
+
+    // Follows solved variables until reaching a known type, function or unsolved variable.
+    fn prune(self: &Self, term: &Term) -> Term {
+        match term {
+            Term::Var(var) => match &self.solutions[*var] {
+                Some(solution) => return self.prune(solution),
+                None => return term.clone(),
+            },
+            other => return other.clone(),
+        }
+    }
+
+    // Substitutes every solved variable, all the way down.
+    fn zonk(self: &Self, term: &Term) -> Term {
+        match self.prune(term) {
+            Term::Function(params, returns) => {
+                let params = params.iter().map(|param| self.zonk(param)).collect();
+                return Term::Function(params, Box::new(self.zonk(&returns)));
+            }
+            other => return other,
+        }
+    }
+
+    fn free_vars(self: &Self, term: &Term, vars: &mut Vec<usize>) {
+        match self.prune(term) {
+            Term::Var(var) if !vars.contains(&var) => vars.push(var),
+            Term::Function(params, returns) => {
+                for param in &params {
+                    self.free_vars(param, vars);
+                }
+                self.free_vars(&returns, vars);
+            }
+            _ => {}
+        }
+    }
+
+    fn occurs(self: &Self, var: usize, term: &Term) -> bool {
+        let mut vars = Vec::new();
+        self.free_vars(term, &mut vars);
+        return vars.contains(&var);
+    }
+
+    fn unify(self: &mut Self, left: &Term, right: &Term) -> bool {
+        match (self.prune(left), self.prune(right)) {
+            (Term::Var(x), Term::Var(y)) if x == y => return true,
+            (Term::Var(var), term) | (term, Term::Var(var)) => {
+                if self.occurs(var, &term) {
+                    return false;
+                }
+                self.solutions[var] = Some(term);
+                return true;
+            }
+            (Term::Known(Type::Any), _) | (_, Term::Known(Type::Any)) => return true,
+            (Term::Function(x, x_returns), Term::Function(y, y_returns)) => {
+                if x.len() != y.len() {
+                    return false;
+                }
+                for (x, y) in x.iter().zip(&y) {
+                    if !self.unify(x, y) {
+                        return false;
+                    }
+                }
+                return self.unify(&x_returns, &y_returns);
+            }
+            (Term::Function(..), Term::Known(Type::Function(_)))
+            | (Term::Known(Type::Function(_)), Term::Function(..)) => return true,
+            (Term::Known(x), Term::Known(y)) => return x.accepts(&y) || y.accepts(&x),
+            _ => return false,
+        }
+    }
+
+    fn generalize(self: &Self, term: &Term) -> Scheme {
+        let mut vars = Vec::new();
+        self.free_vars(term, &mut vars);
+        let mut bound = Vec::new();
+        for scope in &self.scopes {
+            for scheme in scope.values() {
+                let mut free = Vec::new();
+                self.free_vars(&scheme.term, &mut free);
+                bound.extend(free.into_iter().filter(|var| !scheme.vars.contains(var)));
+            }
+        }
+        vars.retain(|var| !bound.contains(var));
+        return Scheme {
+            vars: vars,
+            term: self.zonk(term),
+        };
+    }
+
+    fn instantiate(self: &mut Self, scheme: &Scheme) -> Term {
+        let fresh: HashMap<usize, Term> =
+            scheme.vars.iter().map(|var| (*var, self.fresh())).collect();
+        return substitute(&self.zonk(&scheme.term), &fresh);
+    }
+
+    // A readable spelling of a term, naming its unsolved variables 'a, 'b and so on.
+    fn describe(self: &Self, term: &Term, names: &mut HashMap<usize, String>) -> String {
+        match self.prune(term) {
+            Term::Known(ty) => return ty.to_string(),
+            Term::Var(var) => {
+                let next = format!("'{}", (b'a' + (names.len() % 26) as u8) as char);
+                return names.entry(var).or_insert(next).clone();
+            }
+            Term::Function(params, returns) => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|param| self.describe(param, names))
+                    .collect();
+                let returns = self.describe(&returns, names);
+                return format!("({}) -> {}", params.join(", "), returns);
+            }
+        }
+    }
+
+    fn show(self: &Self, term: &Term) -> String {
+        return self.describe(term, &mut HashMap::new());
+    }
+
+    fn declare(self: &mut Self, name: &Token, scheme: Scheme) {
+        if self.scopes.len() == 1 && !self.declared.contains(&name.lexeme) {
+            self.declared.push(name.lexeme.clone());
+        }
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.lexeme.clone(), scheme);
+    }
+
+    fn lookup(self: &mut Self, name: &Token) -> Term {
+        for scope in self.scopes.iter().rev() {
+            if let Some(scheme) = scope.get(&name.lexeme) {
+                let scheme = scheme.clone();
+                return self.instantiate(&scheme);
+            }
+        }
+        return Term::Known(Type::Any);
+    }
+
+    // The term an annotation stands for; unknown names are reported and left dynamic.
+    fn annotation(self: &mut Self, ty: &Type, token: &Token) -> Term {
+        match ty {
+            Type::Named(name) if !self.enums.contains(name) => {
+                self.report(token, format!("unknown type '{}'", name));
+                return Term::Known(Type::Any);
+            }
+            Type::Nullable(inner) => match self.annotation(inner, token) {
+                Term::Known(Type::Any) => return Term::Known(Type::Any),
+                Term::Known(inner) => return Term::Known(Type::Nullable(Box::new(inner))),
+                other => return other,
+            },
+            other => return from_type(other),
+        }
+    }
+
+    // The term of a binding without an annotation: reassigned names stay dynamic.
+    fn unannotated(self: &mut Self, name: &Token) -> Term {
+        if self.reassigned.contains(&name.lexeme) {
+            return Term::Known(Type::Any);
+        }
+        return self.fresh();
+    }
+
+    fn statement(self: &mut Self, statement: &Statement) {
+        match statement {
+            Statement::ExpressionStatement(expression) | Statement::PrintStatement(expression) => {
+                self.expression(expression);
+            }
+            Statement::Variable(name, annotation, initializer)
+            | Statement::Const(name, annotation, initializer) => {
+                let actual = self.expression(initializer);
+                let binding = match annotation {
+                    Some(declared) => self.annotation(declared, name),
+                    None => self.unannotated(name),
+                };
+                if !self.unify(&binding, &actual) {
+                    let message = format!(
+                        "'{}' is declared {} but initialized with {}",
+                        name.lexeme,
+                        self.show(&binding),
+                        self.show(&actual)
+                    );
+                    self.report(name, message);
+                }
+                // Only plain values are generalized, as in ML's value restriction.
+                let scheme = match initializer {
+                    Expression::Variable { .. } | Expression::Literal { .. } => {
+                        self.generalize(&binding)
+                    }
+                    _ => Scheme::mono(binding),
+                };
+                self.declare(name, scheme);
+            }
+            Statement::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                for statement in statements.iter() {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+            }
+            Statement::Enum(name, _) => self.declare(name, Scheme::mono(Term::Known(Type::Any))),
+            Statement::Match(_, subject, arms) => {
+                self.expression(subject);
+                for (pattern, body) in arms {
+                    self.scopes.push(HashMap::new());
+                    for name in pattern.bindings() {
+                        self.declare(&name, Scheme::mono(Term::Known(Type::Any)));
+                    }
+                    self.statement(body);
+                    self.scopes.pop();
+                }
+            }
+            Statement::ForIn(_, variable, iterable, body) => {
+                let iterable = self.expression(iterable);
+                let item = match self.prune(&iterable) {
+                    Term::Known(Type::String) => Term::Known(Type::String),
+                    Term::Known(Type::Range) => Term::Known(Type::Int),
+                    _ => Term::Known(Type::Any),
+                };
+                self.scopes.push(HashMap::new());
+                self.declare(variable, Scheme::mono(item));
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Statement::Function(name, params, body, is_generator, signature) => {
+                let mut param_terms = Vec::new();
+                for (param, ty) in params.iter().zip(&signature.params) {
+                    let term = match ty {
+                        Type::Any => self.unannotated(param),
+                        ty => self.annotation(ty, param),
+                    };
+                    param_terms.push(term);
+                }
+                let declared_returns = match &signature.returns {
+                    Type::Any => None,
+                    ty => Some(self.annotation(ty, name)),
+                };
+                let returns = match (&declared_returns, is_generator) {
+                    (_, true) => Term::Known(Type::Generator),
+                    (Some(term), false) => term.clone(),
+                    (None, false) => self.fresh(),
+                };
+                let function = Term::Function(param_terms.clone(), Box::new(returns.clone()));
+                let binding = if self.reassigned.contains(&name.lexeme) {
+                    Term::Known(Type::Any)
+                } else {
+                    function.clone()
+                };
+                // Recursive calls see the function before it is generalized.
+                self.declare(name, Scheme::mono(binding.clone()));
+                self.scopes.push(HashMap::new());
+                for (param, term) in params.iter().zip(param_terms) {
+                    self.declare(param, Scheme::mono(term));
+                }
+                let body_returns = if *is_generator {
+                    self.fresh()
+                } else {
+                    returns.clone()
+                };
+                self.functions.push((name.lexeme.clone(), body_returns));
+                for statement in body.iter() {
+                    self.statement(statement);
+                }
+                self.functions.pop();
+                self.scopes.pop();
+                // Falling off the end of a function returns nil.
+                let falls_through = !*is_generator && !body.iter().any(has_return);
+                if falls_through && !self.unify(&returns, &Term::Known(Type::Nil)) {
+                    let message = format!(
+                        "{}() is declared to return {} but never returns a value",
+                        name.lexeme,
+                        self.show(&returns)
+                    );
+                    self.report(name, message);
+                }
+                // The monomorphic binding used for recursion must not pin the
+                // function's own type variables while generalizing.
+                self.scopes.last_mut().unwrap().remove(&name.lexeme);
+                let scheme = match binding {
+                    Term::Known(_) => Scheme::mono(binding),
+                    _ => self.generalize(&function),
+                };
+                self.declare(name, scheme);
+            }
+            Statement::Return(keyword, value) => {
+                let actual = self.expression(value);
+                if let Some((name, expected)) = self.functions.last().cloned() {
+                    if !self.unify(&expected, &actual) {
+                        let message = format!(
+                            "{}() returns {} here but {} elsewhere",
+                            name,
+                            self.show(&actual),
+                            self.show(&expected)
+                        );
+                        self.report(keyword, message);
+                    }
+                }
+            }
+        }
+    }
+
+    fn expression(self: &mut Self, expression: &Expression) -> Term {
+        match expression {
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                return self.binary(left, operator, right);
+            }
+            Expression::Unary { operator, right } => {
+                let right = self.expression(right);
+                if operator.token_type == TokenType::BANG {
+                    return Term::Known(Type::Bool);
+                }
+                if !self.numeric(&right) {
+                    let message = format!("'-' is not defined for {}", self.show(&right));
+                    self.report(operator, message);
+                    return Term::Known(Type::Any);
+                }
+                return right;
+            }
+            Expression::Grouping { expression } => return self.expression(expression),
+            Expression::Literal { value } => return Term::Known(Type::of(value)),
+            Expression::Variable { name, .. } => return self.lookup(name),
+            Expression::Assign { name, value, .. } => {
+                let actual = self.expression(value);
+                let declared = self.lookup(name);
+                if !self.unify(&declared, &actual) {
+                    let message = format!(
+                        "can not assign {} to '{}', which is declared {}",
+                        self.show(&actual),
+                        name.lexeme,
+                        self.show(&declared)
+                    );
+                    self.report(name, message);
+                }
+                return actual;
+            }
+            Expression::Get { object, name } => {
+                let object = self.expression(object);
+                return self.property(&object, name);
+            }
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let function = self.expression(callee);
+                let arguments: Vec<Term> = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect();
+                return self.call(function, callee, paren, arguments);
+            }
+            Expression::List { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+                return Term::Known(Type::List);
+            }
+            Expression::Map { entries } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                return Term::Known(Type::Map);
+            }
+            Expression::Index { object, index, .. } => {
+                let object = self.expression(object);
+                self.expression(index);
+                match self.prune(&object) {
+                    Term::Known(Type::String) => return Term::Known(Type::String),
+                    _ => return Term::Known(Type::Any),
+                }
+            }
+            Expression::Yield { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+                return Term::Known(Type::Any);
+            }
+            Expression::Range {
+                start,
+                operator,
+                end,
+                step,
+            } => {
+                let mut bounds = vec![self.expression(start), self.expression(end)];
+                if let Some(step) = step {
+                    bounds.push(self.expression(step));
+                }
+                for bound in bounds {
+                    if !self.unify(&bound, &Term::Known(Type::Int)) {
+                        let message =
+                            format!("range bounds must be int, not {}", self.show(&bound));
+                        self.report(operator, message);
+                    }
+                }
+                return Term::Known(Type::Range);
+            }
+        }
+    }
+
+    // Whether `term` can be a number, solving it to `number` if it is unknown.
+    fn numeric(self: &mut Self, term: &Term) -> bool {
+        match self.prune(term) {
+            Term::Var(_) => return self.unify(term, &Term::Known(Type::Number)),
+            Term::Known(ty) => return ty == Type::Any || ty.is_numeric(),
+            Term::Function(..) => return false,
+        }
+    }
+
+    fn binary(self: &mut Self, left: Term, operator: &Token, right: Term) -> Term {
+        let (l, r) = (self.prune(&left), self.prune(&right));
+        let known = match (&l, &r) {
+            (Term::Known(x), Term::Known(y)) => Some((x.clone(), y.clone())),
+            _ => None,
+        };
+        let ok = match operator.token_type {
+            TokenType::EQUAL_EQUAL | TokenType::BANG_EQUAL => return Term::Known(Type::Bool),
+            TokenType::GREATER
+            | TokenType::GREATER_EQUAL
+            | TokenType::LESS
+            | TokenType::LESS_EQUAL => match &known {
+                Some((x, y)) => ordered(x, y),
+                None => orderable(&l) && orderable(&r) && self.unify(&l, &r),
+            },
+            TokenType::PLUS => match &known {
+                // Strings and numbers add like they compare.
+                Some((x, y)) => ordered(x, y),
+                // Adding a number makes the other side a number too, but not
+                // necessarily of the same kind: `n + 1` is a float for a float `n`.
+                None if is_number(&l) || is_number(&r) => self.numeric(&l) && self.numeric(&r),
+                None => orderable(&l) && orderable(&r) && self.unify(&l, &r),
+            },
+            TokenType::STAR => match &known {
+                Some((Type::String, y)) | Some((y, Type::String)) => {
+                    self.unify(&Term::Known(y.clone()), &Term::Known(Type::Int))
+                }
+                Some((x, y)) => numbers_or_any(x, y),
+                // Either side may be a string repeated by the other.
+                None => !matches!(l, Term::Function(..)) && !matches!(r, Term::Function(..)),
+            },
+            _ => self.numeric(&l) && self.numeric(&r),
+        };
+        if !ok {
+            let message = format!(
+                "'{}' is not defined for {} and {}",
+                operator.lexeme,
+                self.show(&l),
+                self.show(&r)
+            );
+            self.report(operator, message);
+            return Term::Known(Type::Any);
+        }
+        match (operator.token_type.clone(), known) {
+            (
+                TokenType::GREATER
+                | TokenType::GREATER_EQUAL
+                | TokenType::LESS
+                | TokenType::LESS_EQUAL,
+                _,
+            ) => return Term::Known(Type::Bool),
+            (_, Some((x, y))) => return Term::Known(Type::of_binary(&x, operator, &y)),
+            (TokenType::PLUS, None) if is_number(&l) || is_number(&r) => {
+                return Term::Known(Type::Number)
+            }
+            (TokenType::PLUS, None) => return self.prune(&l),
+            (TokenType::STAR, None) => return Term::Known(Type::Any),
+            (_, None) => return Term::Known(Type::Number),
+        }
+    }
+
+    fn call(
+        self: &mut Self,
+        function: Term,
+        callee: &Expression,
+        paren: &Token,
+        arguments: Vec<Term>,
+    ) -> Term {
+        match self.prune(&function) {
+            Term::Function(params, returns) => {
+                if params.len() != arguments.len() {
+                    self.errors.push(Error::ArityMismatch(
+                        paren.clone(),
+                        params.len(),
+                        arguments.len(),
+                    ));
+                    return *returns;
+                }
+                self.promote_shared(&params, &arguments);
+                for (position, (param, argument)) in params.iter().zip(&arguments).enumerate() {
+                    if !self.unify(param, argument) {
+                        let message = format!(
+                            "argument {} of {}() must be {} but is {}",
+                            position + 1,
+                            callee_name(callee),
+                            self.show(param),
+                            self.show(argument)
+                        );
+                        self.report(paren, message);
+                    }
+                }
+                return *returns;
+            }
+            Term::Var(_) => {
+                let returns = self.fresh();
+                let shape = Term::Function(arguments, Box::new(returns.clone()));
+                self.unify(&function, &shape);
+                return returns;
+            }
+            Term::Known(Type::Any | Type::Function(_)) => return Term::Known(Type::Any),
+            Term::Known(ty) => {
+                self.report(paren, format!("{} is not callable", ty.to_string()));
+                return Term::Known(Type::Any);
+            }
+        }
+    }
+
+    // A variable several parameters share, like 'a in ('a, 'a) -> 'a, is solved to
+    // the kind the numbers passed for it are promoted to, so `add(1, 2.0)` is a
+    // float rather than whatever the first argument was.
+    fn promote_shared(self: &mut Self, params: &[Term], arguments: &[Term]) {
+        let mut kinds: HashMap<usize, Type> = HashMap::new();
+        for (param, argument) in params.iter().zip(arguments) {
+            match (self.prune(param), self.prune(argument)) {
+                (Term::Var(var), Term::Known(ty)) if ty.is_numeric() => {
+                    let kind = match kinds.get(&var) {
+                        Some(kind) => kind.promote(&ty),
+                        None => ty,
+                    };
+                    kinds.insert(var, kind);
+                }
+                _ => {}
+            }
+        }
+        for (var, kind) in kinds {
+            self.unify(&Term::Var(var), &Term::Known(kind));
+        }
+    }
+
+    fn property(self: &mut Self, object: &Term, name: &Token) -> Term {
+        let method = |arity: usize| {
+            Term::Function(
+                vec![Term::Known(Type::Any); arity],
+                Box::new(Term::Known(Type::Any)),
+            )
+        };
+        let ty = match self.prune(object) {
+            Term::Known(ty) => ty,
+            Term::Var(_) => return Term::Known(Type::Any),
+            Term::Function(..) => Type::Function(None),
+        };
+        match &ty {
+            Type::Module(module) => {
+                if let Some(native) = module.function(name.symbol) {
+                    return method(native.arity);
+                }
+                if module.constant(name.symbol).is_some() {
+                    return Term::Known(Type::Float);
+                }
+                self.errors.push(Error::UndefinedProperty(
+                    Value::Module(module.clone()),
+                    name.clone(),
+                ));
+                return Term::Known(Type::Any);
+            }
+            Type::String => {
+                if let Some(arity) = strings::method_arity(name.symbol) {
+                    return method(arity);
+                }
+            }
+            Type::File if FileHandle::has_method(name.symbol) => return method(0),
+            Type::Generator if GeneratorRef::has_method(name.symbol) => {
+                return Term::Known(Type::Function(None))
+            }
+            Type::Any | Type::Named(_) | Type::Nullable(_) => return Term::Known(Type::Any),
+            _ => {}
+        }
+        self.report(
+            name,
+            format!("{} has no property '{}'", ty.to_string(), name.lexeme),
+        );
+        return Term::Known(Type::Any);
+    }
+}
+
+fn from_type(ty: &Type) -> Term {
+    match ty {
+        Type::Function(Some(signature)) => {
+            let params = signature.params.iter().map(from_type).collect();
+            return Term::Function(params, Box::new(from_type(&signature.returns)));
+        }
+        other => return Term::Known(other.clone()),
+    }
+}
+
+fn substitute(term: &Term, fresh: &HashMap<usize, Term>) -> Term {
+    match term {
+        Term::Var(var) => return fresh.get(var).cloned().unwrap_or(term.clone()),
+        Term::Function(params, returns) => {
+            let params = params
+                .iter()
+                .map(|param| substitute(param, fresh))
+                .collect();
+            return Term::Function(params, Box::new(substitute(returns, fresh)));
+        }
+        Term::Known(_) => return term.clone(),
+    }
+}
+
+fn ordered(x: &Type, y: &Type) -> bool {
+    match (x, y) {
+        (Type::Any, _) | (_, Type::Any) => return true,
+        (Type::String, Type::String) => return true,
+        _ => return x.is_numeric() && y.is_numeric(),
+    }
+}
+
+fn numbers_or_any(x: &Type, y: &Type) -> bool {
+    return (*x == Type::Any || x.is_numeric()) && (*y == Type::Any || y.is_numeric());
+}
+
+fn is_number(term: &Term) -> bool {
+    return matches!(term, Term::Known(ty) if ty.is_numeric());
+}
+
+// Whether an operand of `<` or `+` may be a number or a string.
+fn orderable(term: &Term) -> bool {
+    match term {
+        Term::Known(ty) => return ordered(ty, ty),
+        Term::Var(_) => return true,
+        Term::Function(..) => return false,
+    }
+}
+
+// Whether a function body returns explicitly; nested functions do not count.
+fn has_return(statement: &Statement) -> bool {
+    match statement {
+        Statement::Return(..) => return true,
+        Statement::Block(body) => return body.iter().any(has_return),
+        Statement::Match(_, _, arms) => return arms.iter().any(|(_, body)| has_return(body)),
+        Statement::ForIn(_, _, _, body) => return has_return(body),
+        _ => return false,
+    }
+}
+
+fn collect_assigned(statement: &Statement, names: &mut HashSet<String>) {
+    match statement {
+        Statement::ExpressionStatement(expression)
+        | Statement::PrintStatement(expression)
+        | Statement::Variable(_, _, expression)
+        | Statement::Const(_, _, expression)
+        | Statement::Return(_, expression) => assigned_in(expression, names),
+        Statement::Block(body) | Statement::Function(_, _, body, _, _) => {
+            for statement in body.iter() {
+                collect_assigned(statement, names);
+            }
+        }
+        Statement::Match(_, subject, arms) => {
+            assigned_in(subject, names);
+            for (_, body) in arms {
+                collect_assigned(body, names);
+            }
+        }
+        Statement::ForIn(_, _, iterable, body) => {
+            assigned_in(iterable, names);
+            collect_assigned(body, names);
+        }
+        Statement::Enum(..) => {}
+    }
+}
+
+fn assigned_in(expression: &Expression, names: &mut HashSet<String>) {
+    match expression {
+        Expression::Assign { name, value, .. } => {
+            names.insert(name.lexeme.clone());
+            assigned_in(value, names);
+        }
+        Expression::Binary { left, right, .. } => {
+            assigned_in(left, names);
+            assigned_in(right, names);
+        }
+        Expression::Unary { right, .. } => assigned_in(right, names),
+        Expression::Grouping { expression } => assigned_in(expression, names),
+        Expression::Get { object, .. } => assigned_in(object, names),
+        Expression::Call {
+            callee, arguments, ..
+        } => {
+            assigned_in(callee, names);
+            for argument in arguments {
+                assigned_in(argument, names);
+            }
+        }
+        Expression::List { elements } => {
+            for element in elements {
+                assigned_in(element, names);
+            }
+        }
+        Expression::Map { entries } => {
+            for (key, value) in entries {
+                assigned_in(key, names);
+                assigned_in(value, names);
+            }
+        }
+        Expression::Index { object, index, .. } => {
+            assigned_in(object, names);
+            assigned_in(index, names);
+        }
+        Expression::Yield { value, .. } => {
+            if let Some(value) = value {
+                assigned_in(value, names);
+            }
+        }
+        Expression::Range {
+            start, end, step, ..
+        } => {
+            assigned_in(start, names);
+            assigned_in(end, names);
+            if let Some(step) = step {
+                assigned_in(step, names);
+            }
+        }
+        Expression::Literal { .. } | Expression::Variable { .. } => {}
+    }
+}
@@ -0,0 +1,158 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use crate::{
+    errors::Error,
+    scanner::{Token, TokenType},
+    values::Value,
+};
+
+// Integers are `IntValue`s whenever they fit in an i128 and only become
+// `BigIntValue`s beyond that, so the two never hold the same number and scripts
+// can not tell them apart.
+pub fn normalize(value: BigInt) -> Value {
+    match value.to_i128() {
+        Some(value) => return Value::IntValue(value),
+        None => return Value::BigIntValue(value),
+    }
+}
+
+pub fn to_big(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::IntValue(x) => return Some(BigInt::from(*x)),
+        Value::BigIntValue(x) => return Some(x.clone()),
+        _ => return None,
+    }
+}
+
+// Saturates to an infinity when the integer is beyond the range of a float.
+pub fn to_float(value: &BigInt) -> f64 {
+    match value.to_f64() {
+        Some(value) => return value,
+        None if value.is_negative() => return f64::NEG_INFINITY,
+        None => return f64::INFINITY,
+    }
+}
+
+// Parses an optionally signed decimal or `0x` hexadecimal integer of any size.
+pub fn parse(text: &str) -> Option<Value> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (radix, digits) = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => (16, hex),
+        None => (10, digits),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    let magnitude = BigInt::parse_bytes(digits.as_bytes(), radix)?;
+    if negative {
+        return Some(normalize(-magnitude));
+    }
+    return Some(normalize(magnitude));
+}
+
+pub fn to_hex(value: &BigInt) -> String {
+    if value.is_negative() {
+        return format!("-0x{}", value.abs().to_str_radix(16));
+    }
+    return format!("0x{}", value.to_str_radix(16));
+}
+
+// `x operator y` for two integers of any size. `/` yields a float while `~/`
+// and `%` truncate towards zero, exactly as they do for small integers.
+pub fn binary(x: &BigInt, operator: &Token, y: &BigInt) -> Result<Value, Error> {
+    match operator.token_type {
+        TokenType::PLUS => return Ok(normalize(x + y)),
+        TokenType::MINUS => return Ok(normalize(x - y)),
+        TokenType::STAR => return Ok(normalize(x * y)),
+        TokenType::SLASH => {
+            if y.is_zero() {
+                return Err(Error::ZeroDivisionError(operator.clone()));
+            }
+            return Ok(Value::FValue(to_float(x) / to_float(y)));
+        }
+        TokenType::TILDE_SLASH => {
+            if y.is_zero() {
+                return Err(Error::ZeroDivisionError(operator.clone()));
+            }
+            return Ok(normalize(x / y));
+        }
+        TokenType::MODULO => {
+            if y.is_zero() {
+                return Err(Error::ZeroDivisionError(operator.clone()));
+            }
+            return Ok(normalize(x % y));
+        }
+        TokenType::STAR_STAR => return power(x, operator, y),
+        TokenType::GREATER => return Ok(Value::from_bool(x > y)),
+        TokenType::GREATER_EQUAL => return Ok(Value::from_bool(x >= y)),
+        TokenType::LESS => return Ok(Value::from_bool(x < y)),
+        TokenType::LESS_EQUAL => return Ok(Value::from_bool(x <= y)),
+        TokenType::EQUAL_EQUAL => return Ok(Value::from_bool(x == y)),
+        TokenType::BANG_EQUAL => return Ok(Value::from_bool(x != y)),
+        _ => {
+            return Err(Error::InvalidBinaryOperation(
+                normalize(x.clone()),
+                operator.clone(),
+                normalize(y.clone()),
+            ))
+        }
+    }
+}
+
+// Results of `**` are limited to this many bits, a little over five million digits,
+// so a slip like `2 ** 3000000000` fails at once instead of running for minutes.
+const MAX_POWER_BITS: u64 = 1 << 24;
+
+// Whether `base ** exponent` may need more than `MAX_POWER_BITS` bits.
+pub fn power_too_large(base: &BigInt, exponent: u64) -> bool {
+    return base.bits().saturating_mul(exponent) > MAX_POWER_BITS;
+}
+
+// Non-negative exponents stay exact; negative ones fall back to a float.
+pub fn power(base: &BigInt, operator: &Token, exponent: &BigInt) -> Result<Value, Error> {
+    if exponent.is_negative() {
+        if base.is_zero() {
+            return Err(Error::ZeroDivisionError(operator.clone()));
+        }
+        let exponent = to_float(exponent);
+        return Ok(Value::FValue(to_float(base).powf(exponent)));
+    }
+    // Bases of 0 and ±1 stay small however large the exponent is.
+    if base.is_zero() || base.abs() == BigInt::from(1) {
+        let odd = exponent.is_odd();
+        if base.is_zero() && !exponent.is_zero() {
+            return Ok(Value::IntValue(0));
+        }
+        if base.is_negative() && odd {
+            return Ok(Value::IntValue(-1));
+        }
+        return Ok(Value::IntValue(1));
+    }
+    match exponent.to_u64() {
+        Some(exponent) if !power_too_large(base, exponent) => {
+            return Ok(normalize(base.pow(exponent as u32)))
+        }
+        _ => {
+            return Err(Error::OverflowError(
+                operator.clone(),
+                format!("{} ** {} is too large to compute", base, exponent),
+            ))
+        }
+    }
+}
+
+pub fn gcd(x: &BigInt, y: &BigInt) -> Value {
+    return normalize(x.gcd(y));
+}
+
+pub fn isqrt(x: &BigInt) -> Value {
+    return normalize(x.sqrt());
+}
@@ -1,44 +1,90 @@
+use std::{cell::RefCell, rc::Rc};
+
 use crate::{
-    environment::Environment, errors::Error,
+    bytecode::Prototype,
+    environment::Environment,
+    errors::Error,
+    functions::Function,
+    gc,
+    iteration::iterate,
+    natives::{self, Permissions},
+    scanner::Token,
     statements::Statement,
+    values::Value,
+    vm,
 };
 
+// How a statement finished: normally, or by returning from the enclosing function.
+pub enum Completion {
+    Normal,
+    Return(Value),
+}
+
+// What runs a program once it has been checked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    // Walks the syntax tree directly.
+    Tree,
+    // Compiles to bytecode and runs it on a stack VM (`--engine=vm`).
+    Vm,
+}
+
 pub struct Interpreter {
-    environment: Environment,
+    environment: Rc<RefCell<Environment>>,
+    // Whether `run` infers types for unannotated code (`--infer`) rather than
+    // only checking annotations.
+    pub infer_types: bool,
+    // Whether `run` folds constants and drops dead match arms first (`--optimize`).
+    pub optimize: bool,
+    pub engine: Engine,
 }
 
 impl Interpreter {
+    // An interpreter with no permissions granted, as used by tests and embedders.
+    #[allow(dead_code)]
     pub fn new() -> Interpreter {
+        return Interpreter::with_permissions(Permissions::default());
+    }
+
+    pub fn with_permissions(permissions: Permissions) -> Interpreter {
+        let mut globals = Environment::new();
+        natives::define_globals(&mut globals, permissions);
+        return Interpreter {
+            environment: gc::environment(globals),
+            infer_types: false,
+            optimize: false,
+            engine: Engine::Tree,
+        };
+    }
+
+    pub fn with_environment(environment: Rc<RefCell<Environment>>) -> Interpreter {
         return Interpreter {
-            environment: Environment::new(),
+            environment: environment,
+            infer_types: false,
+            optimize: false,
+            engine: Engine::Tree,
         };
     }
 
+    // The names already defined, which a program run by this interpreter may use
+    // without declaring them.
+    pub fn defined_names(self: &Self) -> Vec<String> {
+        return self.environment.borrow().names();
+    }
+
     pub fn interpret(self: &mut Self, sts: Vec<Statement>) -> Result<(), String> {
+        if self.engine == Engine::Vm {
+            return vm::run(&sts, &self.environment);
+        }
         let mut errors: Vec<Error> = Vec::new();
-        for st in sts {
-            match st {
-                Statement::ExpressionStatement(expr) => {
-                    match expr.evaluate(&mut self.environment) {
-                        Ok(_) => {}
-                        Err(err) => {
-                            return Err(err.to_string());
-                        }
-                    }
-                }
-                Statement::PrintStatement(expr) => match expr.evaluate(&mut self.environment) {
-                    Ok(value) => {
-                        print!("{}", value.to_string());
-                    }
-                    Err(err) => {
+        for st in sts.iter() {
+            match self.execute(st) {
+                Ok(_) => {}
+                Err(err) => match st {
+                    Statement::PrintStatement(_) => {
                         errors.push(err);
                     }
-                },
-                Statement::Variable(name, init) => match init.evaluate(&mut self.environment) {
-                    Ok(val) => {
-                        self.environment.define(name.lexeme, val);
-                    }
-                    Err(err) => {
+                    _ => {
                         return Err(err.to_string());
                     }
                 },
@@ -54,4 +100,108 @@ impl Interpreter {
             return Err(err_str);
         }
     }
+
+    // Runs a program compiled ahead of time, which always needs the VM.
+    pub fn interpret_compiled(self: &mut Self, program: Prototype) -> Result<(), String> {
+        return vm::execute(program, &self.environment);
+    }
+
+    pub fn execute(self: &mut Self, st: &Statement) -> Result<Completion, Error> {
+        match st {
+            Statement::ExpressionStatement(expr) => {
+                expr.evaluate(&self.environment)?;
+            }
+            Statement::PrintStatement(expr) => {
+                let value = expr.evaluate(&self.environment)?;
+                print!("{}", value.to_string());
+            }
+            Statement::Variable(name, _, init) => {
+                let value = init.evaluate(&self.environment)?;
+                self.environment.borrow_mut().define(name, value)?;
+            }
+            Statement::Const(name, _, init) => {
+                let value = init.evaluate(&self.environment)?;
+                self.environment.borrow_mut().define_constant(name, value)?;
+            }
+            Statement::Block(statements) => {
+                return self.execute_scoped(vec![], statements);
+            }
+            Statement::Enum(name, variants) => {
+                let variants = variants
+                    .iter()
+                    .map(|(variant, fields)| (variant.symbol, fields.len()))
+                    .collect();
+                self.environment
+                    .borrow_mut()
+                    .define(name, Value::EnumType(name.symbol, variants))?;
+            }
+            Statement::Match(keyword, subject, arms) => {
+                let value = subject.evaluate(&self.environment)?;
+                for (pattern, body) in arms {
+                    let mut bindings = Vec::new();
+                    if pattern.matches(&value, &self.environment, &mut bindings)? {
+                        return self.execute_scoped(bindings, std::slice::from_ref(&**body));
+                    }
+                }
+                return Err(Error::NoMatchingArm(keyword.clone(), value));
+            }
+            Statement::ForIn(keyword, name, iterable, body) => {
+                let iterable = iterable.evaluate(&self.environment)?;
+                let mut iterator = iterate(iterable, keyword)?;
+                while let Some(item) = iterator.next()? {
+                    let completion = self.execute_scoped(
+                        vec![(name.clone(), item)],
+                        std::slice::from_ref(&**body),
+                    )?;
+                    if let Completion::Return(_) = completion {
+                        return Ok(completion);
+                    }
+                }
+            }
+            Statement::Function(name, params, body, is_generator, _) => {
+                let function = Function {
+                    name: name.clone(),
+                    params: params.clone(),
+                    body: body.clone(),
+                    closure: self.environment.clone(),
+                    is_generator: *is_generator,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(name, Value::Function(gc::function(function)))?;
+            }
+            Statement::Return(_, value) => {
+                let value = value.evaluate(&self.environment)?;
+                return Ok(Completion::Return(value));
+            }
+        }
+        return Ok(Completion::Normal);
+    }
+
+    // Runs `statements` directly in the current environment.
+    pub fn execute_block(self: &mut Self, statements: &[Statement]) -> Result<Completion, Error> {
+        for st in statements {
+            let completion = self.execute(st)?;
+            if let Completion::Return(_) = completion {
+                return Ok(completion);
+            }
+        }
+        return Ok(Completion::Normal);
+    }
+
+    // Runs `statements` in a fresh scope that starts out holding `bindings`.
+    fn execute_scoped(
+        self: &mut Self,
+        bindings: Vec<(Token, Value)>,
+        statements: &[Statement],
+    ) -> Result<Completion, Error> {
+        let mut environment = Environment::new_enclosed(self.environment.clone());
+        for (name, value) in bindings {
+            environment.define(&name, value)?;
+        }
+        let previous = std::mem::replace(&mut self.environment, gc::environment(environment));
+        let result = self.execute_block(statements);
+        self.environment = previous;
+        return result;
+    }
 }
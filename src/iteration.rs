@@ -0,0 +1,95 @@
+use crate::{
+    errors::Error, expressions::call_value, filesystem::FileHandle, generators::GeneratorRef,
+    ranges::RangeIterator, scanner::Token, values::Value,
+};
+
+pub enum ValueIterator {
+    Items(std::vec::IntoIter<Value>),
+    Range(RangeIterator),
+    Generator(GeneratorRef, Token),
+    Lines(FileHandle, Token),
+    // A map following the iterator protocol; its `next()` returns nil once exhausted.
+    Protocol(Value, Token),
+}
+
+impl ValueIterator {
+    pub fn next(self: &mut Self) -> Result<Option<Value>, Error> {
+        match self {
+            ValueIterator::Items(items) => return Ok(items.next()),
+            ValueIterator::Range(range) => return Ok(range.next()),
+            ValueIterator::Generator(generator, token) => {
+                return generator.resume(Value::Nil, token)
+            }
+            ValueIterator::Lines(file, token) => match file.read_line(token)? {
+                Some(line) => return Ok(Some(Value::StringValue(line))),
+                None => return Ok(None),
+            },
+            ValueIterator::Protocol(iterator, token) => {
+                let next = match method(iterator, "next") {
+                    Some(next) => next,
+                    None => return Err(Error::NotIterable(iterator.clone(), token.clone())),
+                };
+                match call_value(next, token, vec![])? {
+                    Value::Nil => return Ok(None),
+                    value => return Ok(Some(value)),
+                }
+            }
+        }
+    }
+}
+
+// Starts iterating over `value`: strings by character, lists by element, maps by key,
+// ranges and files (line by line) lazily. A map holding an `iter` function is an
+// object following the iterator protocol instead: `iter()` returns a map holding a
+// `next` function, which is called for each item until it returns nil.
+pub fn iterate(value: Value, token: &Token) -> Result<ValueIterator, Error> {
+    if let Some(iter) = method(&value, "iter") {
+        let iterator = call_value(iter, token, vec![])?;
+        if method(&iterator, "next").is_none() {
+            return Err(Error::NotIterable(iterator, token.clone()));
+        }
+        return Ok(ValueIterator::Protocol(iterator, token.clone()));
+    }
+    match value {
+        Value::StringValue(x) => {
+            let chars: Vec<Value> = x
+                .chars()
+                .map(|c| Value::StringValue(c.to_string()))
+                .collect();
+            return Ok(ValueIterator::Items(chars.into_iter()));
+        }
+        Value::ListValue(items) => {
+            return Ok(ValueIterator::Items(items.borrow().clone().into_iter()));
+        }
+        Value::RangeValue(range) => return Ok(ValueIterator::Range(range.iter())),
+        Value::Generator(generator) => {
+            return Ok(ValueIterator::Generator(generator, token.clone()));
+        }
+        Value::File(file) => return Ok(ValueIterator::Lines(file, token.clone())),
+        Value::MapValue(entries) => {
+            let keys: Vec<Value> = entries
+                .borrow()
+                .iter()
+                .map(|(key, _)| key.clone())
+                .collect();
+            return Ok(ValueIterator::Items(keys.into_iter()));
+        }
+        other => return Err(Error::NotIterable(other, token.clone())),
+    }
+}
+
+// The function a map holds under the key `name`, for maps used as objects.
+fn method(value: &Value, name: &str) -> Option<Value> {
+    let entries = match value {
+        Value::MapValue(entries) => entries.borrow(),
+        _ => return None,
+    };
+    let key = Value::StringValue(name.to_string());
+    match entries.iter().find(|(k, _)| *k == key) {
+        Some((
+            _,
+            function @ (Value::Function(_) | Value::Closure(_) | Value::NativeFunction(_)),
+        )) => return Some(function.clone()),
+        _ => return None,
+    }
+}
@@ -1,14 +1,47 @@
+mod bytecode;
+mod cache;
+mod checker;
+mod compiler;
+mod disassembler;
 mod environment;
 mod errors;
 mod expressions;
+mod filesystem;
+mod functions;
+mod gc;
+mod generators;
+mod inference;
+mod integers;
 mod interpreter;
+mod iteration;
+mod math;
+mod natives;
+mod numbers;
+mod optimizer;
 mod parser;
+mod patterns;
+mod ranges;
+mod resolver;
 mod scanner;
 mod statements;
+mod strings;
+mod symbols;
 mod tests;
+mod tpc;
+mod types;
+mod values;
+mod vm;
 
-use interpreter::Interpreter;
+use checker::Checker;
+use compiler::Compiler;
+use disassembler::disassemble;
+use inference::Inferer;
+use interpreter::{Engine, Interpreter};
+use natives::Permissions;
+use optimizer::Optimizer;
 use parser::Parser;
+use resolver::Resolver;
+use statements::Statement;
 
 use crate::scanner::*;
 use colored::Colorize;
@@ -16,6 +49,42 @@ use std::io::{self, Write};
 
 use std::{ffi::OsStr, path::Path};
 
+// How programs are checked and prepared before they run, as chosen on the command line.
+#[derive(Clone, Copy)]
+struct Options {
+    infer: bool,
+    optimize: bool,
+    engine: Engine,
+    // Whether the VM may reuse bytecode cached by an earlier run (`--no-cache`).
+    cache: bool,
+}
+
+impl Options {
+    fn apply(self: &Self, interpreter: &mut Interpreter) {
+        interpreter.infer_types = self.infer;
+        interpreter.optimize = self.optimize;
+        interpreter.engine = self.engine;
+    }
+}
+
+// Removes `flag` from the arguments, returning whether it was given.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(position) => {
+            args.remove(position);
+            return true;
+        }
+        None => return false,
+    }
+}
+
+// Removes a `--name=value` argument, returning its value if it was given.
+fn take_value(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let position = args.iter().position(|arg| arg.starts_with(&prefix))?;
+    return Some(args.remove(position)[prefix.len()..].to_string());
+}
+
 fn open_file(filename: String) -> Result<String, String> {
     match std::fs::read_to_string(filename.clone()) {
         Ok(content) => return Ok(content),
@@ -23,11 +92,15 @@ fn open_file(filename: String) -> Result<String, String> {
     }
 }
 
-fn run_file(filename: String) -> Result<(), String> {
-    let mut interpreter = Interpreter::new();
+fn run_file(filename: String, permissions: Permissions, options: Options) -> Result<(), String> {
+    let mut interpreter = Interpreter::with_permissions(permissions);
+    options.apply(&mut interpreter);
     match open_file(filename) {
         Ok(contents) => {
             println!("{}", contents);
+            if options.engine == Engine::Vm && options.cache {
+                return run_cached(contents, &mut interpreter, permissions, options);
+            }
             match run(contents, &mut interpreter) {
                 Ok(_) => {
                     return Ok(());
@@ -44,18 +117,62 @@ fn run_file(filename: String) -> Result<(), String> {
 }
 
 fn run(contents: String, interpreter: &mut Interpreter) -> Result<(), String> {
+    let statements = prepare(contents, interpreter)?;
+    match interpreter.interpret(statements) {
+        Ok(_) => (),
+        Err(e) => return Err(e.to_string()),
+    }
+    return Ok(());
+}
+
+// Runs a program on the VM, reusing the bytecode an earlier run cached for the same
+// source and options, and caching it otherwise.
+fn run_cached(
+    contents: String,
+    interpreter: &mut Interpreter,
+    permissions: Permissions,
+    options: Options,
+) -> Result<(), String> {
+    let directory = cache::directory();
+    let flags = [options.infer, options.optimize, permissions.file_access];
+    let key = cache::key(&contents, &flags);
+    let program = match cache::load(&directory, key, &contents) {
+        Some(program) => program,
+        None => {
+            let statements = prepare(contents.clone(), interpreter)?;
+            let program = Compiler::new().compile(&statements);
+            cache::store(&directory, key, &contents, &program);
+            program
+        }
+    };
+    return interpreter.interpret_compiled(program);
+}
+
+// Scans, parses, resolves and checks a program, and optimizes it when asked to,
+// leaving it ready for either engine.
+fn prepare(contents: String, interpreter: &Interpreter) -> Result<Vec<Statement>, String> {
     let mut sc = Scanner::new(contents.as_bytes());
     match sc.scan_tokens() {
         Ok(tokens) => {
             let mut parser = Parser::new(tokens.clone());
             match parser.parse() {
                 Ok(statements) => {
-                    match interpreter.interpret(statements) {
-                        Ok(_) => (),
-                        Err(e) => return Err(e.to_string()),
+                    let errors = Resolver::new(interpreter.defined_names()).resolve(&statements);
+                    if errors.len() != 0 {
+                        return Err(report(errors));
                     }
-
-                    return Ok(());
+                    let errors = if interpreter.infer_types {
+                        Inferer::new().infer(&statements)
+                    } else {
+                        Checker::new().check(&statements)
+                    };
+                    if errors.len() != 0 {
+                        return Err(report(errors));
+                    }
+                    if interpreter.optimize {
+                        return Optimizer::new().optimize(statements).map_err(report);
+                    }
+                    return Ok(statements);
                 }
                 Err(e) => {
                     return Err(format!("{}", e));
@@ -68,17 +185,113 @@ fn run(contents: String, interpreter: &mut Interpreter) -> Result<(), String> {
     }
 }
 
+// Disassembles a compiled file, or a source file after compiling it.
+fn dump_file(
+    filename: String,
+    permissions: Permissions,
+    options: Options,
+) -> Result<String, String> {
+    if check_extension(filename.clone(), "tpc".to_string()) {
+        return Ok(disassemble(&load_compiled(filename)?.program));
+    }
+    let mut interpreter = Interpreter::with_permissions(permissions);
+    options.apply(&mut interpreter);
+    let statements = prepare(open_file(filename)?, &interpreter)?;
+    return Ok(disassemble(&Compiler::new().compile(&statements)));
+}
+
+// Checks and compiles a source file, saving the bytecode to `output` in the `.tpc`
+// format along with a hash of the source.
+fn compile_file(
+    filename: String,
+    output: String,
+    permissions: Permissions,
+    options: Options,
+) -> Result<(), String> {
+    let mut interpreter = Interpreter::with_permissions(permissions);
+    options.apply(&mut interpreter);
+    let contents = open_file(filename)?;
+    let hash = tpc::source_hash(&contents);
+    let statements = prepare(contents, &interpreter)?;
+    let program = Compiler::new().compile(&statements);
+    match std::fs::write(&output, tpc::write(&program, Some(hash))) {
+        Ok(_) => return Ok(()),
+        Err(e) => return Err(format!("Unable to write {} : {}", output, e)),
+    }
+}
+
+fn load_compiled(filename: String) -> Result<tpc::Compiled, String> {
+    match std::fs::read(filename) {
+        Ok(bytes) => return tpc::read(&bytes),
+        Err(_e) => Err("File not found !".to_string()),
+    }
+}
+
+// Runs a `.tpc` file. It was checked when it was compiled, so it goes straight to
+// the VM whichever engine was asked for. A source file next to it that no longer
+// matches the hash it was compiled from is only warned about.
+fn run_compiled_file(filename: String, permissions: Permissions) -> Result<(), String> {
+    let mut interpreter = Interpreter::with_permissions(permissions);
+    let compiled = load_compiled(filename.clone())?;
+    let source = Path::new(&filename).with_extension("tp");
+    if let (Some(hash), Ok(contents)) = (compiled.source_hash, std::fs::read_to_string(&source)) {
+        if tpc::source_hash(&contents) != hash {
+            eprintln!(
+                "WARNING : {} has changed since {} was compiled",
+                source.display(),
+                filename
+            );
+        }
+    }
+    return interpreter.interpret_compiled(compiled.program);
+}
+
+fn report(errors: Vec<errors::Error>) -> String {
+    let messages: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+    return messages.join("\n");
+}
+
+// Scans, parses and type checks a file without running it. With `infer`, the
+// inferred type of every top level binding is returned.
+fn check_file(filename: String, infer: bool) -> Result<Vec<String>, String> {
+    let contents = open_file(filename)?;
+    let tokens = Scanner::new(contents.as_bytes())
+        .scan_tokens()
+        .map_err(|e| e.to_string())?;
+    let statements = Parser::new(tokens).parse().map_err(|e| e.to_string())?;
+    let globals = natives::globals(Permissions::default())
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    let errors = Resolver::new(globals).resolve(&statements);
+    if errors.len() != 0 {
+        return Err(report(errors));
+    }
+    if infer {
+        let mut inferer = Inferer::new();
+        let errors = inferer.infer(&statements);
+        if errors.len() != 0 {
+            return Err(report(errors));
+        }
+        return Ok(inferer.signatures());
+    }
+    let errors = Checker::new().check(&statements);
+    if errors.len() != 0 {
+        return Err(report(errors));
+    }
+    return Ok(Vec::new());
+}
+
 fn check_extension(filename: String, extension: String) -> bool {
-    let file_ext = (Path::new(filename.as_str())
+    let file_ext = Path::new(filename.as_str())
         .extension()
-        .and_then(OsStr::to_str))
-    .map(str::to_string)
-    .unwrap();
-    return file_ext == extension;
+        .and_then(OsStr::to_str);
+    return file_ext == Some(extension.as_str());
 }
 
-fn open_shell() -> Result<(), String> {
-    let mut interpreter = Interpreter::new();
+fn open_shell(permissions: Permissions, options: Options) -> Result<(), String> {
+    let mut interpreter = Interpreter::with_permissions(permissions);
+    options.apply(&mut interpreter);
     #[allow(while_true)]
     while true {
         print!("> ");
@@ -109,14 +322,75 @@ fn open_shell() -> Result<(), String> {
     return Ok(());
 }
 
+// The tree-walker recurses on the native stack for every call, so programs run on
+// a thread with room for `functions::MAX_CALL_DEPTH` nested calls.
+const STACK_SIZE: usize = 256 << 20;
+
 fn main() {
+    let worker = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(start);
+    match worker.map(|worker| worker.join()) {
+        Ok(Ok(())) => {}
+        Ok(Err(_)) => std::process::exit(101),
+        Err(e) => {
+            eprintln!("ERROR : Unable to start the interpreter : {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn start() {
     std::env::set_var("RUST_BACKTRACE", "full");
-    let args: Vec<_> = std::env::args().collect();
+    let mut args: Vec<_> = std::env::args().collect();
+    let permissions = Permissions {
+        file_access: take_flag(&mut args, "--allow-fs"),
+    };
+    let engine = match take_value(&mut args, "--engine").as_deref() {
+        None | Some("tree") => Engine::Tree,
+        Some("vm") => Engine::Vm,
+        Some(other) => {
+            eprintln!(
+                "ERROR : Unknown engine '{}', expected 'tree' or 'vm'",
+                other
+            );
+            std::process::exit(1);
+        }
+    };
+    let gc_threshold = match take_value(&mut args, "--gc-threshold") {
+        None => gc::DEFAULT_THRESHOLD,
+        Some(value) => match value.parse::<usize>() {
+            Ok(threshold) if threshold > 0 => threshold,
+            _ => {
+                eprintln!("ERROR : --gc-threshold expects a positive number of objects");
+                std::process::exit(1);
+            }
+        },
+    };
+    let gc_growth = match take_value(&mut args, "--gc-growth") {
+        None => gc::DEFAULT_GROWTH,
+        Some(value) => match value.parse::<f64>() {
+            Ok(growth) if growth >= 1.0 => growth,
+            _ => {
+                eprintln!("ERROR : --gc-growth expects a factor of at least 1");
+                std::process::exit(1);
+            }
+        },
+    };
+    gc::configure(gc_threshold, gc_growth);
+    let options = Options {
+        infer: take_flag(&mut args, "--infer"),
+        optimize: take_flag(&mut args, "--optimize"),
+        engine: engine,
+        cache: !take_flag(&mut args, "--no-cache"),
+    };
+    let dump_bytecode = take_flag(&mut args, "--dump-bytecode");
     let lang_name: String = "timepass".to_string();
     let extension: String = "tp".to_string();
+    let compiled_extension: String = "tpc".to_string();
     let filename: String;
     if args.len() == 1 {
-        match open_shell() {
+        match open_shell(permissions, options) {
             Ok(_) => {
                 // do nothing
             }
@@ -133,12 +407,107 @@ fn main() {
         filename = args[1].clone();
         if filename == "-help" {
             println!(
-                "Correct usage : {} relative/path/to/file.{}",
+                "Correct usage : {} [--allow-fs] [--infer] [--optimize] [--engine=tree|vm] [--no-cache] [--gc-threshold=N] [--gc-growth=F] relative/path/to/file.{}",
                 lang_name, extension
             );
+            println!(
+                "Show compiled bytecode : {} --dump-bytecode relative/path/to/file.{}",
+                lang_name, extension
+            );
+            println!(
+                "Compile ahead of time : {} compile relative/path/to/file.{} [-o file.{}]",
+                lang_name, extension, compiled_extension
+            );
+            println!(
+                "Clear compiled programs cached by the VM : {} cache clear",
+                lang_name
+            );
+            println!(
+                "Type check only : {} check [--infer] relative/path/to/file.{}",
+                lang_name, extension
+            );
+        } else if filename == "compile" && args.len() <= 2 {
+            eprintln!(
+                "Correct usage : {} compile relative/path/to/file.{} [-o file.{}]",
+                lang_name, extension, compiled_extension
+            );
+            std::process::exit(1);
+        } else if filename == "compile" {
+            let source = args[2].clone();
+            if !check_extension(source.clone(), extension.clone()) {
+                eprintln!("ERROR : File must be in the format of .{}", extension);
+                std::process::exit(1);
+            }
+            let output = match args.iter().position(|arg| arg == "-o") {
+                Some(position) if position + 1 < args.len() => args[position + 1].clone(),
+                _ => Path::new(&source)
+                    .with_extension(&compiled_extension)
+                    .to_string_lossy()
+                    .to_string(),
+            };
+            match compile_file(source, output.clone(), permissions, options) {
+                Ok(_) => println!("Compiled to {}", output),
+                Err(msg) => {
+                    eprintln!("ERROR : {}", msg);
+                    std::process::exit(1);
+                }
+            }
+        } else if filename == "cache" && (args.len() <= 2 || args[2] != "clear") {
+            eprintln!("Correct usage : {} cache clear", lang_name);
+            std::process::exit(1);
+        } else if filename == "cache" {
+            let directory = cache::directory();
+            match cache::clear(&directory) {
+                Ok(removed) => println!(
+                    "Removed {} cached files from {}",
+                    removed,
+                    directory.display()
+                ),
+                Err(msg) => {
+                    eprintln!("ERROR : {}", msg);
+                    std::process::exit(1);
+                }
+            }
+        } else if filename == "check" && args.len() <= 2 {
+            eprintln!(
+                "Correct usage : {} check [--infer] relative/path/to/file.{}",
+                lang_name, extension
+            );
+            std::process::exit(1);
+        } else if filename == "check" {
+            match check_file(args[2].clone(), options.infer) {
+                Ok(signatures) => {
+                    for signature in signatures {
+                        println!("{}", signature);
+                    }
+                    println!("{} : no type errors", args[2]);
+                }
+                Err(msg) => {
+                    eprintln!("{}", msg);
+                    std::process::exit(1);
+                }
+            }
         } else {
-            if check_extension(filename.clone(), extension.clone()) {
-                match run_file(filename) {
+            let compiled = check_extension(filename.clone(), compiled_extension.clone());
+            if (compiled || check_extension(filename.clone(), extension.clone())) && dump_bytecode {
+                match dump_file(filename, permissions, options) {
+                    Ok(listing) => print!("{}", listing),
+                    Err(msg) => {
+                        eprintln!("ERROR : {}", msg);
+                        std::process::exit(1);
+                    }
+                }
+            } else if compiled {
+                match run_compiled_file(filename, permissions) {
+                    Ok(_) => {
+                        println!("FINISED RUNNING FILE");
+                    }
+                    Err(msg) => {
+                        eprintln!("ERROR : {}", msg);
+                    }
+                }
+            } else if check_extension(filename.clone(), extension.clone()) {
+                match run_file(filename, permissions, options) {
                     Ok(_) => {
                         println!("FINISED RUNNING FILE");
                     }
@@ -147,7 +516,10 @@ fn main() {
                     }
                 }
             } else {
-                eprintln!("ERROR : File must be in the format of .{}", extension);
+                eprintln!(
+                    "ERROR : File must be in the format of .{} or .{}",
+                    extension, compiled_extension
+                );
             }
         }
     }
@@ -0,0 +1,345 @@
+use std::cmp::Ordering;
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, Signed};
+
+use crate::{
+    errors::Error,
+    integers,
+    natives::{invalid_argument, Module, NativeFunction},
+    numbers,
+    scanner::Token,
+    values::Value,
+};
+
+// The `math` module. Functions that have an exact integer answer (`abs`, `pow`
+// with a non-negative exponent, `floor`, `gcd`, ...) return ints; the rest
+// follow IEEE float semantics, so `math.sqrt(-1)` is nan rather than an error.
+pub const MATH: Module = Module {
+    name: "math",
+    functions: &[
+        NativeFunction {
+            name: "sqrt",
+            arity: 1,
+            function: sqrt,
+        },
+        NativeFunction {
+            name: "pow",
+            arity: 2,
+            function: pow,
+        },
+        NativeFunction {
+            name: "abs",
+            arity: 1,
+            function: abs,
+        },
+        NativeFunction {
+            name: "floor",
+            arity: 1,
+            function: floor,
+        },
+        NativeFunction {
+            name: "ceil",
+            arity: 1,
+            function: ceil,
+        },
+        NativeFunction {
+            name: "round",
+            arity: 1,
+            function: round,
+        },
+        NativeFunction {
+            name: "min",
+            arity: 2,
+            function: min,
+        },
+        NativeFunction {
+            name: "max",
+            arity: 2,
+            function: max,
+        },
+        NativeFunction {
+            name: "sin",
+            arity: 1,
+            function: sin,
+        },
+        NativeFunction {
+            name: "cos",
+            arity: 1,
+            function: cos,
+        },
+        NativeFunction {
+            name: "tan",
+            arity: 1,
+            function: tan,
+        },
+        NativeFunction {
+            name: "asin",
+            arity: 1,
+            function: asin,
+        },
+        NativeFunction {
+            name: "acos",
+            arity: 1,
+            function: acos,
+        },
+        NativeFunction {
+            name: "atan",
+            arity: 1,
+            function: atan,
+        },
+        NativeFunction {
+            name: "atan2",
+            arity: 2,
+            function: atan2,
+        },
+        NativeFunction {
+            name: "exp",
+            arity: 1,
+            function: exp,
+        },
+        NativeFunction {
+            name: "log",
+            arity: 1,
+            function: log,
+        },
+        NativeFunction {
+            name: "log2",
+            arity: 1,
+            function: log2,
+        },
+        NativeFunction {
+            name: "log10",
+            arity: 1,
+            function: log10,
+        },
+        NativeFunction {
+            name: "gcd",
+            arity: 2,
+            function: gcd,
+        },
+        NativeFunction {
+            name: "isqrt",
+            arity: 1,
+            function: isqrt,
+        },
+    ],
+    constants: &[
+        ("pi", std::f64::consts::PI),
+        ("e", std::f64::consts::E),
+        ("tau", std::f64::consts::TAU),
+        ("inf", f64::INFINITY),
+        ("nan", f64::NAN),
+    ],
+    denied: None,
+};
+
+fn number(paren: &Token, function: &str, argument: &Value) -> Result<f64, Error> {
+    match argument {
+        Value::IntValue(x) => return Ok(*x as f64),
+        Value::FValue(x) => return Ok(*x),
+        other => match numbers::to_float(other) {
+            Some(x) => return Ok(x),
+            None => return Err(invalid_argument(paren, function, "a number", other)),
+        },
+    }
+}
+
+fn integer(paren: &Token, function: &str, argument: &Value) -> Result<BigInt, Error> {
+    match integers::to_big(argument) {
+        Some(x) => return Ok(x),
+        None => return Err(invalid_argument(paren, function, "an integer", argument)),
+    }
+}
+
+// Rounds a float to an int with `rounding`, and a rational or decimal exactly with
+// `exact`; ints are already whole and pass through.
+fn to_integer(
+    paren: &Token,
+    function: &str,
+    argument: &Value,
+    rounding: fn(f64) -> f64,
+    exact: fn(&BigRational) -> BigRational,
+) -> Result<Value, Error> {
+    match argument {
+        Value::IntValue(_) | Value::BigIntValue(_) => return Ok(argument.clone()),
+        Value::FValue(x) => match BigInt::from_f64(rounding(*x)) {
+            Some(rounded) => return Ok(integers::normalize(rounded)),
+            None => {
+                return Err(Error::InvalidArgument(
+                    paren.clone(),
+                    format!("{}() cannot convert {} to an integer", function, x),
+                ))
+            }
+        },
+        Value::RationalValue(_) | Value::DecimalValue(_) => match numbers::to_rational(argument) {
+            Some(x) => return Ok(integers::normalize(exact(&x).to_integer())),
+            None => {
+                return Err(Error::InvalidArgument(
+                    paren.clone(),
+                    format!(
+                        "{}() cannot convert {} to an integer",
+                        function,
+                        argument.to_string()
+                    ),
+                ))
+            }
+        },
+        other => return Err(invalid_argument(paren, function, "a number", other)),
+    }
+}
+
+fn float_function(
+    paren: &Token,
+    function: &str,
+    arguments: &[Value],
+    operation: fn(f64) -> f64,
+) -> Result<Value, Error> {
+    let x = number(paren, function, &arguments[0])?;
+    return Ok(Value::FValue(operation(x)));
+}
+
+fn sqrt(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "sqrt", &arguments, f64::sqrt);
+}
+
+fn pow(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    match (
+        integers::to_big(&arguments[0]),
+        integers::to_big(&arguments[1]),
+    ) {
+        (Some(base), Some(exponent)) => return integers::power(&base, paren, &exponent),
+        _ => {
+            let base = number(paren, "pow", &arguments[0])?;
+            let exponent = number(paren, "pow", &arguments[1])?;
+            return Ok(Value::FValue(base.powf(exponent)));
+        }
+    }
+}
+
+fn abs(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    match &arguments[0] {
+        Value::IntValue(x) => match x.checked_abs() {
+            Some(result) => return Ok(Value::IntValue(result)),
+            None => return Ok(integers::normalize(BigInt::from(*x).abs())),
+        },
+        Value::BigIntValue(x) => return Ok(integers::normalize(x.abs())),
+        Value::FValue(x) => return Ok(Value::FValue(x.abs())),
+        Value::RationalValue(x) => return Ok(Value::RationalValue(x.abs())),
+        Value::DecimalValue(x) => return Ok(Value::DecimalValue(x.abs())),
+        other => return Err(invalid_argument(paren, "abs", "a number", other)),
+    }
+}
+
+fn floor(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return to_integer(
+        paren,
+        "floor",
+        &arguments[0],
+        f64::floor,
+        BigRational::floor,
+    );
+}
+
+fn ceil(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return to_integer(paren, "ceil", &arguments[0], f64::ceil, BigRational::ceil);
+}
+
+// Halves round away from zero, so `round(2.5)` is 3 and `round(-2.5)` is -3.
+fn round(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return to_integer(
+        paren,
+        "round",
+        &arguments[0],
+        f64::round,
+        BigRational::round,
+    );
+}
+
+// `min` and `max` return one of their arguments unchanged, keeping its type.
+fn min(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    number(paren, "min", &arguments[0])?;
+    number(paren, "min", &arguments[1])?;
+    let mut arguments = arguments;
+    if numbers::ordering(&arguments[1], &arguments[0]) == Some(Ordering::Less) {
+        return Ok(arguments.remove(1));
+    }
+    return Ok(arguments.remove(0));
+}
+
+fn max(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    number(paren, "max", &arguments[0])?;
+    number(paren, "max", &arguments[1])?;
+    let mut arguments = arguments;
+    if numbers::ordering(&arguments[1], &arguments[0]) == Some(Ordering::Greater) {
+        return Ok(arguments.remove(1));
+    }
+    return Ok(arguments.remove(0));
+}
+
+fn sin(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "sin", &arguments, f64::sin);
+}
+
+fn cos(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "cos", &arguments, f64::cos);
+}
+
+fn tan(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "tan", &arguments, f64::tan);
+}
+
+fn asin(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "asin", &arguments, f64::asin);
+}
+
+fn acos(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "acos", &arguments, f64::acos);
+}
+
+fn atan(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "atan", &arguments, f64::atan);
+}
+
+fn atan2(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let y = number(paren, "atan2", &arguments[0])?;
+    let x = number(paren, "atan2", &arguments[1])?;
+    return Ok(Value::FValue(y.atan2(x)));
+}
+
+fn exp(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "exp", &arguments, f64::exp);
+}
+
+fn log(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "log", &arguments, f64::ln);
+}
+
+fn log2(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "log2", &arguments, f64::log2);
+}
+
+fn log10(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return float_function(paren, "log10", &arguments, f64::log10);
+}
+
+fn gcd(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let a = integer(paren, "gcd", &arguments[0])?;
+    let b = integer(paren, "gcd", &arguments[1])?;
+    return Ok(integers::gcd(&a, &b));
+}
+
+// The largest integer whose square does not exceed `n`.
+fn isqrt(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let n = integer(paren, "isqrt", &arguments[0])?;
+    if n.is_negative() {
+        return Err(invalid_argument(
+            paren,
+            "isqrt",
+            "a non-negative integer",
+            &arguments[0],
+        ));
+    }
+    return Ok(integers::isqrt(&n));
+}
@@ -0,0 +1,451 @@
+use std::{collections::HashSet, io::Write};
+
+use num_bigint::BigInt;
+use num_traits::FromPrimitive;
+
+use crate::{
+    environment::Environment,
+    errors::Error,
+    filesystem, gc, integers, math, numbers,
+    scanner::{Token, TokenType},
+    symbols::{self, Symbol},
+    values::Value,
+};
+
+// A built-in function implemented in Rust. `NativeFunction::call` checks the arity
+// before `function` runs, so implementations may index their arguments directly.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub(crate) name: &'static str,
+    pub(crate) arity: usize,
+    pub(crate) function: fn(&Token, Vec<Value>) -> Result<Value, Error>,
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        return self.name == other.name;
+    }
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "<native fn {}>", self.name);
+    }
+}
+
+impl NativeFunction {
+    pub fn call(self: &Self, paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+        if arguments.len() != self.arity {
+            return Err(Error::ArityMismatch(
+                paren.clone(),
+                self.arity,
+                arguments.len(),
+            ));
+        }
+        return (self.function)(paren, arguments);
+    }
+}
+
+// A namespace of natives and numeric constants, such as `math`, reached with
+// property access (`math.sqrt(2)`).
+#[derive(Clone)]
+pub struct Module {
+    pub(crate) name: &'static str,
+    pub(crate) functions: &'static [NativeFunction],
+    pub(crate) constants: &'static [(&'static str, f64)],
+    // Why the module's members are off limits, when the host has not granted it.
+    pub(crate) denied: Option<&'static str>,
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        return self.name == other.name;
+    }
+}
+
+impl std::fmt::Debug for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "<module {}>", self.name);
+    }
+}
+
+impl Module {
+    pub fn function(self: &Self, name: Symbol) -> Option<&'static NativeFunction> {
+        let position = symbols::position(self.functions, |native| native.name, name)?;
+        return Some(&self.functions[position]);
+    }
+
+    pub fn constant(self: &Self, name: Symbol) -> Option<f64> {
+        let position = symbols::position(self.constants, |(constant, _)| constant, name)?;
+        return Some(self.constants[position].1);
+    }
+
+    pub fn get(self: &Self, name: &Token) -> Result<Value, Error> {
+        if let Some(reason) = self.denied {
+            return Err(Error::PermissionDenied(
+                name.clone(),
+                format!("{}.{} needs {}", self.name, name.lexeme, reason),
+            ));
+        }
+        if let Some(native) = self.function(name.symbol) {
+            return Ok(Value::NativeFunction(native.clone()));
+        }
+        match self.constant(name.symbol) {
+            Some(value) => return Ok(Value::FValue(value)),
+            None => {
+                return Err(Error::UndefinedProperty(
+                    Value::Module(self.clone()),
+                    name.clone(),
+                ))
+            }
+        }
+    }
+}
+
+// What the host lets scripts touch beyond the interpreter itself. Everything is
+// denied by default; the CLI grants file access with `--allow-fs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Permissions {
+    pub file_access: bool,
+}
+
+const GLOBALS: &[NativeFunction] = &[
+    NativeFunction {
+        name: "clock",
+        arity: 0,
+        function: clock,
+    },
+    NativeFunction {
+        name: "input",
+        arity: 1,
+        function: input,
+    },
+    NativeFunction {
+        name: "int",
+        arity: 1,
+        function: int,
+    },
+    NativeFunction {
+        name: "float",
+        arity: 1,
+        function: float,
+    },
+    NativeFunction {
+        name: "rational",
+        arity: 1,
+        function: rational,
+    },
+    NativeFunction {
+        name: "decimal",
+        arity: 1,
+        function: decimal,
+    },
+    NativeFunction {
+        name: "hex",
+        arity: 1,
+        function: hex,
+    },
+    NativeFunction {
+        name: "str",
+        arity: 1,
+        function: str,
+    },
+    NativeFunction {
+        name: "bool",
+        arity: 1,
+        function: bool,
+    },
+    NativeFunction {
+        name: "type",
+        arity: 1,
+        function: type_of,
+    },
+    NativeFunction {
+        name: "gc",
+        arity: 0,
+        function: gc,
+    },
+    NativeFunction {
+        name: "gc_stats",
+        arity: 0,
+        function: gc_stats,
+    },
+];
+
+// Every global a script starts out with, by name.
+pub fn globals(permissions: Permissions) -> Vec<(&'static str, Value)> {
+    let mut globals: Vec<(&'static str, Value)> = GLOBALS
+        .iter()
+        .map(|native| (native.name, Value::NativeFunction(native.clone())))
+        .collect();
+    let fs = if permissions.file_access {
+        filesystem::FS
+    } else {
+        Module {
+            denied: Some("file access, which was not granted (run with --allow-fs)"),
+            ..filesystem::FS
+        }
+    };
+    for module in [math::MATH, fs] {
+        globals.push((module.name, Value::Module(module)));
+    }
+    globals.push((
+        "Result",
+        Value::EnumType(
+            symbols::intern("Result"),
+            vec![(symbols::intern("Ok"), 1), (symbols::intern("Err"), 1)],
+        ),
+    ));
+    return globals;
+}
+
+// The enums every script starts out with, like `Result`.
+pub fn enum_names() -> HashSet<String> {
+    return globals(Permissions::default())
+        .into_iter()
+        .filter(|(_, value)| matches!(value, Value::EnumType(..)))
+        .map(|(name, _)| name.to_string())
+        .collect();
+}
+
+// What a call that can fail for reasons outside the script, like the `fs`
+// functions, returns instead of raising an error: `Result.Ok(value)` or
+// `Result.Err(message)`. The script `match`es on it and carries on either way.
+pub fn ok(value: Value) -> Value {
+    return Value::EnumValue(
+        symbols::intern("Result"),
+        symbols::intern("Ok"),
+        vec![value],
+    );
+}
+
+pub fn err(message: String) -> Value {
+    return Value::EnumValue(
+        symbols::intern("Result"),
+        symbols::intern("Err"),
+        vec![Value::StringValue(message)],
+    );
+}
+
+pub fn define_globals(environment: &mut Environment, permissions: Permissions) {
+    for (name, value) in globals(permissions) {
+        let name = Token::new(TokenType::IDENTIFIER, name.to_string(), None, 0);
+        // The global environment starts out empty, so this can never clash.
+        let _ = environment.define(&name, value);
+    }
+}
+
+pub fn invalid_argument(paren: &Token, function: &str, expected: &str, got: &Value) -> Error {
+    return Error::InvalidArgument(
+        paren.clone(),
+        format!(
+            "{}() expected {} but got {} {}",
+            function,
+            expected,
+            got.type_name(),
+            got.to_repr()
+        ),
+    );
+}
+
+fn clock(paren: &Token, _arguments: Vec<Value>) -> Result<Value, Error> {
+    match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(elapsed) => return Ok(Value::FValue(elapsed.as_secs_f64())),
+        Err(_) => {
+            return Err(Error::IoError(
+                paren.clone(),
+                "system clock is set before 1970".to_string(),
+            ))
+        }
+    }
+}
+
+// Collects garbage now, returning how many objects were reclaimed.
+fn gc(_paren: &Token, _arguments: Vec<Value>) -> Result<Value, Error> {
+    return Ok(Value::IntValue(gc::collect() as i128));
+}
+
+fn gc_stats(_paren: &Token, _arguments: Vec<Value>) -> Result<Value, Error> {
+    let stats = gc::stats();
+    let entries = [
+        ("collections", stats.collections),
+        ("freed", stats.freed),
+        ("live", stats.live),
+        ("threshold", stats.threshold),
+    ];
+    return Ok(Value::map(
+        entries
+            .iter()
+            .map(|(name, count)| {
+                return (
+                    Value::StringValue(name.to_string()),
+                    Value::IntValue(*count as i128),
+                );
+            })
+            .collect(),
+    ));
+}
+
+fn input(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let prompt = match &arguments[0] {
+        Value::StringValue(prompt) => prompt.clone(),
+        other => return Err(invalid_argument(paren, "input", "a string prompt", other)),
+    };
+    print!("{}", prompt);
+    if std::io::stdout().flush().is_err() {
+        return Err(Error::IoError(
+            paren.clone(),
+            "Error while flushing stdout !".to_string(),
+        ));
+    }
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line) {
+        Ok(_) => {
+            let line = line.trim_end_matches(['\n', '\r']).to_string();
+            return Ok(Value::StringValue(line));
+        }
+        Err(_) => {
+            return Err(Error::IoError(
+                paren.clone(),
+                "Unable to read from stdin!".to_string(),
+            ))
+        }
+    }
+}
+
+fn int(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    match &arguments[0] {
+        Value::IntValue(_) | Value::BigIntValue(_) => return Ok(arguments[0].clone()),
+        Value::RationalValue(_) | Value::DecimalValue(_) => {
+            match numbers::truncate(&arguments[0]) {
+                Some(value) => return Ok(value),
+                None => return Err(invalid_argument(paren, "int", "a number", &arguments[0])),
+            }
+        }
+        Value::FValue(x) => match BigInt::from_f64(x.trunc()) {
+            Some(value) => return Ok(integers::normalize(value)),
+            None => {
+                return Err(Error::IntegerParsingError(
+                    format!("cannot convert {} to Integer", x),
+                    paren.line_number as i128,
+                ))
+            }
+        },
+        Value::StringValue(x) => match integers::parse(x.trim()) {
+            Some(value) => return Ok(value),
+            None => {
+                return Err(Error::IntegerParsingError(
+                    format!("cannot parse \"{}\" as Integer", x),
+                    paren.line_number as i128,
+                ))
+            }
+        },
+        Value::True => return Ok(Value::IntValue(1)),
+        Value::False => return Ok(Value::IntValue(0)),
+        other => {
+            return Err(invalid_argument(
+                paren,
+                "int",
+                "a number, string or bool",
+                other,
+            ))
+        }
+    }
+}
+
+fn float(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    match &arguments[0] {
+        Value::IntValue(x) => return Ok(Value::FValue(*x as f64)),
+        Value::BigIntValue(x) => return Ok(Value::FValue(integers::to_float(x))),
+        Value::RationalValue(_) | Value::DecimalValue(_) => {
+            return Ok(Value::FValue(
+                numbers::to_float(&arguments[0]).unwrap_or(f64::NAN),
+            ))
+        }
+        Value::FValue(x) => return Ok(Value::FValue(*x)),
+        Value::StringValue(x) => match x.trim().parse::<f64>() {
+            Ok(value) => return Ok(Value::FValue(value)),
+            Err(_) => {
+                return Err(Error::FloatParsingError(
+                    format!("cannot parse \"{}\" as Float", x),
+                    paren.line_number as i128,
+                ))
+            }
+        },
+        Value::True => return Ok(Value::FValue(1.0)),
+        Value::False => return Ok(Value::FValue(0.0)),
+        other => {
+            return Err(invalid_argument(
+                paren,
+                "float",
+                "a number, string or bool",
+                other,
+            ))
+        }
+    }
+}
+
+// Converts exactly: `rational(0.75)` is 3/4 and `rational("2/6")` is 1/3. Floats
+// go through their shortest decimal spelling, as `decimal()` does.
+fn rational(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let value = match &arguments[0] {
+        Value::FValue(x) => numbers::float_to_decimal(*x)
+            .and_then(|x| numbers::to_rational(&Value::DecimalValue(x))),
+        Value::StringValue(x) => numbers::parse_rational(x.trim()),
+        other => numbers::to_rational(other),
+    };
+    match value {
+        Some(value) => return Ok(Value::RationalValue(value)),
+        None => {
+            return Err(invalid_argument(
+                paren,
+                "rational",
+                "a finite number or numeric string",
+                &arguments[0],
+            ))
+        }
+    }
+}
+
+// Rationals that do not terminate, like 1/3, are rounded to 28 significant digits.
+fn decimal(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    let value = match &arguments[0] {
+        Value::FValue(x) => numbers::float_to_decimal(*x),
+        Value::StringValue(x) => numbers::parse_decimal(x.trim()),
+        other => numbers::to_decimal(other),
+    };
+    match value {
+        Some(value) => return Ok(Value::DecimalValue(value)),
+        None => {
+            return Err(invalid_argument(
+                paren,
+                "decimal",
+                "a finite number or numeric string",
+                &arguments[0],
+            ))
+        }
+    }
+}
+
+// Formats an integer of any size in hexadecimal, e.g. `hex(255)` is "0xff".
+fn hex(paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    match integers::to_big(&arguments[0]) {
+        Some(value) => return Ok(Value::StringValue(integers::to_hex(&value))),
+        None => return Err(invalid_argument(paren, "hex", "an integer", &arguments[0])),
+    }
+}
+
+fn str(_paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return Ok(Value::StringValue(arguments[0].to_string()));
+}
+
+fn bool(_paren: &Token, mut arguments: Vec<Value>) -> Result<Value, Error> {
+    match arguments[0].is_falsy()? {
+        Value::True => return Ok(Value::False),
+        _ => return Ok(Value::True),
+    }
+}
+
+fn type_of(_paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    return Ok(Value::StringValue(arguments[0].type_name()));
+}
@@ -0,0 +1,426 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::{
+    errors::Error,
+    integers,
+    scanner::{Token, TokenType},
+    values::Value,
+};
+
+// Significant digits kept when a decimal division does not terminate.
+const DECIMAL_PRECISION: u64 = 28;
+
+// The furthest a decimal point may sit from the digits, as in `1e-1000000d`.
+// Beyond this a decimal can not be printed or made exact without running out of
+// memory, so such values are rejected instead.
+const MAX_SCALE: u32 = 1_000_000;
+
+// Where a number sits in the promotion order. Mixing two kinds of number converts
+// the lower one up to the higher one before the operator is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rank {
+    Integer,
+    Rational,
+    Decimal,
+    Float,
+}
+
+pub fn rank(value: &Value) -> Option<Rank> {
+    match value {
+        Value::IntValue(_) | Value::BigIntValue(_) => return Some(Rank::Integer),
+        Value::RationalValue(_) => return Some(Rank::Rational),
+        Value::DecimalValue(_) => return Some(Rank::Decimal),
+        Value::FValue(_) => return Some(Rank::Float),
+        _ => return None,
+    }
+}
+
+pub fn to_rational(value: &Value) -> Option<BigRational> {
+    match value {
+        Value::RationalValue(x) => return Some(x.clone()),
+        Value::DecimalValue(x) => {
+            let (digits, scale) = x.as_bigint_and_exponent();
+            let size = bounded_scale(scale)?;
+            if scale >= 0 {
+                return Some(over_power_of_ten(digits, size));
+            }
+            return Some(BigRational::from_integer(
+                digits * BigInt::from(10).pow(size),
+            ));
+        }
+        other => return Some(BigRational::from_integer(integers::to_big(other)?)),
+    }
+}
+
+// `digits / 10 ** scale` in lowest terms. The only factors the two can share are
+// twos and fives, so they are cancelled directly; a general gcd takes seconds once
+// the power of ten has a few hundred thousand digits.
+fn over_power_of_ten(digits: BigInt, scale: u32) -> BigRational {
+    if digits.is_zero() {
+        return BigRational::zero();
+    }
+    let twos = digits.trailing_zeros().unwrap_or(0).min(scale as u64) as u32;
+    let mut numerator = digits >> twos;
+    let mut fives = 0;
+    let five = BigInt::from(5);
+    while fives < scale && (&numerator % &five).is_zero() {
+        numerator /= &five;
+        fives += 1;
+    }
+    let denominator = (BigInt::from(1) << (scale - twos)) * five.pow(scale - fives);
+    return BigRational::new_raw(numerator, denominator);
+}
+
+fn bounded_scale(scale: i64) -> Option<u32> {
+    return u32::try_from(scale.unsigned_abs())
+        .ok()
+        .filter(|scale| *scale <= MAX_SCALE);
+}
+
+pub fn to_decimal(value: &Value) -> Option<BigDecimal> {
+    match value {
+        Value::DecimalValue(x) => return Some(x.clone()),
+        Value::RationalValue(x) => {
+            let numerator = BigDecimal::from(x.numer().clone());
+            let denominator = BigDecimal::from(x.denom().clone());
+            return Some(divide_decimals(&numerator, &denominator));
+        }
+        other => return Some(BigDecimal::from(integers::to_big(other)?)),
+    }
+}
+
+pub fn to_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::IntValue(x) => return Some(*x as f64),
+        Value::BigIntValue(x) => return Some(integers::to_float(x)),
+        Value::RationalValue(x) => return x.to_f64(),
+        Value::DecimalValue(x) => return x.to_f64(),
+        Value::FValue(x) => return Some(*x),
+        _ => return None,
+    }
+}
+
+// Floats convert through their shortest decimal spelling, so `decimal(0.1)` is
+// exactly 0.1 rather than the binary value nearest to it.
+pub fn float_to_decimal(value: f64) -> Option<BigDecimal> {
+    if !value.is_finite() {
+        return None;
+    }
+    return BigDecimal::from_str(&value.to_string()).ok();
+}
+
+// The shortest spelling that reads back as the same float, always with a decimal
+// point or exponent so `3.0` does not print like the integer `3`. The scanner
+// accepts the exponent form, so `1e20` is also a valid literal.
+pub fn float_to_string(value: f64) -> String {
+    return format!("{:?}", value);
+}
+
+// Exponents that would put the decimal point more than `MAX_SCALE` digits away
+// are rejected here, before anything tries to expand them.
+pub fn parse_decimal(text: &str) -> Option<BigDecimal> {
+    let value = BigDecimal::from_str(text).ok()?;
+    bounded_scale(value.as_bigint_and_exponent().1)?;
+    return Some(value);
+}
+
+// Accepts `n/d` fractions as well as anything `parse_decimal` accepts.
+pub fn parse_rational(text: &str) -> Option<BigRational> {
+    if let Some((numerator, denominator)) = text.split_once('/') {
+        let numerator = integers::to_big(&integers::parse(numerator.trim())?)?;
+        let denominator = integers::to_big(&integers::parse(denominator.trim())?)?;
+        if denominator.is_zero() {
+            return None;
+        }
+        return Some(BigRational::new(numerator, denominator));
+    }
+    return to_rational(&Value::DecimalValue(parse_decimal(text)?));
+}
+
+pub fn rational_to_string(value: &BigRational) -> String {
+    if value.is_integer() {
+        return value.numer().to_string();
+    }
+    return format!("{}/{}", value.numer(), value.denom());
+}
+
+fn divide_decimals(x: &BigDecimal, y: &BigDecimal) -> BigDecimal {
+    return (x / y).with_prec(DECIMAL_PRECISION).normalized();
+}
+
+// `left operator right` for any two numbers. Both sides are first converted to
+// the higher of their two ranks, so `1 + 0.5`, `1 + 1/2r` and `1 % 0.5` all go
+// through the same rules as their single-kind counterparts. Division with `/` is
+// true division: it is exact for rationals and decimals and a float for integers
+// and floats. Integer division with `~/` truncates towards zero and keeps the
+// kind of its operands, and `%` is the matching remainder.
+pub fn binary(left: Value, operator: &Token, right: Value) -> Result<Value, Error> {
+    let invalid = || Error::InvalidBinaryOperation(left.clone(), operator.clone(), right.clone());
+    let rank = match (rank(&left), rank(&right)) {
+        (Some(x), Some(y)) => x.max(y),
+        _ => return Err(invalid()),
+    };
+    if operator.token_type == TokenType::STAR_STAR {
+        return power(&left, operator, &right, rank);
+    }
+    let result = match rank {
+        Rank::Integer => integer_binary(&left, operator, &right),
+        Rank::Rational => {
+            let x = to_rational(&left).ok_or_else(invalid)?;
+            let y = to_rational(&right).ok_or_else(invalid)?;
+            rational_binary(x, operator, y)
+        }
+        Rank::Decimal => {
+            let x = to_decimal(&left).ok_or_else(invalid)?;
+            let y = to_decimal(&right).ok_or_else(invalid)?;
+            decimal_binary(x, operator, y)
+        }
+        Rank::Float => {
+            let x = to_float(&left).ok_or_else(invalid)?;
+            let y = to_float(&right).ok_or_else(invalid)?;
+            float_binary(x, operator, y)
+                .or_else(|| compare(float_ordering(&left, &right), operator))
+        }
+    };
+    return result.ok_or_else(invalid)?;
+}
+
+// Small integers are computed directly and only fall back to big integers when
+// the result does not fit in an i128.
+fn integer_binary(left: &Value, operator: &Token, right: &Value) -> Option<Result<Value, Error>> {
+    if let (Value::IntValue(x), Value::IntValue(y)) = (left, right) {
+        let result = match operator.token_type {
+            TokenType::PLUS => x.checked_add(*y),
+            TokenType::MINUS => x.checked_sub(*y),
+            TokenType::STAR => x.checked_mul(*y),
+            TokenType::TILDE_SLASH => x.checked_div(*y),
+            TokenType::MODULO => x.checked_rem(*y),
+            _ => None,
+        };
+        if let Some(result) = result {
+            return Some(Ok(Value::IntValue(result)));
+        }
+    }
+    let x = integers::to_big(left)?;
+    let y = integers::to_big(right)?;
+    return Some(integers::binary(&x, operator, &y));
+}
+
+fn float_binary(x: f64, operator: &Token, y: f64) -> Option<Result<Value, Error>> {
+    let zero_division = || Some(Err(Error::ZeroDivisionError(operator.clone())));
+    let result = match operator.token_type {
+        TokenType::PLUS => x + y,
+        TokenType::MINUS => x - y,
+        TokenType::STAR => x * y,
+        TokenType::SLASH | TokenType::TILDE_SLASH | TokenType::MODULO if y == 0.0 => {
+            return zero_division()
+        }
+        TokenType::SLASH => x / y,
+        TokenType::TILDE_SLASH => (x / y).trunc(),
+        TokenType::MODULO => x % y,
+        _ => return None,
+    };
+    return Some(Ok(Value::FValue(result)));
+}
+
+// Floats are compared with exact numbers by value instead of rounding the exact
+// side to a float, which would make `2 ** 53 + 1` equal to `2.0 ** 53`. The
+// infinities lie beyond every exact number and NaN is unordered with everything.
+fn float_ordering(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::FValue(x), Value::FValue(y)) => return x.partial_cmp(y),
+        (Value::FValue(x), exact) => return exact_ordering(exact, *x).map(Ordering::reverse),
+        (exact, Value::FValue(y)) => return exact_ordering(exact, *y),
+        _ => return None,
+    }
+}
+
+fn exact_ordering(exact: &Value, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    if float.is_infinite() {
+        return Some(if float > 0.0 {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        });
+    }
+    return Some(to_rational(exact)?.cmp(&BigRational::from_float(float)?));
+}
+
+// `None` when the operator does not apply to numbers at all.
+fn decimal_binary(x: BigDecimal, operator: &Token, y: BigDecimal) -> Option<Result<Value, Error>> {
+    let zero_division = || Some(Err(Error::ZeroDivisionError(operator.clone())));
+    let result = match operator.token_type {
+        TokenType::PLUS => x + y,
+        TokenType::MINUS => x - y,
+        TokenType::STAR => x * y,
+        TokenType::SLASH | TokenType::TILDE_SLASH | TokenType::MODULO if y.is_zero() => {
+            return zero_division()
+        }
+        TokenType::SLASH => divide_decimals(&x, &y),
+        TokenType::TILDE_SLASH => {
+            let quotient =
+                to_rational(&Value::DecimalValue(x))? / to_rational(&Value::DecimalValue(y))?;
+            BigDecimal::from(quotient.to_integer())
+        }
+        TokenType::MODULO => x % y,
+        _ => return compare(Some(x.cmp(&y)), operator),
+    };
+    // A scale past `MAX_SCALE` could not be compared or made exact later on.
+    if bounded_scale(result.as_bigint_and_exponent().1).is_none() {
+        return Some(Err(Error::OverflowError(
+            operator.clone(),
+            format!(
+                "the result of {} needs a decimal scale beyond {}",
+                operator.lexeme, MAX_SCALE
+            ),
+        )));
+    }
+    return Some(Ok(Value::DecimalValue(result)));
+}
+
+fn rational_binary(
+    x: BigRational,
+    operator: &Token,
+    y: BigRational,
+) -> Option<Result<Value, Error>> {
+    let zero_division = || Some(Err(Error::ZeroDivisionError(operator.clone())));
+    let result = match operator.token_type {
+        TokenType::PLUS => x + y,
+        TokenType::MINUS => x - y,
+        TokenType::STAR => x * y,
+        TokenType::SLASH | TokenType::TILDE_SLASH | TokenType::MODULO if y.is_zero() => {
+            return zero_division()
+        }
+        TokenType::SLASH => x / y,
+        TokenType::TILDE_SLASH => (x / y).trunc(),
+        TokenType::MODULO => x % y,
+        _ => return compare(Some(x.cmp(&y)), operator),
+    };
+    return Some(Ok(Value::RationalValue(result)));
+}
+
+// An ordering of `None` means the operands are unordered (a NaN is involved), in
+// which case only `!=` holds.
+fn compare(ordering: Option<Ordering>, operator: &Token) -> Option<Result<Value, Error>> {
+    let holds = |test: fn(Ordering) -> bool| ordering.is_some_and(test);
+    let result = match operator.token_type {
+        TokenType::GREATER => holds(Ordering::is_gt),
+        TokenType::GREATER_EQUAL => holds(Ordering::is_ge),
+        TokenType::LESS => holds(Ordering::is_lt),
+        TokenType::LESS_EQUAL => holds(Ordering::is_le),
+        TokenType::EQUAL_EQUAL => holds(Ordering::is_eq),
+        TokenType::BANG_EQUAL => !holds(Ordering::is_eq),
+        _ => return None,
+    };
+    return Some(Ok(Value::from_bool(result)));
+}
+
+// How two numbers of any kinds compare, or `None` when either is not a number or
+// a NaN is involved.
+pub fn ordering(left: &Value, right: &Value) -> Option<Ordering> {
+    match rank(left)?.max(rank(right)?) {
+        Rank::Integer => return Some(integers::to_big(left)?.cmp(&integers::to_big(right)?)),
+        Rank::Rational => return Some(to_rational(left)?.cmp(&to_rational(right)?)),
+        Rank::Decimal => return Some(to_decimal(left)?.cmp(&to_decimal(right)?)),
+        Rank::Float => return float_ordering(left, right),
+    }
+}
+
+// Numeric equality across kinds, so `1 == 1.0` and `1/2r == 0.5d`. Non-numbers
+// are never equal to a number.
+pub fn equals(left: &Value, right: &Value) -> bool {
+    return ordering(left, right) == Some(Ordering::Equal);
+}
+
+// Whole exponents keep the base exact (negative ones make an integer base a
+// float, as `/` does); any other exponent falls back to a float.
+fn power(base: &Value, operator: &Token, exponent: &Value, rank: Rank) -> Result<Value, Error> {
+    let invalid =
+        || Error::InvalidBinaryOperation(base.clone(), operator.clone(), exponent.clone());
+    let whole = match to_rational(exponent) {
+        Some(exponent) if exponent.is_integer() => Some(exponent.to_integer()),
+        _ => None,
+    };
+    let too_large = |n: &BigInt| {
+        Error::OverflowError(
+            operator.clone(),
+            format!("{} ** {} is too large to compute", base.to_string(), n),
+        )
+    };
+    match (rank, whole) {
+        (Rank::Integer, Some(n)) => {
+            let x = integers::to_big(base).ok_or_else(invalid)?;
+            return integers::power(&x, operator, &n);
+        }
+        (Rank::Rational, Some(n)) => {
+            let x = to_rational(base).ok_or_else(invalid)?;
+            let n = n.to_i32().ok_or_else(|| too_large(&n))?;
+            if x.is_zero() && n < 0 {
+                return Err(Error::ZeroDivisionError(operator.clone()));
+            }
+            let size = n.unsigned_abs() as u64;
+            if integers::power_too_large(x.numer(), size)
+                || integers::power_too_large(x.denom(), size)
+            {
+                return Err(too_large(&BigInt::from(n)));
+            }
+            return Ok(Value::RationalValue(x.pow(n)));
+        }
+        (Rank::Decimal, Some(n)) => {
+            let x = to_decimal(base).ok_or_else(invalid)?;
+            let n = n.to_i64().ok_or_else(|| too_large(&n))?;
+            if x.is_zero() && n < 0 {
+                return Err(Error::ZeroDivisionError(operator.clone()));
+            }
+            // Both the digits and the distance of the point from them grow with `n`.
+            let (digits, scale) = x.as_bigint_and_exponent();
+            if integers::power_too_large(&digits, n.unsigned_abs())
+                || bounded_scale(scale.saturating_mul(n)).is_none()
+            {
+                return Err(too_large(&BigInt::from(n)));
+            }
+            return Ok(Value::DecimalValue(x.powi(n).normalized()));
+        }
+        _ => {
+            let x = to_float(base).ok_or_else(invalid)?;
+            let y = to_float(exponent).ok_or_else(invalid)?;
+            return Ok(Value::FValue(x.powf(y)));
+        }
+    }
+}
+
+pub fn negate(value: &Value) -> Option<Value> {
+    match value {
+        Value::IntValue(x) => match x.checked_neg() {
+            Some(x) => return Some(Value::IntValue(x)),
+            None => return Some(integers::normalize(-BigInt::from(*x))),
+        },
+        Value::BigIntValue(x) => return Some(integers::normalize(-x)),
+        Value::FValue(x) => return Some(Value::FValue(-x)),
+        Value::RationalValue(x) => return Some(Value::RationalValue(-x)),
+        Value::DecimalValue(x) => return Some(Value::DecimalValue(-x)),
+        _ => return None,
+    }
+}
+
+pub fn is_zero(value: &Value) -> bool {
+    match value {
+        Value::RationalValue(x) => return x.is_zero(),
+        Value::DecimalValue(x) => return x.is_zero(),
+        _ => return false,
+    }
+}
+
+// Truncates towards zero, like `int()` does for floats.
+pub fn truncate(value: &Value) -> Option<Value> {
+    let rational = to_rational(value)?;
+    return Some(integers::normalize(rational.to_integer()));
+}
@@ -0,0 +1,303 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    errors::Error,
+    expressions::{equals, Expression},
+    patterns::Pattern,
+    scanner::{Token, TokenType},
+    statements::Statement,
+    values::Value,
+};
+
+// The optional pass run with `--optimize` after checking. It folds operators whose
+// operands are all literals by evaluating them exactly as the interpreter would,
+// string concatenation included. The language has no `if`, so the dead branches it
+// removes are the `match` arms a literal subject can never reach. A folded operation
+// that fails, like `1 / 0`, would fail every time it ran, so it is reported
+// instead of being left for runtime.
+pub struct Optimizer {
+    errors: Vec<Error>,
+}
+
+impl Optimizer {
+    pub fn new() -> Self {
+        return Optimizer { errors: Vec::new() };
+    }
+
+    pub fn optimize(
+        mut self: Self,
+        statements: Vec<Statement>,
+    ) -> Result<Vec<Statement>, Vec<Error>> {
+        let statements = self.statements(statements);
+        if self.errors.len() != 0 {
+            return Err(self.errors);
+        }
+        return Ok(statements);
+    }
+
+    fn statements(self: &mut Self, statements: Vec<Statement>) -> Vec<Statement> {
+        return statements
+            .into_iter()
+            .map(|statement| self.statement(statement))
+            .collect();
+    }
+
+    // Bodies are only shared once something holds on to them at runtime, so a
+    // freshly parsed one can always be taken apart.
+    fn body(self: &mut Self, body: Rc<Vec<Statement>>) -> Rc<Vec<Statement>> {
+        match Rc::try_unwrap(body) {
+            Ok(statements) => return Rc::new(self.statements(statements)),
+            Err(shared) => return shared,
+        }
+    }
+
+    fn nested(self: &mut Self, statement: Rc<Statement>) -> Rc<Statement> {
+        match Rc::try_unwrap(statement) {
+            Ok(statement) => return Rc::new(self.statement(statement)),
+            Err(shared) => return shared,
+        }
+    }
+
+    fn statement(self: &mut Self, statement: Statement) -> Statement {
+        match statement {
+            Statement::ExpressionStatement(expression) => {
+                return Statement::ExpressionStatement(self.expression(expression))
+            }
+            Statement::PrintStatement(expression) => {
+                return Statement::PrintStatement(self.expression(expression))
+            }
+            Statement::Variable(name, annotation, initializer) => {
+                return Statement::Variable(name, annotation, self.expression(initializer))
+            }
+            Statement::Const(name, annotation, initializer) => {
+                return Statement::Const(name, annotation, self.expression(initializer))
+            }
+            Statement::Block(statements) => return Statement::Block(self.body(statements)),
+            // Unreachable arms are dropped before any body is folded, so an error in
+            // an arm that can never run is not reported.
+            Statement::Match(keyword, subject, arms) => {
+                let subject = self.expression(subject);
+                match reachable_arms(keyword, subject, arms) {
+                    Statement::Match(keyword, subject, arms) => {
+                        let arms = arms
+                            .into_iter()
+                            .map(|(pattern, body)| (pattern, self.nested(body)))
+                            .collect();
+                        return Statement::Match(keyword, subject, arms);
+                    }
+                    statement => return self.statement(statement),
+                }
+            }
+            Statement::ForIn(keyword, name, iterable, body) => {
+                let iterable = self.expression(iterable);
+                return Statement::ForIn(keyword, name, iterable, self.nested(body));
+            }
+            Statement::Function(name, params, body, is_generator, signature) => {
+                let body = self.body(body);
+                return Statement::Function(name, params, body, is_generator, signature);
+            }
+            Statement::Return(keyword, value) => {
+                return Statement::Return(keyword, self.expression(value))
+            }
+            Statement::Enum(..) => return statement,
+        }
+    }
+
+    fn expression(self: &mut Self, expression: Expression) -> Expression {
+        let expression = match expression {
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            } => Expression::Binary {
+                left: Box::new(self.expression(*left)),
+                operator: operator,
+                right: Box::new(self.expression(*right)),
+            },
+            Expression::Unary { operator, right } => Expression::Unary {
+                operator: operator,
+                right: Box::new(self.expression(*right)),
+            },
+            Expression::Grouping { expression } => match self.expression(*expression) {
+                Expression::Literal { value } => return Expression::Literal { value: value },
+                expression => Expression::Grouping {
+                    expression: Box::new(expression),
+                },
+            },
+            Expression::Assign {
+                name,
+                value,
+                resolution,
+            } => {
+                return Expression::Assign {
+                    name: name,
+                    value: Box::new(self.expression(*value)),
+                    resolution: resolution,
+                }
+            }
+            Expression::Get { object, name } => {
+                return Expression::Get {
+                    object: Box::new(self.expression(*object)),
+                    name: name,
+                }
+            }
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                return Expression::Call {
+                    callee: Box::new(self.expression(*callee)),
+                    paren: paren,
+                    arguments: self.expressions(arguments),
+                }
+            }
+            Expression::List { elements } => {
+                return Expression::List {
+                    elements: self.expressions(elements),
+                }
+            }
+            Expression::Map { entries } => {
+                return Expression::Map {
+                    entries: entries
+                        .into_iter()
+                        .map(|(key, value)| (self.expression(key), self.expression(value)))
+                        .collect(),
+                }
+            }
+            Expression::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                return Expression::Index {
+                    object: Box::new(self.expression(*object)),
+                    bracket: bracket,
+                    index: Box::new(self.expression(*index)),
+                }
+            }
+            Expression::Yield { keyword, value } => {
+                return Expression::Yield {
+                    keyword: keyword,
+                    value: value.map(|value| Box::new(self.expression(*value))),
+                }
+            }
+            Expression::Range {
+                start,
+                operator,
+                end,
+                step,
+            } => {
+                return Expression::Range {
+                    start: Box::new(self.expression(*start)),
+                    operator: operator,
+                    end: Box::new(self.expression(*end)),
+                    step: step.map(|step| Box::new(self.expression(*step))),
+                }
+            }
+            Expression::Literal { .. } | Expression::Variable { .. } => return expression,
+        };
+        if !foldable(&expression) {
+            return expression;
+        }
+        // Only literals are read, so an empty environment is all evaluation needs.
+        match expression.evaluate(&Rc::new(RefCell::new(Environment::new()))) {
+            Ok(value) => return Expression::Literal { value: value },
+            Err(err) => {
+                self.errors.push(err);
+                return expression;
+            }
+        }
+    }
+
+    fn expressions(self: &mut Self, expressions: Vec<Expression>) -> Vec<Expression> {
+        return expressions
+            .into_iter()
+            .map(|expression| self.expression(expression))
+            .collect();
+    }
+}
+
+fn is_literal(expression: &Expression) -> bool {
+    return matches!(expression, Expression::Literal { .. });
+}
+
+// Strings repeated with `*` are only folded up to this many bytes, so a large
+// count can not blow up the program text.
+const MAX_FOLDED_LENGTH: i128 = 1 << 10;
+
+// Whether `expression` is an operator applied to literals only, short of a string
+// repetition whose result would be longer than `MAX_FOLDED_LENGTH`.
+fn foldable(expression: &Expression) -> bool {
+    match expression {
+        Expression::Unary { right, .. } => return is_literal(right),
+        Expression::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            let repeated = match (&**left, &**right) {
+                (
+                    Expression::Literal {
+                        value: Value::StringValue(text),
+                    },
+                    Expression::Literal {
+                        value: Value::IntValue(count),
+                    },
+                )
+                | (
+                    Expression::Literal {
+                        value: Value::IntValue(count),
+                    },
+                    Expression::Literal {
+                        value: Value::StringValue(text),
+                    },
+                ) => (text.len() as i128).saturating_mul(*count),
+                _ => 0,
+            };
+            if operator.token_type == TokenType::STAR && repeated > MAX_FOLDED_LENGTH {
+                return false;
+            }
+            return is_literal(left) && is_literal(right);
+        }
+        _ => return false,
+    }
+}
+
+// Drops the arms a literal subject can not match. When the first arm it can reach
+// matches for certain and binds nothing, the whole match becomes that arm's body.
+fn reachable_arms(
+    keyword: Token,
+    subject: Expression,
+    arms: Vec<(Pattern, Rc<Statement>)>,
+) -> Statement {
+    let value = match &subject {
+        Expression::Literal { value } => value.clone(),
+        _ => return Statement::Match(keyword, subject, arms),
+    };
+    let mut reachable = Vec::new();
+    for (pattern, body) in arms {
+        let matches = match &pattern {
+            Pattern::Wildcard => Some(true),
+            Pattern::Literal(literal) => Some(equals(literal, &value)),
+            Pattern::Range(range) => match value {
+                Value::IntValue(x) => Some(range.contains(x)),
+                _ => Some(false),
+            },
+            // Bindings always match and variants are checked against the enum at
+            // runtime, so everything from here on stays.
+            Pattern::Binding(_) | Pattern::Variant { .. } => None,
+        };
+        match matches {
+            Some(true) if reachable.len() == 0 => match Rc::try_unwrap(body) {
+                Ok(body) => return Statement::Block(Rc::new(vec![body])),
+                Err(shared) => return Statement::Match(keyword, subject, vec![(pattern, shared)]),
+            },
+            Some(false) => {}
+            _ => reachable.push((pattern, body)),
+        }
+    }
+    // With no arm left, the match still fails at runtime with the same error.
+    return Statement::Match(keyword, subject, reachable);
+}
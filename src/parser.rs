@@ -1,13 +1,32 @@
+use std::{cell::Cell, collections::HashMap, rc::Rc};
+
 use crate::{
     errors::Error,
     expressions::Expression,
-    scanner::{LiteralValue, Token, TokenType},
+    numbers,
+    patterns::Pattern,
+    ranges::Range,
+    resolver::Resolution,
+    scanner::{Token, TokenType},
     statements::Statement,
+    types::{Signature, Type},
+    values::Value,
 };
 
 pub struct Parser {
     pub(crate) tokens: Vec<Token>,
     current: usize,
+    // Names declared in each open scope; constants carry their declaration line.
+    scopes: Vec<HashMap<String, Option<usize>>>,
+    // One entry per function body currently being parsed.
+    functions: Vec<FunctionContext>,
+}
+
+#[derive(Default)]
+struct FunctionContext {
+    saw_yield: bool,
+    // The first `return` that carries a value, which generators may not have.
+    value_return: Option<Token>,
 }
 
 impl Parser {
@@ -15,16 +34,91 @@ impl Parser {
         return Parser {
             tokens: tokens,
             current: 0,
+            scopes: vec![HashMap::new()],
+            functions: Vec::new(),
         };
     }
 
     fn expression(self: &mut Self) -> Result<Expression, Error> {
-        match self.equality() {
+        match self.assignment() {
             Ok(expr) => return Ok(expr),
             Err(msg) => return Err(msg),
         }
     }
 
+    fn assignment(self: &mut Self) -> Result<Expression, Error> {
+        let expr = self.range()?;
+        if self.match_tokens(&[TokenType::EQUAL]) {
+            let equals = self.previous();
+            let value = self.assignment()?;
+            match expr {
+                Expression::Variable { name, .. } => {
+                    self.check_not_constant(&name)?;
+                    return Ok(Expression::Assign {
+                        name: name,
+                        value: Box::new(value),
+                        resolution: Cell::new(Resolution::Unresolved),
+                    });
+                }
+                _ => {
+                    return Err(Error::ParsingError(
+                        "Invalid assignment target".to_string(),
+                        equals.line_number as i128,
+                    ));
+                }
+            }
+        }
+        return Ok(expr);
+    }
+
+    fn range(self: &mut Self) -> Result<Expression, Error> {
+        let expr = self.equality()?;
+        if self.match_tokens(&[TokenType::DOT_DOT, TokenType::DOT_DOT_EQUAL]) {
+            let operator = self.previous();
+            let end = self.equality()?;
+            // `step` is only a keyword right after the end of a range.
+            let mut step = None;
+            if self.check(&TokenType::IDENTIFIER) && self.peek().lexeme == "step" {
+                self.advance();
+                step = Some(Box::new(self.equality()?));
+            }
+            return Ok(Expression::Range {
+                start: Box::new(expr),
+                operator: operator,
+                end: Box::new(end),
+                step: step,
+            });
+        }
+        return Ok(expr);
+    }
+
+    fn check_not_constant(self: &Self, name: &Token) -> Result<(), Error> {
+        for scope in self.scopes.iter().rev() {
+            match scope.get(&name.lexeme) {
+                Some(Some(line)) => {
+                    return Err(Error::ConstantReassignment(name.clone(), *line));
+                }
+                Some(None) => return Ok(()),
+                None => {}
+            }
+        }
+        return Ok(());
+    }
+
+    fn declare(self: &mut Self, name: &Token, is_constant: bool) -> Result<(), Error> {
+        let scope = self.scopes.last_mut().unwrap();
+        if let Some(Some(line)) = scope.get(&name.lexeme) {
+            return Err(Error::ConstantReassignment(name.clone(), *line));
+        }
+        let line = if is_constant {
+            Some(name.line_number)
+        } else {
+            None
+        };
+        scope.insert(name.lexeme.clone(), line);
+        return Ok(());
+    }
+
     fn equality(self: &mut Self) -> Result<Expression, Error> {
         match self.comparison() {
             Ok(mut expr) => {
@@ -82,6 +176,13 @@ impl Parser {
         return self.tokens[self.current as usize].clone();
     }
 
+    fn check_ahead(self: &Self, offset: usize, token_type: TokenType) -> bool {
+        match self.tokens.get(self.current + offset) {
+            Some(token) => return token.token_type == token_type,
+            None => return false,
+        }
+    }
+
     fn is_at_end(self: &mut Self) -> bool {
         return self.peek().token_type == TokenType::EOF;
     }
@@ -138,7 +239,12 @@ impl Parser {
     fn factor(self: &mut Self) -> Result<Expression, Error> {
         match self.unary() {
             Ok(mut expr) => {
-                while self.match_tokens(&[TokenType::SLASH, TokenType::STAR]) {
+                while self.match_tokens(&[
+                    TokenType::SLASH,
+                    TokenType::TILDE_SLASH,
+                    TokenType::STAR,
+                    TokenType::MODULO,
+                ]) {
                     let operator: Token = self.previous().clone();
                     match self.unary() {
                         Ok(right) => {
@@ -171,28 +277,90 @@ impl Parser {
                 Err(err) => return Err(err),
             }
         }
-        match self.primary() {
-            Ok(expr) => return Ok(expr),
-            Err(err) => return Err(err),
+        return self.power();
+    }
+
+    // `**` binds tighter than unary minus on its left and is right associative,
+    // so `-2 ** 2` is -4 and `2 ** 3 ** 2` is 512.
+    fn power(self: &mut Self) -> Result<Expression, Error> {
+        let expr = self.call()?;
+        if self.match_tokens(&[TokenType::STAR_STAR]) {
+            let operator = self.previous();
+            let right = self.unary()?;
+            return Ok(Expression::Binary {
+                left: Box::new(expr),
+                operator: operator,
+                right: Box::new(right),
+            });
+        }
+        return Ok(expr);
+    }
+
+    fn call(self: &mut Self) -> Result<Expression, Error> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.match_tokens(&[TokenType::LEFT_PAREN]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_tokens(&[TokenType::LEFT_BRACKET]) {
+                let index = self.expression()?;
+                let bracket = self.consume(
+                    TokenType::RIGHT_BRACKET,
+                    "Expect ']' after index".to_string(),
+                )?;
+                expr = Expression::Index {
+                    object: Box::new(expr),
+                    bracket: bracket,
+                    index: Box::new(index),
+                };
+            } else if self.match_tokens(&[TokenType::DOT]) {
+                let name = self.consume(
+                    TokenType::IDENTIFIER,
+                    "Expect property name after '.'".to_string(),
+                )?;
+                expr = Expression::Get {
+                    object: Box::new(expr),
+                    name: name,
+                };
+            } else {
+                break;
+            }
         }
+        return Ok(expr);
+    }
+
+    fn finish_call(self: &mut Self, callee: Expression) -> Result<Expression, Error> {
+        let mut arguments = Vec::new();
+        if !self.check(&TokenType::RIGHT_PAREN) {
+            loop {
+                arguments.push(self.expression()?);
+                if !self.match_tokens(&[TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(
+            TokenType::RIGHT_PAREN,
+            "Expect ')' after arguments".to_string(),
+        )?;
+        return Ok(Expression::Call {
+            callee: Box::new(callee),
+            paren: paren,
+            arguments: arguments,
+        });
     }
 
     fn primary(self: &mut Self) -> Result<Expression, Error> {
         if self.match_tokens(&[TokenType::FALSE]) {
             return Ok(Expression::Literal {
-                value: LiteralValue::False,
+                value: Value::False,
             });
         } else if self.match_tokens(&[TokenType::TRUE]) {
-            return Ok(Expression::Literal {
-                value: LiteralValue::True,
-            });
+            return Ok(Expression::Literal { value: Value::True });
         } else if self.match_tokens(&[TokenType::NIL]) {
-            return Ok(Expression::Literal {
-                value: LiteralValue::Nil,
-            });
+            return Ok(Expression::Literal { value: Value::Nil });
         } else if self.match_tokens(&[TokenType::NUMBER, TokenType::STRING]) {
             return Ok(Expression::Literal {
-                value: self.previous().literal.clone().unwrap(),
+                value: literal_value(&self.previous())?,
             });
         } else if self.match_tokens(&[TokenType::LEFT_PAREN]) {
             match self.expression() {
@@ -207,14 +375,54 @@ impl Parser {
                 }
                 Err(msg) => return Err(msg),
             }
+        } else if self.match_tokens(&[TokenType::LEFT_BRACKET]) {
+            let mut elements = Vec::new();
+            while !self.check(&TokenType::RIGHT_BRACKET) && !self.is_at_end() {
+                elements.push(self.expression()?);
+                if !self.match_tokens(&[TokenType::COMMA]) {
+                    break;
+                }
+            }
+            self.consume(
+                TokenType::RIGHT_BRACKET,
+                "Expect ']' after list elements".to_string(),
+            )?;
+            return Ok(Expression::List { elements: elements });
+        } else if self.match_tokens(&[TokenType::LEFT_BRACE]) {
+            let mut entries = Vec::new();
+            while !self.check(&TokenType::RIGHT_BRACE) && !self.is_at_end() {
+                let key = self.expression()?;
+                self.consume(TokenType::COLON, "Expect ':' after map key".to_string())?;
+                entries.push((key, self.expression()?));
+                if !self.match_tokens(&[TokenType::COMMA]) {
+                    break;
+                }
+            }
+            self.consume(
+                TokenType::RIGHT_BRACE,
+                "Expect '}' after map entries".to_string(),
+            )?;
+            return Ok(Expression::Map { entries: entries });
+        } else if self.check(&TokenType::YIELD) {
+            return Err(Error::ParsingError(
+                "'yield' is only allowed as a statement, initializer or assignment value"
+                    .to_string(),
+                self.peek().line_number as i128,
+            ));
+        } else if self.check(&TokenType::THIS) {
+            return Err(Error::ParsingError(
+                "'this' is reserved and has no meaning without classes".to_string(),
+                self.peek().line_number as i128,
+            ));
         } else if self.peek().token_type == TokenType::IDENTIFIER {
             self.advance();
             return Ok(Expression::Variable {
                 name: self.previous(),
+                resolution: Cell::new(Resolution::Unresolved),
             });
         } else {
             return Ok(Expression::Literal {
-                value: LiteralValue::IntValue(69),
+                value: Value::IntValue(69),
             });
         }
     }
@@ -239,6 +447,9 @@ impl Parser {
                     TokenType::CLASS
                     | TokenType::FUN
                     | TokenType::VAR
+                    | TokenType::CONST
+                    | TokenType::ENUM
+                    | TokenType::MATCH
                     | TokenType::FOR
                     | TokenType::IF
                     | TokenType::WHILE
@@ -285,32 +496,204 @@ impl Parser {
                     return Err(err);
                 }
             }
+        } else if self.match_tokens(&[TokenType::CONST]) {
+            match self.const_declaration() {
+                Ok(statement) => {
+                    return Ok(statement);
+                }
+                Err(err) => {
+                    self.synchronize();
+                    return Err(err);
+                }
+            }
+        } else if self.match_tokens(&[TokenType::FUN]) {
+            match self.function_declaration() {
+                Ok(statement) => {
+                    return Ok(statement);
+                }
+                Err(err) => {
+                    self.synchronize();
+                    return Err(err);
+                }
+            }
+        } else if self.match_tokens(&[TokenType::ENUM]) {
+            match self.enum_declaration() {
+                Ok(statement) => {
+                    return Ok(statement);
+                }
+                Err(err) => {
+                    self.synchronize();
+                    return Err(err);
+                }
+            }
         } else {
             return self.statement();
         }
     }
 
+    fn function_declaration(self: &mut Self) -> Result<Statement, Error> {
+        let name = self.consume(TokenType::IDENTIFIER, "Function name".to_string())?;
+        self.declare(&name, false)?;
+        self.consume(
+            TokenType::LEFT_PAREN,
+            "Expect '(' after function name".to_string(),
+        )?;
+        let mut params: Vec<Token> = Vec::new();
+        let mut signature = Signature {
+            params: Vec::new(),
+            returns: Type::Any,
+        };
+        if !self.check(&TokenType::RIGHT_PAREN) {
+            loop {
+                params.push(self.consume(TokenType::IDENTIFIER, "Parameter name".to_string())?);
+                let annotation = self.type_annotation()?;
+                signature.params.push(annotation.unwrap_or(Type::Any));
+                if !self.match_tokens(&[TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenType::RIGHT_PAREN,
+            "Expect ')' after parameters".to_string(),
+        )?;
+        if self.match_tokens(&[TokenType::ARROW]) {
+            signature.returns = self.type_name()?;
+        }
+        self.consume(
+            TokenType::LEFT_BRACE,
+            "Expect '{' before function body".to_string(),
+        )?;
+        self.functions.push(FunctionContext::default());
+        self.scopes.push(HashMap::new());
+        let body = self.function_body(&params);
+        self.scopes.pop();
+        let context = self.functions.pop().unwrap();
+        let body = body?;
+        if context.saw_yield {
+            if let Some(keyword) = context.value_return {
+                return Err(Error::ParsingError(
+                    "Cannot return a value from a generator".to_string(),
+                    keyword.line_number as i128,
+                ));
+            }
+        }
+        return Ok(Statement::Function(
+            name,
+            params,
+            Rc::new(body),
+            context.saw_yield,
+            signature,
+        ));
+    }
+
+    fn function_body(self: &mut Self, params: &Vec<Token>) -> Result<Vec<Statement>, Error> {
+        for param in params {
+            self.declare(param, false)?;
+        }
+        return self.block();
+    }
+
+    // An optional `: type` after a variable or parameter name.
+    fn type_annotation(self: &mut Self) -> Result<Option<Type>, Error> {
+        if !self.match_tokens(&[TokenType::COLON]) {
+            return Ok(None);
+        }
+        return Ok(Some(self.type_name()?));
+    }
+
+    // A type name such as `int`, an enum name, or either followed by `?` for nullable.
+    fn type_name(self: &mut Self) -> Result<Type, Error> {
+        let name = if self.match_tokens(&[TokenType::NIL]) {
+            self.previous()
+        } else {
+            self.consume(TokenType::IDENTIFIER, "Expect type name".to_string())?
+        };
+        let ty = Type::from_name(&name.lexeme);
+        if self.match_tokens(&[TokenType::QUESTION]) {
+            return Ok(Type::Nullable(Box::new(ty)));
+        }
+        return Ok(ty);
+    }
+
+    fn enum_declaration(self: &mut Self) -> Result<Statement, Error> {
+        let name = self.consume(TokenType::IDENTIFIER, "Enum name".to_string())?;
+        self.declare(&name, false)?;
+        self.consume(
+            TokenType::LEFT_BRACE,
+            "Expect '{' before enum variants".to_string(),
+        )?;
+        let mut variants: Vec<(Token, Vec<Token>)> = Vec::new();
+        while !self.check(&TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            let variant = self.consume(TokenType::IDENTIFIER, "Variant name".to_string())?;
+            if variants
+                .iter()
+                .any(|(existing, _)| existing.lexeme == variant.lexeme)
+            {
+                return Err(Error::ParsingError(
+                    format!("Duplicate variant '{}'", variant.lexeme),
+                    variant.line_number as i128,
+                ));
+            }
+            let mut fields = Vec::new();
+            if self.match_tokens(&[TokenType::LEFT_PAREN]) {
+                loop {
+                    fields.push(self.consume(TokenType::IDENTIFIER, "Field name".to_string())?);
+                    if !self.match_tokens(&[TokenType::COMMA]) {
+                        break;
+                    }
+                }
+                self.consume(
+                    TokenType::RIGHT_PAREN,
+                    "Expect ')' after variant fields".to_string(),
+                )?;
+            }
+            variants.push((variant, fields));
+            if !self.match_tokens(&[TokenType::COMMA]) {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::RIGHT_BRACE,
+            "Expect '}' after enum variants".to_string(),
+        )?;
+        return Ok(Statement::Enum(name, variants));
+    }
+
+    fn const_declaration(self: &mut Self) -> Result<Statement, Error> {
+        let name = self.consume(TokenType::IDENTIFIER, "Constant name".to_string())?;
+        let annotation = self.type_annotation()?;
+        self.consume(
+            TokenType::EQUAL,
+            format!("Constant '{}' must be initialized", name.lexeme),
+        )?;
+        let initializer = self.initializer()?;
+        self.consume(TokenType::SEMICOLON, "Missing ';'".to_string())?;
+        self.declare(&name, true)?;
+        return Ok(Statement::Const(name, annotation, initializer));
+    }
+
     fn var_declaration(self: &mut Self) -> Result<Statement, Error> {
         match self.consume(TokenType::IDENTIFIER, "Variable name".to_string()) {
             Ok(name) => {
+                self.declare(&name, false)?;
+                let annotation = self.type_annotation()?;
                 let initializer;
                 if self.match_tokens(&[TokenType::EQUAL]) {
-                    match self.expression() {
+                    match self.initializer() {
                         Ok(expr) => {
                             initializer = expr;
                             let _ = self.consume(TokenType::SEMICOLON, "Missing ';'".to_string());
-                            return Ok(Statement::Variable(name, initializer));
+                            return Ok(Statement::Variable(name, annotation, initializer));
                         }
                         Err(err) => {
                             return Err(err);
                         }
                     }
                 } else {
-                    initializer = Expression::Literal {
-                        value: LiteralValue::Nil,
-                    };
+                    initializer = Expression::Literal { value: Value::Nil };
                     let _ = self.consume(TokenType::SEMICOLON, "Missing ';'".to_string());
-                    return Ok(Statement::Variable(name, initializer));
+                    return Ok(Statement::Variable(name, annotation, initializer));
                 }
             }
             Err(err) => return Err(err),
@@ -320,11 +703,247 @@ impl Parser {
     fn statement(self: &mut Self) -> Result<Statement, Error> {
         if self.match_tokens(&[TokenType::PRINT]) {
             self.print_statement()
+        } else if self.match_tokens(&[TokenType::LEFT_BRACE]) {
+            self.scopes.push(HashMap::new());
+            let block = self.block();
+            self.scopes.pop();
+            return Ok(Statement::Block(Rc::new(block?)));
+        } else if self.match_tokens(&[TokenType::MATCH]) {
+            self.match_statement()
+        } else if self.match_tokens(&[TokenType::FOR]) {
+            self.for_statement()
+        } else if self.match_tokens(&[TokenType::RETURN]) {
+            self.return_statement()
         } else {
             self.expr_statement()
         }
     }
 
+    fn block(self: &mut Self) -> Result<Vec<Statement>, Error> {
+        let mut statements: Vec<Statement> = Vec::new();
+        while !self.check(&TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block".to_string())?;
+        return Ok(statements);
+    }
+
+    fn return_statement(self: &mut Self) -> Result<Statement, Error> {
+        // A `return` outside any function is reported by the resolver.
+        let keyword = self.previous();
+        let mut value = Expression::Literal { value: Value::Nil };
+        if !self.check(&TokenType::SEMICOLON) {
+            value = self.expression()?;
+            if let Some(context) = self.functions.last_mut() {
+                if context.value_return.is_none() {
+                    context.value_return = Some(keyword.clone());
+                }
+            }
+        }
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expect ';' after return value".to_string(),
+        )?;
+        return Ok(Statement::Return(keyword, value));
+    }
+
+    // Parses the expression of an expression statement, where `yield` may appear on its
+    // own or as the value of an assignment.
+    fn statement_expression(self: &mut Self) -> Result<Expression, Error> {
+        if self.check(&TokenType::YIELD) {
+            return self.yield_expression();
+        }
+        if self.check(&TokenType::IDENTIFIER)
+            && self.check_ahead(1, TokenType::EQUAL)
+            && self.check_ahead(2, TokenType::YIELD)
+        {
+            let name = self.advance();
+            self.advance();
+            self.check_not_constant(&name)?;
+            return Ok(Expression::Assign {
+                name: name,
+                value: Box::new(self.yield_expression()?),
+                resolution: Cell::new(Resolution::Unresolved),
+            });
+        }
+        return self.expression();
+    }
+
+    // Parses a variable initializer, which may suspend a generator with `yield`.
+    fn initializer(self: &mut Self) -> Result<Expression, Error> {
+        if self.check(&TokenType::YIELD) {
+            return self.yield_expression();
+        }
+        return self.expression();
+    }
+
+    fn yield_expression(self: &mut Self) -> Result<Expression, Error> {
+        let keyword = self.consume(TokenType::YIELD, "Expect 'yield'".to_string())?;
+        match self.functions.last_mut() {
+            Some(context) => context.saw_yield = true,
+            None => {
+                return Err(Error::ParsingError(
+                    "Cannot yield outside of a function".to_string(),
+                    keyword.line_number as i128,
+                ))
+            }
+        }
+        let mut value = None;
+        if !self.check(&TokenType::SEMICOLON) {
+            value = Some(Box::new(self.expression()?));
+        }
+        return Ok(Expression::Yield {
+            keyword: keyword,
+            value: value,
+        });
+    }
+
+    fn for_statement(self: &mut Self) -> Result<Statement, Error> {
+        let keyword = self.previous();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'".to_string())?;
+        let name = self.consume(TokenType::IDENTIFIER, "Loop variable name".to_string())?;
+        self.consume(TokenType::IN, "Expect 'in' after loop variable".to_string())?;
+        let iterable = self.expression()?;
+        self.consume(
+            TokenType::RIGHT_PAREN,
+            "Expect ')' after for clause".to_string(),
+        )?;
+        self.scopes.push(HashMap::new());
+        let body = self.loop_body(&name);
+        self.scopes.pop();
+        return Ok(Statement::ForIn(keyword, name, iterable, Rc::new(body?)));
+    }
+
+    fn loop_body(self: &mut Self, name: &Token) -> Result<Statement, Error> {
+        self.declare(name, false)?;
+        return self.statement();
+    }
+
+    fn match_statement(self: &mut Self) -> Result<Statement, Error> {
+        let keyword = self.previous();
+        self.consume(
+            TokenType::LEFT_PAREN,
+            "Expect '(' after 'match'".to_string(),
+        )?;
+        let subject = self.expression()?;
+        self.consume(
+            TokenType::RIGHT_PAREN,
+            "Expect ')' after match subject".to_string(),
+        )?;
+        self.consume(
+            TokenType::LEFT_BRACE,
+            "Expect '{' before match arms".to_string(),
+        )?;
+        let mut arms: Vec<(Pattern, Rc<Statement>)> = Vec::new();
+        while !self.check(&TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            let pattern = self.pattern()?;
+            self.consume(
+                TokenType::FAT_ARROW,
+                "Expect '=>' after pattern".to_string(),
+            )?;
+            self.scopes.push(HashMap::new());
+            let body = self.match_arm_body(&pattern);
+            self.scopes.pop();
+            arms.push((pattern, Rc::new(body?)));
+        }
+        self.consume(
+            TokenType::RIGHT_BRACE,
+            "Expect '}' after match arms".to_string(),
+        )?;
+        return Ok(Statement::Match(keyword, subject, arms));
+    }
+
+    // A possibly negated number literal inside a pattern.
+    fn pattern_number(self: &mut Self) -> Result<Value, Error> {
+        let negate = self.match_tokens(&[TokenType::MINUS]);
+        let number = self.consume(TokenType::NUMBER, "Expect number in pattern".to_string())?;
+        let value = literal_value(&number)?;
+        if !negate {
+            return Ok(value);
+        }
+        match numbers::negate(&value) {
+            Some(negated) => return Ok(negated),
+            None => return Err(Error::InvalidToken(number)),
+        }
+    }
+
+    fn match_arm_body(self: &mut Self, pattern: &Pattern) -> Result<Statement, Error> {
+        for name in pattern.bindings() {
+            self.declare(&name, false)?;
+        }
+        return self.statement();
+    }
+
+    fn pattern(self: &mut Self) -> Result<Pattern, Error> {
+        if self.match_tokens(&[TokenType::IDENTIFIER]) {
+            let name = self.previous();
+            if name.lexeme == "_" {
+                return Ok(Pattern::Wildcard);
+            }
+            if !self.match_tokens(&[TokenType::DOT]) {
+                return Ok(Pattern::Binding(name));
+            }
+            let variant = self.consume(TokenType::IDENTIFIER, "Variant name".to_string())?;
+            let mut fields = Vec::new();
+            if self.match_tokens(&[TokenType::LEFT_PAREN]) {
+                loop {
+                    fields.push(self.pattern()?);
+                    if !self.match_tokens(&[TokenType::COMMA]) {
+                        break;
+                    }
+                }
+                self.consume(
+                    TokenType::RIGHT_PAREN,
+                    "Expect ')' after variant fields".to_string(),
+                )?;
+            }
+            return Ok(Pattern::Variant {
+                enum_name: name,
+                variant: variant,
+                fields: fields,
+            });
+        } else if self.match_tokens(&[TokenType::TRUE]) {
+            return Ok(Pattern::Literal(Value::True));
+        } else if self.match_tokens(&[TokenType::FALSE]) {
+            return Ok(Pattern::Literal(Value::False));
+        } else if self.match_tokens(&[TokenType::NIL]) {
+            return Ok(Pattern::Literal(Value::Nil));
+        } else if self.match_tokens(&[TokenType::STRING]) {
+            return Ok(Pattern::Literal(literal_value(&self.previous())?));
+        } else if self.check(&TokenType::NUMBER) || self.check(&TokenType::MINUS) {
+            let start = self.pattern_number()?;
+            if !self.match_tokens(&[TokenType::DOT_DOT, TokenType::DOT_DOT_EQUAL]) {
+                return Ok(Pattern::Literal(start));
+            }
+            let operator = self.previous();
+            let end = self.pattern_number()?;
+            let mut step = Value::IntValue(1);
+            if self.check(&TokenType::IDENTIFIER) && self.peek().lexeme == "step" {
+                self.advance();
+                step = self.pattern_number()?;
+            }
+            match (start, end, step) {
+                (Value::IntValue(start), Value::IntValue(end), Value::IntValue(step))
+                    if step != 0 =>
+                {
+                    let inclusive = operator.token_type == TokenType::DOT_DOT_EQUAL;
+                    return Ok(Pattern::Range(Range::new(start, end, step, inclusive)));
+                }
+                _ => {
+                    return Err(Error::InvalidPattern(
+                        operator,
+                        "range patterns need integer bounds and a non-zero step".to_string(),
+                    ))
+                }
+            }
+        } else {
+            return Err(Error::InvalidPattern(
+                self.peek(),
+                format!("unexpected '{}'", self.peek().lexeme),
+            ));
+        }
+    }
+
     fn print_statement(self: &mut Self) -> Result<Statement, Error> {
         match self.expression() {
             Ok(value) => {
@@ -342,7 +961,7 @@ impl Parser {
     }
 
     fn expr_statement(self: &mut Self) -> Result<Statement, Error> {
-        match self.expression() {
+        match self.statement_expression() {
             Ok(value) => {
                 match self.consume(TokenType::SEMICOLON, "Expect ';' after value.".to_string()) {
                     Ok(_) => {}
@@ -356,3 +975,17 @@ impl Parser {
         }
     }
 }
+
+// The runtime value of a NUMBER or STRING token.
+fn literal_value(token: &Token) -> Result<Value, Error> {
+    match token.literal.as_ref().and_then(Value::from_literal) {
+        Some(value) => return Ok(value),
+        None if token.token_type == TokenType::NUMBER => {
+            return Err(Error::ParsingError(
+                format!("cannot parse {} as a number", token.lexeme),
+                token.line_number as i128,
+            ))
+        }
+        None => return Err(Error::InvalidToken(token.clone())),
+    }
+}
@@ -0,0 +1,157 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    environment::Environment, errors::Error, expressions::equals, ranges::Range, scanner::Token,
+    values::Value,
+};
+
+#[derive(Clone, PartialEq)]
+pub enum Pattern {
+    Wildcard,
+    Binding(Token),
+    Literal(Value),
+    Range(Range),
+    Variant {
+        enum_name: Token,
+        variant: Token,
+        fields: Vec<Pattern>,
+    },
+}
+
+impl Pattern {
+    pub fn to_string(self: &Self) -> String {
+        match self {
+            Pattern::Wildcard => return "_".to_string(),
+            Pattern::Binding(name) => return name.lexeme.clone(),
+            Pattern::Literal(value) => return value.to_string(),
+            Pattern::Range(range) => return range.to_string(),
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                if fields.len() == 0 {
+                    return format!("{}.{}", enum_name.lexeme, variant.lexeme);
+                }
+                let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+                return format!(
+                    "{}.{}({})",
+                    enum_name.lexeme,
+                    variant.lexeme,
+                    fields.join(", ")
+                );
+            }
+        }
+    }
+
+    pub fn bindings(self: &Self) -> Vec<Token> {
+        match self {
+            Pattern::Binding(name) => return vec![name.clone()],
+            Pattern::Variant { fields, .. } => {
+                return fields.iter().flat_map(|field| field.bindings()).collect();
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range(_) => return vec![],
+        }
+    }
+
+    // The enums the pattern names, in the order `matches_with` looks them up.
+    pub fn enum_names(self: &Self) -> Vec<Token> {
+        match self {
+            Pattern::Variant {
+                enum_name, fields, ..
+            } => {
+                let mut names = vec![enum_name.clone()];
+                names.extend(fields.iter().flat_map(|field| field.enum_names()));
+                return names;
+            }
+            Pattern::Wildcard | Pattern::Binding(_) | Pattern::Literal(_) | Pattern::Range(_) => {
+                return vec![]
+            }
+        }
+    }
+
+    // Tests `value` against the pattern, pushing every name it binds onto `bindings`.
+    pub fn matches(
+        self: &Self,
+        value: &Value,
+        environment: &Rc<RefCell<Environment>>,
+        bindings: &mut Vec<(Token, Value)>,
+    ) -> Result<bool, Error> {
+        let lookup = |name: &Token| match environment.borrow().get(name.symbol) {
+            Ok(value) => return Ok(value),
+            Err(_) => return Err(Error::InvalidToken(name.clone())),
+        };
+        return self.matches_with(value, &lookup, bindings);
+    }
+
+    // `matches`, with the enums named by variant patterns found through `lookup`.
+    pub fn matches_with(
+        self: &Self,
+        value: &Value,
+        lookup: &dyn Fn(&Token) -> Result<Value, Error>,
+        bindings: &mut Vec<(Token, Value)>,
+    ) -> Result<bool, Error> {
+        match self {
+            Pattern::Wildcard => return Ok(true),
+            Pattern::Binding(name) => {
+                bindings.push((name.clone(), value.clone()));
+                return Ok(true);
+            }
+            Pattern::Literal(literal) => return Ok(equals(literal, value)),
+            Pattern::Range(range) => match value {
+                Value::IntValue(x) => return Ok(range.contains(*x)),
+                _ => return Ok(false),
+            },
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                let variants = match lookup(enum_name)? {
+                    Value::EnumType(_, variants) => variants,
+                    _ => {
+                        return Err(Error::InvalidPattern(
+                            enum_name.clone(),
+                            format!("{} is not an enum", enum_name.lexeme),
+                        ))
+                    }
+                };
+                match variants.iter().find(|(name, _)| *name == variant.symbol) {
+                    Some((_, arity)) => {
+                        if *arity != fields.len() {
+                            return Err(Error::InvalidPattern(
+                                variant.clone(),
+                                format!(
+                                    "{}.{} has {} fields but the pattern has {}",
+                                    enum_name.lexeme,
+                                    variant.lexeme,
+                                    arity,
+                                    fields.len()
+                                ),
+                            ));
+                        }
+                    }
+                    None => {
+                        return Err(Error::InvalidPattern(
+                            variant.clone(),
+                            format!("{} has no variant {}", enum_name.lexeme, variant.lexeme),
+                        ))
+                    }
+                }
+                match value {
+                    Value::EnumValue(name, tag, payload)
+                        if *name == enum_name.symbol && *tag == variant.symbol =>
+                    {
+                        for (field, item) in fields.iter().zip(payload.iter()) {
+                            if !field.matches_with(item, lookup, bindings)? {
+                                return Ok(false);
+                            }
+                        }
+                        return Ok(true);
+                    }
+                    _ => return Ok(false),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::values::Value;
+
+// An integer range such as `0..10`, `1..=5` or `10..0 step -2`. Ranges are never
+// materialised; their elements are computed on demand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Range {
+    pub(crate) start: i128,
+    pub(crate) end: i128,
+    pub(crate) step: i128,
+    pub(crate) inclusive: bool,
+}
+
+impl Range {
+    pub fn new(start: i128, end: i128, step: i128, inclusive: bool) -> Self {
+        return Range {
+            start,
+            end,
+            step,
+            inclusive,
+        };
+    }
+
+    fn in_bounds(self: &Self, value: i128) -> bool {
+        if self.step > 0 {
+            return value >= self.start
+                && (value < self.end || (self.inclusive && value == self.end));
+        } else {
+            return value <= self.start
+                && (value > self.end || (self.inclusive && value == self.end));
+        }
+    }
+
+    pub fn contains(self: &Self, value: i128) -> bool {
+        if !self.in_bounds(value) {
+            return false;
+        }
+        match value.checked_sub(self.start) {
+            Some(offset) => return offset.checked_rem(self.step) == Some(0),
+            None => return false,
+        }
+    }
+
+    pub fn len(self: &Self) -> i128 {
+        if !self.in_bounds(self.start) {
+            return 0;
+        }
+        let span = match self.end.checked_sub(self.start) {
+            Some(span) => span,
+            None => return i128::MAX,
+        };
+        let mut count = span.checked_div(self.step).unwrap_or(i128::MAX);
+        if self.inclusive || span.checked_rem(self.step) != Some(0) {
+            count = count.saturating_add(1);
+        }
+        return count;
+    }
+
+    pub fn iter(self: &Self) -> RangeIterator {
+        return RangeIterator {
+            range: self.clone(),
+            next: Some(self.start),
+        };
+    }
+
+    pub fn to_string(self: &Self) -> String {
+        let operator = if self.inclusive { "..=" } else { ".." };
+        if self.step == 1 {
+            return format!("{}{}{}", self.start, operator, self.end);
+        }
+        return format!("{}{}{} step {}", self.start, operator, self.end, self.step);
+    }
+}
+
+pub struct RangeIterator {
+    range: Range,
+    next: Option<i128>,
+}
+
+impl Iterator for RangeIterator {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        let current = self.next?;
+        if !self.range.in_bounds(current) {
+            self.next = None;
+            return None;
+        }
+        self.next = current.checked_add(self.range.step);
+        return Some(Value::IntValue(current));
+    }
+}
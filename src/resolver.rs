@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    errors::Error, expressions::Expression, patterns::Pattern, scanner::Token,
+    statements::Statement,
+};
+
+// How far a name in a scope has got: declared later in the scope, being
+// initialized, or ready to be read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binding {
+    // A variable or constant declared further down.
+    Later,
+    // A function or enum declared further down.
+    Pending,
+    Initializing,
+    Defined,
+}
+
+// Where a variable reference finds its value: the global of that name, or the
+// `slot`th name defined in the environment `depth` scopes out from the one the
+// reference is evaluated in. References the resolver has not seen are looked up
+// by name, scope by scope.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Unresolved,
+    Global,
+    Local(usize, usize),
+}
+
+// The pass between parsing and execution that finds, for every variable
+// reference, the scope declaring it, and records it in the reference. Scopes
+// mirror the environments the interpreter creates: one per block, match arm, loop
+// iteration and function call, with a function's parameters and body sharing one.
+// A name's slot is its position among the scope's names in the order they are
+// defined when the scope runs.
+//
+// Code inside a function runs later, so it may call functions and refer to enums
+// its enclosing scopes only declare further down. Variables are different: a
+// function sees the variable a name meant where the function was declared, so a
+// later declaration in an enclosing function does not change what it reads. Top
+// level names are globals and are always looked up by name.
+//
+// `return` outside a function is reported here. There are no classes, so no
+// context makes `this` valid; the parser rejects it wherever it appears.
+pub struct Resolver {
+    // Each name with how far it has got and its slot.
+    scopes: Vec<HashMap<String, (Binding, usize)>>,
+    // For each function being resolved, the index of its scope; the top level is 0.
+    functions: Vec<usize>,
+    // Names that already exist before the program runs: natives and, in the shell,
+    // everything earlier lines defined.
+    globals: HashSet<String>,
+    errors: Vec<Error>,
+}
+
+impl Resolver {
+    pub fn new(globals: Vec<String>) -> Self {
+        return Resolver {
+            scopes: Vec::new(),
+            functions: Vec::new(),
+            globals: globals.into_iter().collect(),
+            errors: Vec::new(),
+        };
+    }
+
+    pub fn resolve(mut self: Self, statements: &Vec<Statement>) -> Vec<Error> {
+        self.begin_scope(&[], statements);
+        for statement in statements {
+            self.statement(statement);
+        }
+        return self.errors;
+    }
+
+    fn report(self: &mut Self, token: &Token, message: String) {
+        self.errors
+            .push(Error::ResolutionError(token.clone(), message));
+    }
+
+    // Opens a scope holding `params`, in which everything `statements` declares is
+    // already known.
+    fn begin_scope(self: &mut Self, params: &[Token], statements: &[Statement]) {
+        let mut scope = HashMap::new();
+        for param in params {
+            let slot = scope.len();
+            scope
+                .entry(param.lexeme.clone())
+                .or_insert((Binding::Defined, slot));
+        }
+        for statement in statements {
+            let (name, binding) = match statement {
+                Statement::Variable(name, ..) | Statement::Const(name, ..) => {
+                    (name, Binding::Later)
+                }
+                Statement::Function(name, ..) | Statement::Enum(name, _) => {
+                    (name, Binding::Pending)
+                }
+                _ => continue,
+            };
+            let slot = scope.len();
+            scope.entry(name.lexeme.clone()).or_insert((binding, slot));
+        }
+        self.scopes.push(scope);
+    }
+
+    fn declare(self: &mut Self, name: &Token, binding: Binding) {
+        let scope = self.scopes.last_mut().unwrap();
+        match scope.get(&name.lexeme) {
+            Some((Binding::Initializing | Binding::Defined, _)) => {
+                let message = format!("'{}' is already declared in this scope", name.lexeme);
+                self.report(name, message);
+            }
+            Some((_, slot)) => {
+                let slot = *slot;
+                scope.insert(name.lexeme.clone(), (binding, slot));
+            }
+            None => {
+                let slot = scope.len();
+                scope.insert(name.lexeme.clone(), (binding, slot));
+            }
+        }
+    }
+
+    fn define(self: &mut Self, name: &Token) {
+        let scope = self.scopes.last_mut().unwrap();
+        if let Some((binding, _)) = scope.get_mut(&name.lexeme) {
+            *binding = Binding::Defined;
+        }
+    }
+
+    // Finds the scope `name` refers to, reporting references that can only fail.
+    fn reference(self: &mut Self, name: &Token) -> Resolution {
+        let local = *self.functions.last().unwrap_or(&0);
+        let innermost = self.scopes.len() - 1;
+        for depth in (0..self.scopes.len()).rev() {
+            let (binding, slot) = match self.scopes[depth].get(&name.lexeme) {
+                Some(entry) => *entry,
+                None => continue,
+            };
+            let found = match depth {
+                0 => Resolution::Global,
+                _ => Resolution::Local(innermost - depth, slot),
+            };
+            if binding == Binding::Defined {
+                return found;
+            }
+            // Scopes outside the current function are complete by the time it runs,
+            // but a variable they declare later is not the one the function meant.
+            if depth < local {
+                if binding == Binding::Later && depth > 0 {
+                    continue;
+                }
+                return found;
+            }
+            // The shell may be redeclaring a name an earlier line defined.
+            if depth == 0 && self.globals.contains(&name.lexeme) {
+                return found;
+            }
+            let message = match binding {
+                Binding::Initializing => {
+                    format!("can not read '{}' in its own initializer", name.lexeme)
+                }
+                _ => format!("'{}' is used before its declaration", name.lexeme),
+            };
+            self.report(name, message);
+            return found;
+        }
+        if !self.globals.contains(&name.lexeme) {
+            self.report(name, format!("'{}' is not declared", name.lexeme));
+        }
+        return Resolution::Global;
+    }
+
+    fn statement(self: &mut Self, statement: &Statement) {
+        match statement {
+            Statement::ExpressionStatement(expression) | Statement::PrintStatement(expression) => {
+                self.expression(expression);
+            }
+            Statement::Variable(name, _, initializer) | Statement::Const(name, _, initializer) => {
+                self.declare(name, Binding::Initializing);
+                self.expression(initializer);
+                self.define(name);
+            }
+            Statement::Block(statements) => {
+                self.begin_scope(&[], statements);
+                for statement in statements.iter() {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+            }
+            Statement::Enum(name, _) => self.declare(name, Binding::Defined),
+            Statement::Match(_, subject, arms) => {
+                self.expression(subject);
+                for (pattern, body) in arms {
+                    self.scopes.push(HashMap::new());
+                    self.pattern(pattern);
+                    self.statement(body);
+                    self.scopes.pop();
+                }
+            }
+            Statement::ForIn(_, variable, iterable, body) => {
+                self.expression(iterable);
+                self.scopes.push(HashMap::new());
+                self.declare(variable, Binding::Defined);
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Statement::Function(name, params, body, _, _) => {
+                // Defined before its body so it can call itself.
+                self.declare(name, Binding::Defined);
+                self.begin_scope(params, body);
+                self.functions.push(self.scopes.len() - 1);
+                for (position, param) in params.iter().enumerate() {
+                    if params[..position].iter().any(|p| p.lexeme == param.lexeme) {
+                        let message =
+                            format!("'{}' is already declared in this scope", param.lexeme);
+                        self.report(param, message);
+                    }
+                }
+                for statement in body.iter() {
+                    self.statement(statement);
+                }
+                self.functions.pop();
+                self.scopes.pop();
+            }
+            Statement::Return(keyword, value) => {
+                if self.functions.len() == 0 {
+                    self.report(keyword, "can not return from top-level code".to_string());
+                }
+                self.expression(value);
+            }
+        }
+    }
+
+    fn pattern(self: &mut Self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Binding(name) => self.declare(name, Binding::Defined),
+            Pattern::Variant {
+                enum_name, fields, ..
+            } => {
+                self.reference(enum_name);
+                for field in fields {
+                    self.pattern(field);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range(_) => {}
+        }
+    }
+
+    fn expression(self: &mut Self, expression: &Expression) {
+        match expression {
+            Expression::Variable { name, resolution } => {
+                resolution.set(self.reference(name));
+            }
+            Expression::Assign {
+                name,
+                value,
+                resolution,
+            } => {
+                self.expression(value);
+                resolution.set(self.reference(name));
+            }
+            Expression::Binary { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary { right, .. } => self.expression(right),
+            Expression::Grouping { expression } => self.expression(expression),
+            Expression::Get { object, .. } => self.expression(object),
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::List { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            Expression::Map { entries } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::Yield { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Expression::Range {
+                start, end, step, ..
+            } => {
+                self.expression(start);
+                self.expression(end);
+                if let Some(step) = step {
+                    self.expression(step);
+                }
+            }
+            Expression::Literal { .. } => {}
+        }
+    }
+}
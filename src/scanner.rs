@@ -1,6 +1,9 @@
 extern crate lazy_static;
 
-use crate::errors::Error;
+use crate::{
+    errors::Error,
+    symbols::{self, Symbol},
+};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 
@@ -9,11 +12,15 @@ lazy_static! {
         let mut m = HashMap::new();
         m.insert("and".to_string(), TokenType::AND);
         m.insert("class".to_string(), TokenType::CLASS);
+        m.insert("const".to_string(), TokenType::CONST);
         m.insert("else".to_string(), TokenType::ELSE);
+        m.insert("enum".to_string(), TokenType::ENUM);
         m.insert("false".to_string(), TokenType::FALSE);
         m.insert("for".to_string(), TokenType::FOR);
         m.insert("fun".to_string(), TokenType::FUN);
         m.insert("if".to_string(), TokenType::IF);
+        m.insert("in".to_string(), TokenType::IN);
+        m.insert("match".to_string(), TokenType::MATCH);
         m.insert("nil".to_string(), TokenType::NIL);
         m.insert("or".to_string(), TokenType::OR);
         m.insert("print".to_string(), TokenType::PRINT);
@@ -23,6 +30,7 @@ lazy_static! {
         m.insert("true".to_string(), TokenType::TRUE);
         m.insert("var".to_string(), TokenType::VAR);
         m.insert("while".to_string(), TokenType::WHILE);
+        m.insert("yield".to_string(), TokenType::YIELD);
         return m;
     };
 }
@@ -64,12 +72,12 @@ impl<'a> Scanner<'a> {
             self.start = self.current;
             self.scan_token()?;
         }
-        self.tokens.push(Token {
-            token_type: TokenType::EOF,
-            lexeme: String::from(""),
-            literal: None,
-            line_number: self.line,
-        });
+        self.tokens.push(Token::new(
+            TokenType::EOF,
+            String::from(""),
+            None,
+            self.line,
+        ));
         return Ok(self.tokens.clone());
     }
 
@@ -92,13 +100,46 @@ impl<'a> Scanner<'a> {
             ')' => self.add_token(TokenType::RIGHT_PAREN),
             '{' => self.add_token(TokenType::LEFT_BRACE),
             '}' => self.add_token(TokenType::RIGHT_BRACE),
+            '[' => self.add_token(TokenType::LEFT_BRACKET),
+            ']' => self.add_token(TokenType::RIGHT_BRACKET),
+            ':' => self.add_token(TokenType::COLON),
             ',' => self.add_token(TokenType::COMMA),
-            '.' => self.add_token(TokenType::DOT),
-            '-' => self.add_token(TokenType::MINUS),
+            '.' => {
+                if self.match_double('.') {
+                    if self.match_double('=') {
+                        self.add_token(TokenType::DOT_DOT_EQUAL);
+                    } else {
+                        self.add_token(TokenType::DOT_DOT);
+                    }
+                } else {
+                    self.add_token(TokenType::DOT);
+                }
+            }
+            '-' => {
+                if self.match_double('>') {
+                    self.add_token(TokenType::ARROW);
+                } else {
+                    self.add_token(TokenType::MINUS);
+                }
+            }
+            '?' => self.add_token(TokenType::QUESTION),
             '+' => self.add_token(TokenType::PLUS),
             ';' => self.add_token(TokenType::SEMICOLON),
-            '*' => self.add_token(TokenType::STAR),
+            '*' => {
+                if self.match_double('*') {
+                    self.add_token(TokenType::STAR_STAR);
+                } else {
+                    self.add_token(TokenType::STAR);
+                }
+            }
             '%' => self.add_token(TokenType::MODULO),
+            '~' => {
+                if self.match_double('/') {
+                    self.add_token(TokenType::TILDE_SLASH);
+                } else {
+                    return Err(Error::UnknownToken(c, self.line as i128));
+                }
+            }
             '!' => {
                 if self.match_double('=') {
                     self.add_token(TokenType::BANG_EQUAL);
@@ -109,6 +150,8 @@ impl<'a> Scanner<'a> {
             '=' => {
                 if self.match_double('=') {
                     self.add_token(TokenType::EQUAL_EQUAL);
+                } else if self.match_double('>') {
+                    self.add_token(TokenType::FAT_ARROW);
                 } else {
                     self.add_token(TokenType::EQUAL);
                 }
@@ -156,7 +199,7 @@ impl<'a> Scanner<'a> {
                             return Err(msg);
                         }
                     }
-                } else if is_alpha(c) {
+                } else if is_alpha(c) || c == '_' {
                     self.identifier();
                 } else {
                     return Err(Error::UnknownToken(c, self.line as i128));
@@ -188,89 +231,95 @@ impl<'a> Scanner<'a> {
     }
 
     fn number(self: &mut Self) -> Result<(), Error> {
+        if self.source_as_bytes[self.start] == b'0'
+            && (self.peek() == 'x' || self.peek() == 'X')
+            && self.peek_next().is_ascii_hexdigit()
+        {
+            self.advance();
+            while self.peek().is_ascii_hexdigit() {
+                self.advance();
+            }
+            self.check_number_end()?;
+            self.add_number(NumberKind::Integer);
+            return Ok(());
+        }
         while is_digit(self.peek()) {
             self.advance();
         }
+        let mut fractional = false;
         if self.peek() == '.' && is_digit(self.peek_next()) {
+            fractional = true;
             self.advance();
             while is_digit(self.peek()) {
                 self.advance();
             }
-            if !self.is_at_end() {
-                let next = self.peek() as char;
-                match next {
-                    '=' | '!' | '*' | '+' | '-' | '/' | '>' | '<' | ' ' | ';' | ')' | '(' | '%' => {
-                    }
-                    _ => {
-                        return Err(Error::ExpectedAToken(
-                            Token {
-                                token_type: TokenType::BAD_TOKEN,
-                                lexeme: "".to_string(),
-                                literal: None,
-                                line_number: self.line,
-                            },
-                            "valid tokens : '=' | '!' | '*' | '+' | '-' | '/' | '>' | '<' | ' ' | ';' | ')' | '(' | '%' "
-                                .to_string(),
-                        ));
-                    }
-                }
-            }
-            let mut substr: String = String::new();
-            for i in self.start..self.current {
-                substr.push(self.source_as_bytes[i] as char);
+        }
+        // An exponent, as in `1e20` or `2.5E-3`, which is how large and tiny floats print.
+        let exponent = match (self.peek(), self.peek_next(), self.peek_after_next()) {
+            ('e' | 'E', digit, _) if is_digit(digit) => 1,
+            ('e' | 'E', '+' | '-', digit) if is_digit(digit) => 2,
+            _ => 0,
+        };
+        if exponent > 0 {
+            fractional = true;
+            for _ in 0..exponent {
+                self.advance();
             }
-            match substr.parse() {
-                Ok(value) => {
-                    self.add_token_to_scanner(TokenType::NUMBER, Some(LiteralValue::FValue(value)));
-                    return Ok(());
-                }
-                Err(_) => {
-                    return Err(Error::ParsingError(
-                        format!("cannot parse {} as Float", substr),
-                        self.line as i128,
-                    ));
-                }
+            while is_digit(self.peek()) {
+                self.advance();
             }
+        }
+        if (self.peek() == 'd' || self.peek() == 'r') && !is_valid_literal(self.peek_next()) {
+            return self.exact_number();
+        }
+        self.check_number_end()?;
+        let kind = if fractional {
+            NumberKind::Float
         } else {
-            if !self.is_at_end() {
-                let next = self.peek() as char;
-                match next {
-                    '=' | '!' | '*' | '+' | '-' | '/' | '>' | '<' | ' ' | ';' | '.' | ')' | '('
-                    | '%' => {}
-                    _ => {
-                        return Err(Error::ExpectedAToken(
-                            Token {
-                                token_type: TokenType::BAD_TOKEN,
-                                lexeme: "".to_string(),
-                                literal: None,
-                                line_number: self.line,
-                            },
-                            "valid token : '=' | '!' | '*' | '+' | '-' | '/' | '>' | '<' | ' ' | ';' | '.' | ')' | '(' | '%' "
-                                .to_string(),
-                        ));
-                    }
-                }
-            }
-            let mut substr: String = String::new();
-            for i in self.start..self.current {
-                substr.push(self.source_as_bytes[i] as char);
-            }
-            match substr.parse() {
-                Ok(value) => {
-                    self.add_token_to_scanner(
-                        TokenType::NUMBER,
-                        Some(LiteralValue::IntValue(value)),
-                    );
-                    return Ok(());
-                }
-                Err(_) => {
-                    return Err(Error::ParsingError(
-                        format!("cannot parse {} as Integer", substr),
-                        self.line as i128,
-                    ));
-                }
-            }
+            NumberKind::Integer
+        };
+        self.add_number(kind);
+        return Ok(());
+    }
+
+    // A number with a `d` (decimal) or `r` (rational) suffix, such as `1.10d` or `3r`.
+    fn exact_number(self: &mut Self) -> Result<(), Error> {
+        let kind = match self.advance() {
+            'd' => NumberKind::Decimal,
+            _ => NumberKind::Rational,
+        };
+        self.check_number_end()?;
+        self.add_number(kind);
+        return Ok(());
+    }
+
+    // Numbers keep their spelling, without any suffix, and the parser works out
+    // their value.
+    fn add_number(self: &mut Self, kind: NumberKind) {
+        let end = match kind {
+            NumberKind::Decimal | NumberKind::Rational => self.current - 1,
+            NumberKind::Integer | NumberKind::Float => self.current,
+        };
+        let digits = self.text(self.start..end);
+        self.add_token_to_scanner(
+            TokenType::NUMBER,
+            Some(LiteralValue::NumberValue(kind, digits)),
+        );
+    }
+
+    // A number may be followed by any operator or delimiter (including the `..` of a
+    // range), but not by identifier characters as in `12abc`.
+    fn check_number_end(self: &Self) -> Result<(), Error> {
+        if is_valid_literal(self.peek()) {
+            return Err(Error::ExpectedAToken(
+                Token::new(TokenType::BAD_TOKEN, "".to_string(), None, self.line),
+                format!(
+                    "an operator or delimiter after a number, found '{}'",
+                    self.peek()
+                ),
+            ));
         }
+        return Ok(());
     }
 
     fn peek_next(self: &mut Self) -> char {
@@ -281,6 +330,14 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    fn peek_after_next(self: &Self) -> char {
+        if (self.current + 2) >= self.source_as_bytes.len() {
+            return '\0';
+        } else {
+            return self.source_as_bytes[self.current + 2] as char;
+        }
+    }
+
     fn string(self: &mut Self) -> Result<(), Error> {
         while !self.is_at_end() && self.peek() != '"' {
             if self.peek() == '\n' {
@@ -292,10 +349,8 @@ impl<'a> Scanner<'a> {
             return Err(Error::UnterminatedStringError(self.line as i128));
         } else {
             self.advance();
-            let mut value: String = String::new();
-            for i in (self.start + 1)..(self.current - 1) {
-                value.push(self.source_as_bytes[i] as char)
-            }
+            // Sliced rather than read byte by byte so non-ASCII characters survive.
+            let value = self.text((self.start + 1)..(self.current - 1));
             self.add_token_to_scanner(TokenType::STRING, Some(LiteralValue::StringValue(value)));
             return Ok(());
         }
@@ -316,17 +371,14 @@ impl<'a> Scanner<'a> {
         token_type: TokenType,
         literal_value: Option<LiteralValue>,
     ) -> () {
-        let mut text: String = String::new();
-        let bytes = self.source_as_bytes;
-        for i in self.start..self.current {
-            text.push(bytes[i] as char);
-        }
-        self.tokens.push(Token {
-            token_type: (token_type),
-            lexeme: (text),
-            literal: (literal_value),
-            line_number: (self.line),
-        })
+        let text = self.text(self.start..self.current);
+        self.tokens
+            .push(Token::new(token_type, text, literal_value, self.line))
+    }
+
+    // The source text in `range`, which always starts and ends on ASCII delimiters.
+    fn text(self: &Self, range: std::ops::Range<usize>) -> String {
+        return String::from_utf8_lossy(&self.source_as_bytes[range]).into_owned();
     }
 
     fn match_double(self: &mut Self, expected: char) -> bool {
@@ -351,11 +403,14 @@ pub enum TokenType {
     RIGHT_PAREN,
     LEFT_BRACE,
     RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
     COMMA,
     DOT,
     MINUS,
     PLUS,
     SEMICOLON,
+    COLON,
     SLASH,
     STAR,
     MODULO,
@@ -369,6 +424,13 @@ pub enum TokenType {
     GREATER_EQUAL,
     LESS,
     LESS_EQUAL,
+    FAT_ARROW,
+    ARROW,
+    QUESTION,
+    DOT_DOT,
+    DOT_DOT_EQUAL,
+    STAR_STAR,
+    TILDE_SLASH,
 
     // Literals.
     IDENTIFIER,
@@ -378,11 +440,15 @@ pub enum TokenType {
     // Keywords.
     AND,
     CLASS,
+    CONST,
     ELSE,
+    ENUM,
     FALSE,
     FUN,
     FOR,
     IF,
+    IN,
+    MATCH,
     NIL,
     OR,
     PRINT,
@@ -392,6 +458,7 @@ pub enum TokenType {
     TRUE,
     VAR,
     WHILE,
+    YIELD,
 
     EOF,
     BAD_TOKEN,
@@ -403,83 +470,22 @@ impl std::fmt::Display for TokenType {
     }
 }
 
+// The value of a literal token, as produced by the scanner.
 #[derive(Debug, Clone, PartialEq)]
-#[allow(dead_code)]
 pub enum LiteralValue {
-    IntValue(i128),
-    FValue(f64),
+    // The digits of a number as written, without a `d` or `r` suffix.
+    NumberValue(NumberKind, String),
     StringValue(String),
     IdentifierValue(String),
-    True,
-    False,
-    Nil,
 }
 
-impl LiteralValue {
-    pub(crate) fn is_falsy(self: &mut Self) -> Result<LiteralValue, Error> {
-        match self {
-            LiteralValue::IntValue(x) => {
-                if x.clone() == 0 {
-                    return Ok(LiteralValue::True);
-                } else {
-                    return Ok(LiteralValue::False);
-                }
-            }
-            LiteralValue::FValue(x) => {
-                if x.clone() == 0.0 {
-                    return Ok(LiteralValue::True);
-                } else {
-                    return Ok(LiteralValue::False);
-                }
-            }
-            LiteralValue::StringValue(x) => {
-                if x.len() == (0 as usize) {
-                    return Ok(LiteralValue::True);
-                } else {
-                    return Ok(LiteralValue::False);
-                }
-            }
-            LiteralValue::IdentifierValue(_) => {
-                todo!();
-                // return Err(format!("Identifier is not implemented now ! "));
-            }
-            LiteralValue::True => {
-                return Ok(LiteralValue::False);
-            }
-            LiteralValue::False => {
-                return Ok(LiteralValue::True);
-            }
-            LiteralValue::Nil => {
-                return Ok(LiteralValue::True);
-            }
-        }
-    }
-
-    pub fn to_string(self: &Self) -> String {
-        match self {
-            LiteralValue::IntValue(value) => {
-                return value.to_string();
-            }
-            LiteralValue::FValue(value) => {
-                return value.to_string();
-            }
-            LiteralValue::StringValue(value) => {
-                return value.to_string();
-            }
-            LiteralValue::IdentifierValue(value) => {
-                return value.to_string();
-            }
-            LiteralValue::True => {
-                return "true".to_string();
-            }
-            LiteralValue::False => {
-                return "false".to_string();
-            }
-            LiteralValue::Nil => {
-                return "nil".to_string();
-            }
-        }
-    }
+// How a number literal is spelled: `1` or `0x1f`, `1.5` or `1e3`, `1.10d`, `3r`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberKind {
+    Integer,
+    Float,
+    Decimal,
+    Rational,
 }
 
 impl std::fmt::Display for LiteralValue {
@@ -494,6 +500,9 @@ pub struct Token {
     pub(crate) lexeme: String,
     pub(crate) literal: Option<LiteralValue>,
     pub(crate) line_number: usize,
+    // The interned lexeme of an identifier; other tokens are never names and
+    // carry `symbols::EMPTY`.
+    pub(crate) symbol: Symbol,
 }
 
 #[allow(dead_code)]
@@ -504,11 +513,16 @@ impl Token {
         literal: Option<LiteralValue>,
         line_number: usize,
     ) -> Self {
+        let symbol = match token_type {
+            TokenType::IDENTIFIER => symbols::intern(&lexeme),
+            _ => symbols::EMPTY,
+        };
         return Self {
             token_type,
             lexeme,
             literal,
             line_number,
+            symbol,
         };
     }
 }
@@ -1,10 +1,29 @@
-use crate::{expressions::Expression, scanner::Token};
+use std::rc::Rc;
+
+use crate::{
+    expressions::Expression,
+    patterns::Pattern,
+    scanner::Token,
+    types::{Signature, Type},
+};
 
 #[derive(PartialEq)]
 pub enum Statement {
     ExpressionStatement(Expression),
     PrintStatement(Expression),
-    Variable(Token, Expression),
+    // Declarations carry their type annotation, if any; only the checker reads it.
+    Variable(Token, Option<Type>, Expression),
+    Const(Token, Option<Type>, Expression),
+    // Nested bodies are reference counted so suspended generators can hold on to them.
+    Block(Rc<Vec<Statement>>),
+    // Enum name and its variants, each with the names of its payload fields.
+    Enum(Token, Vec<(Token, Vec<Token>)>),
+    Match(Token, Expression, Vec<(Pattern, Rc<Statement>)>),
+    // The `for` keyword, loop variable, iterable and body.
+    ForIn(Token, Token, Expression, Rc<Statement>),
+    // Name, parameters, body, whether the body contains `yield`, and the declared types.
+    Function(Token, Vec<Token>, Rc<Vec<Statement>>, bool, Signature),
+    Return(Token, Expression),
 }
 
 impl Statement {}
@@ -0,0 +1,324 @@
+use crate::{
+    errors::Error,
+    integers,
+    natives::invalid_argument,
+    scanner::Token,
+    symbols::{self, Symbol},
+    values::Value,
+};
+
+// A method available on every string, e.g. `"abc".upper()`. The receiver is
+// passed separately from the arguments, so `arity` does not count it.
+struct StringMethod {
+    name: &'static str,
+    arity: usize,
+    function: fn(&Token, &str, Vec<Value>) -> Result<Value, Error>,
+}
+
+// The longest string a method or the `*` operator will build, in bytes. Every
+// method whose result can outgrow its inputs checks it with `check_length`.
+const MAX_LENGTH: usize = 1 << 28;
+
+const METHODS: &[StringMethod] = &[
+    StringMethod {
+        name: "len",
+        arity: 0,
+        function: len,
+    },
+    StringMethod {
+        name: "upper",
+        arity: 0,
+        function: upper,
+    },
+    StringMethod {
+        name: "lower",
+        arity: 0,
+        function: lower,
+    },
+    StringMethod {
+        name: "trim",
+        arity: 0,
+        function: trim,
+    },
+    StringMethod {
+        name: "split",
+        arity: 1,
+        function: split,
+    },
+    StringMethod {
+        name: "join",
+        arity: 1,
+        function: join,
+    },
+    StringMethod {
+        name: "replace",
+        arity: 2,
+        function: replace,
+    },
+    StringMethod {
+        name: "find",
+        arity: 1,
+        function: find,
+    },
+    StringMethod {
+        name: "starts_with",
+        arity: 1,
+        function: starts_with,
+    },
+    StringMethod {
+        name: "ends_with",
+        arity: 1,
+        function: ends_with,
+    },
+    StringMethod {
+        name: "substring",
+        arity: 2,
+        function: substring,
+    },
+    StringMethod {
+        name: "chars",
+        arity: 0,
+        function: chars,
+    },
+    StringMethod {
+        name: "repeat",
+        arity: 1,
+        function: repeat,
+    },
+    StringMethod {
+        name: "parse_int",
+        arity: 0,
+        function: parse_int,
+    },
+    StringMethod {
+        name: "parse_float",
+        arity: 0,
+        function: parse_float,
+    },
+];
+
+fn lookup(name: Symbol) -> Option<&'static StringMethod> {
+    let position = symbols::position(METHODS, |method| method.name, name)?;
+    return Some(&METHODS[position]);
+}
+
+pub fn has_method(name: Symbol) -> bool {
+    return lookup(name).is_some();
+}
+
+pub fn method_arity(name: Symbol) -> Option<usize> {
+    return Some(lookup(name)?.arity);
+}
+
+pub fn call_method(
+    receiver: &str,
+    name: Symbol,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> Result<Value, Error> {
+    let method = match lookup(name) {
+        Some(method) => method,
+        None => {
+            return Err(Error::UndefinedProperty(
+                Value::StringValue(receiver.to_string()),
+                paren.clone(),
+            ))
+        }
+    };
+    if arguments.len() != method.arity {
+        return Err(Error::ArityMismatch(
+            paren.clone(),
+            method.arity,
+            arguments.len(),
+        ));
+    }
+    return (method.function)(paren, receiver, arguments);
+}
+
+fn string_argument<'a>(paren: &Token, method: &str, argument: &'a Value) -> Result<&'a str, Error> {
+    match argument {
+        Value::StringValue(x) => return Ok(x),
+        other => return Err(invalid_argument(paren, method, "a string", other)),
+    }
+}
+
+// Character positions are clamped to the string, so `substring(2, 100)` simply
+// runs to the end.
+fn position_argument(
+    paren: &Token,
+    method: &str,
+    argument: &Value,
+    length: usize,
+) -> Result<usize, Error> {
+    match argument {
+        Value::IntValue(i) => {
+            let position = if *i < 0 { length as i128 + i } else { *i };
+            return Ok(position.clamp(0, length as i128) as usize);
+        }
+        other => return Err(invalid_argument(paren, method, "an integer", other)),
+    }
+}
+
+fn len(_paren: &Token, receiver: &str, _arguments: Vec<Value>) -> Result<Value, Error> {
+    return Ok(Value::IntValue(receiver.chars().count() as i128));
+}
+
+fn upper(_paren: &Token, receiver: &str, _arguments: Vec<Value>) -> Result<Value, Error> {
+    return Ok(Value::StringValue(receiver.to_uppercase()));
+}
+
+fn lower(_paren: &Token, receiver: &str, _arguments: Vec<Value>) -> Result<Value, Error> {
+    return Ok(Value::StringValue(receiver.to_lowercase()));
+}
+
+fn trim(_paren: &Token, receiver: &str, _arguments: Vec<Value>) -> Result<Value, Error> {
+    return Ok(Value::StringValue(receiver.trim().to_string()));
+}
+
+// An empty separator splits on runs of whitespace, so `" a  b ".split("")` is
+// ["a", "b"].
+fn split(paren: &Token, receiver: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+    let separator = string_argument(paren, "split", &arguments[0])?;
+    let parts: Vec<Value> = if separator.is_empty() {
+        receiver
+            .split_whitespace()
+            .map(|part| Value::StringValue(part.to_string()))
+            .collect()
+    } else {
+        receiver
+            .split(separator)
+            .map(|part| Value::StringValue(part.to_string()))
+            .collect()
+    };
+    return Ok(Value::list(parts));
+}
+
+// `", ".join(items)` glues the string forms of `items` together with the receiver.
+fn join(paren: &Token, receiver: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+    match &arguments[0] {
+        Value::ListValue(items) => {
+            let parts: Vec<String> = items.borrow().iter().map(|item| item.to_string()).collect();
+            let length = parts.iter().map(String::len).sum::<usize>()
+                + receiver.len() * parts.len().saturating_sub(1);
+            check_length(paren, "join()", length as i128)?;
+            return Ok(Value::StringValue(parts.join(receiver)));
+        }
+        other => return Err(invalid_argument(paren, "join", "a list", other)),
+    }
+}
+
+fn replace(paren: &Token, receiver: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+    let from = string_argument(paren, "replace", &arguments[0])?;
+    let to = string_argument(paren, "replace", &arguments[1])?;
+    if from.is_empty() {
+        return Err(Error::InvalidArgument(
+            paren.clone(),
+            "replace() cannot replace an empty string".to_string(),
+        ));
+    }
+    let count = receiver.matches(from).count() as i128;
+    let length = receiver.len() as i128 + count * (to.len() as i128 - from.len() as i128);
+    check_length(paren, "replace()", length)?;
+    return Ok(Value::StringValue(receiver.replace(from, to)));
+}
+
+// The character index of the first occurrence, or -1 when there is none.
+fn find(paren: &Token, receiver: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+    let needle = string_argument(paren, "find", &arguments[0])?;
+    match receiver.find(needle) {
+        Some(byte) => return Ok(Value::IntValue(receiver[..byte].chars().count() as i128)),
+        None => return Ok(Value::IntValue(-1)),
+    }
+}
+
+fn starts_with(paren: &Token, receiver: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+    let prefix = string_argument(paren, "starts_with", &arguments[0])?;
+    return Ok(Value::from_bool(receiver.starts_with(prefix)));
+}
+
+fn ends_with(paren: &Token, receiver: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+    let suffix = string_argument(paren, "ends_with", &arguments[0])?;
+    return Ok(Value::from_bool(receiver.ends_with(suffix)));
+}
+
+fn substring(paren: &Token, receiver: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+    let chars: Vec<char> = receiver.chars().collect();
+    let start = position_argument(paren, "substring", &arguments[0], chars.len())?;
+    let end = position_argument(paren, "substring", &arguments[1], chars.len())?;
+    if start >= end {
+        return Ok(Value::StringValue(String::new()));
+    }
+    return Ok(Value::StringValue(chars[start..end].iter().collect()));
+}
+
+fn chars(_paren: &Token, receiver: &str, _arguments: Vec<Value>) -> Result<Value, Error> {
+    return Ok(Value::list(
+        receiver
+            .chars()
+            .map(|c| Value::StringValue(c.to_string()))
+            .collect(),
+    ));
+}
+
+fn repeat(paren: &Token, receiver: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+    match &arguments[0] {
+        Value::IntValue(count) if *count >= 0 => {
+            return repeated(paren, "repeat()", receiver, *count);
+        }
+        other => {
+            return Err(invalid_argument(
+                paren,
+                "repeat",
+                "a non-negative integer",
+                other,
+            ))
+        }
+    }
+}
+
+// `text` repeated `count` times, as built by both `repeat()` and the `*` operator.
+// A negative count gives the empty string; `operation` names the caller in the
+// error `check_length` raises.
+pub fn repeated(token: &Token, operation: &str, text: &str, count: i128) -> Result<Value, Error> {
+    let count = count.max(0);
+    check_length(token, operation, (text.len() as i128).saturating_mul(count))?;
+    return Ok(Value::StringValue(text.repeat(count as usize)));
+}
+
+// Fails when `operation` would build a string of `length` bytes, more than `MAX_LENGTH`.
+fn check_length(token: &Token, operation: &str, length: i128) -> Result<(), Error> {
+    if length > MAX_LENGTH as i128 {
+        return Err(Error::OverflowError(
+            token.clone(),
+            format!(
+                "{} would build a string of {} bytes, the limit is {}",
+                operation, length, MAX_LENGTH
+            ),
+        ));
+    }
+    return Ok(());
+}
+
+fn parse_int(paren: &Token, receiver: &str, _arguments: Vec<Value>) -> Result<Value, Error> {
+    match integers::parse(receiver.trim()) {
+        Some(value) => return Ok(value),
+        None => {
+            return Err(Error::IntegerParsingError(
+                format!("cannot parse \"{}\" as Integer", receiver),
+                paren.line_number as i128,
+            ))
+        }
+    }
+}
+
+fn parse_float(paren: &Token, receiver: &str, _arguments: Vec<Value>) -> Result<Value, Error> {
+    match receiver.trim().parse::<f64>() {
+        Ok(value) => return Ok(Value::FValue(value)),
+        Err(_) => {
+            return Err(Error::FloatParsingError(
+                format!("cannot parse \"{}\" as Float", receiver),
+                paren.line_number as i128,
+            ))
+        }
+    }
+}
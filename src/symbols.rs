@@ -0,0 +1,118 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+    rc::Rc,
+};
+
+// Identifiers are interned when they are scanned, so a name is a small number
+// from then on: environments are keyed by it, and comparing or hashing one never
+// touches the characters. Each thread has its own table, as it has its own values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    names: Vec<Rc<str>>,
+}
+
+// Names the interpreter itself looks up. They are interned first on every thread,
+// so their symbols are constants that can be matched on.
+const KNOWN: [&str; 6] = ["", "next", "send", "close", "iter", "read_line"];
+
+// What tokens that are not identifiers carry, as their text is never a name.
+pub const EMPTY: Symbol = Symbol(0);
+pub const NEXT: Symbol = Symbol(1);
+pub const SEND: Symbol = Symbol(2);
+pub const CLOSE: Symbol = Symbol(3);
+pub const ITER: Symbol = Symbol(4);
+pub const READ_LINE: Symbol = Symbol(5);
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::new());
+    // Static tables of named entries, indexed by symbol; see `position`.
+    static TABLES: RefCell<HashMap<(usize, usize), SymbolMap<usize>>> =
+        RefCell::new(HashMap::new());
+}
+
+impl Interner {
+    fn new() -> Self {
+        let mut interner = Interner {
+            symbols: HashMap::new(),
+            names: Vec::new(),
+        };
+        for name in KNOWN {
+            let name: Rc<str> = Rc::from(name);
+            let symbol = Symbol(interner.names.len() as u32);
+            interner.names.push(name.clone());
+            interner.symbols.insert(name, symbol);
+        }
+        return interner;
+    }
+}
+
+pub fn intern(name: &str) -> Symbol {
+    return INTERNER.with(|interner| {
+        let mut interner = interner.borrow_mut();
+        if let Some(symbol) = interner.symbols.get(name) {
+            return *symbol;
+        }
+        let name: Rc<str> = Rc::from(name);
+        let symbol = Symbol(interner.names.len() as u32);
+        interner.names.push(name.clone());
+        interner.symbols.insert(name, symbol);
+        return symbol;
+    });
+}
+
+impl Symbol {
+    pub fn name(self: Self) -> Rc<str> {
+        return INTERNER.with(|interner| interner.borrow().names[self.0 as usize].clone());
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", self.name());
+    }
+}
+
+// Where `name` is in a static table of named entries, like a module's functions.
+// A table is indexed by symbol the first time it is searched on a thread, so
+// lookups after that never compare strings.
+pub fn position<T>(table: &'static [T], entry_name: fn(&T) -> &str, name: Symbol) -> Option<usize> {
+    let key = (table.as_ptr() as usize, table.len());
+    return TABLES.with(|tables| {
+        let mut tables = tables.borrow_mut();
+        let index = tables.entry(key).or_insert_with(|| {
+            let mut index = SymbolMap::default();
+            for (position, entry) in table.iter().enumerate() {
+                index.insert(intern(entry_name(entry)), position);
+            }
+            return index;
+        });
+        return index.get(&name).copied();
+    });
+}
+
+// Symbols are already unique numbers, so hashing one only has to spread its bits.
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 << 8) | *byte as u64;
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.0 = n as u64;
+    }
+
+    fn finish(&self) -> u64 {
+        return self.0.wrapping_mul(0x9e3779b97f4a7c15);
+    }
+}
+
+pub type SymbolMap<V> = HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>;
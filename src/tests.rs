@@ -1,8 +1,826 @@
 #[cfg(test)]
 mod tests {
+    use crate::{
+        bytecode::Op,
+        cache,
+        compiler::Compiler,
+        disassembler::disassemble,
+        inference::Inferer,
+        interpreter::{Engine, Interpreter},
+        natives::Permissions,
+        optimizer::Optimizer,
+        parser::Parser,
+        run,
+        scanner::Scanner,
+        symbols, tpc,
+    };
 
     #[test]
     fn test_name() {}
+
+    #[test]
+    fn const_reassignment_is_rejected_by_parser() {
+        let mut interpreter = Interpreter::new();
+        let err = run("const a = 1;\na = 2;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("CANNOT REASSIGN CONSTANT : a (declared at line 1)"));
+    }
+
+    #[test]
+    fn const_reassignment_is_rejected_at_runtime() {
+        let mut interpreter = Interpreter::new();
+        assert!(run("const a = 1;".to_string(), &mut interpreter).is_ok());
+        let err = run("var a = 3;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("CANNOT REASSIGN CONSTANT : a (declared at line 1)"));
+        assert!(run("var b = 1; b = 2;".to_string(), &mut interpreter).is_ok());
+    }
+
+    #[test]
+    fn enum_variants_compare_and_destructure() {
+        let mut interpreter = Interpreter::new();
+        let source = "enum Status { Pending, Done(value), Failed(reason) }
+            var s = Status.Done(42);
+            match (s == Status.Done(42)) { true => {} }
+            match (s != Status.Done(41)) { true => {} }
+            match (s) { Status.Pending => {} Status.Done(v) => match (v) { 42 => {} } }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run(
+            "match (Status.Pending) { Status.Failed(r) => {} }".to_string(),
+            &mut interpreter,
+        )
+        .unwrap_err();
+        assert!(err.contains("NO MATCH ARM FOR VALUE : Status.Pending"));
+        let err = run("Status.Done(1, 2);".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("EXPECTED 1 ARGUMENTS BUT GOT 2"));
+    }
+
+    #[test]
+    fn for_in_walks_lists_strings_and_map_keys() {
+        let mut interpreter = Interpreter::new();
+        let source = "var total = 0;
+            for (x in [1, 2, 3]) total = total + x;
+            match (total) { 6 => {} }
+            var word = \"\";
+            for (c in \"abc\") word = c + word;
+            match (word) { \"cba\" => {} }
+            var accents = \"\";
+            for (c in \"éü\") accents = accents + c + \"|\";
+            match (accents) { \"é|ü|\" => {} }
+            var keys = \"\";
+            for (k in {\"a\": 1, \"b\": 2}) keys = keys + k;
+            match (keys) { \"ab\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run("for (x in 5) print x;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("NOT ITERABLE : 5"));
+        let source = "fun count_to(n) {
+                var i = 0;
+                fun next() { match (i < n) { true => { i = i + 1; return i; } false => return nil; } }
+                return {\"next\": next};
+            }
+            fun three() { return count_to(3); }
+            var counted = 0;
+            for (x in {\"iter\": three}) counted = counted * 10 + x;
+            match (counted) { 123 => {} }
+            var keys = \"\";
+            for (k in {\"iter\": 1, \"next\": 2}) keys = keys + k;
+            match (keys) { \"iternext\" => {} }";
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut interpreter = Interpreter::new();
+            interpreter.engine = engine;
+            assert!(run(source.to_string(), &mut interpreter).is_ok());
+            let err = run(
+                "fun bad() { return 1; }\nfor (x in {\"iter\": bad}) {}".to_string(),
+                &mut interpreter,
+            )
+            .unwrap_err();
+            assert!(err.contains("NOT ITERABLE : 1, at line 2"));
+        }
+    }
+
+    #[test]
+    fn generators_yield_lazily_and_accept_sent_values() {
+        let mut interpreter = Interpreter::new();
+        let source = "fun running_total() {
+                var total = 0;
+                for (x in [1, 2, 3]) {
+                    var sent = yield total;
+                    match (sent) { nil => {} n => total = total + n; }
+                }
+            }
+            var g = running_total();
+            match (g.next()) { 0 => {} }
+            match (g.send(5)) { 5 => {} }
+            match (g.send(7)) { 12 => {} }
+            g.close();
+            match (g.next()) { nil => {} }
+            fun squares(items) { for (x in items) yield x * x; }
+            var sum = 0;
+            for (s in squares([1, 2, 3])) sum = sum + s;
+            match (sum) { 14 => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run(
+            "fun bad() { yield 1; return 2; }".to_string(),
+            &mut interpreter,
+        )
+        .unwrap_err();
+        assert!(err.contains("Cannot return a value from a generator"));
+    }
+
+    #[test]
+    fn ranges_iterate_slice_and_match() {
+        let mut interpreter = Interpreter::new();
+        let source = "var sum = 0;
+            for (i in 0..=10 step 2) sum = sum + i;
+            match (sum) { 30 => {} }
+            var down = 0;
+            for (i in 10..0 step -3) down = down + i;
+            match (down) { 22 => {} }
+            match ([1, 2, 3, 4][1..3][1]) { 3 => {} }
+            match (\"hello\"[0..5 step 2]) { \"hlo\" => {} }
+            match (7) { 0..5 => print 1; 5..=9 => {} }
+            var huge = 0..1000000000000000000;";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run("var r = 0..10 step 0;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("INVALID RANGE : step cannot be zero"));
+    }
+
+    #[test]
+    fn native_conversions_check_arity_and_types() {
+        let mut interpreter = Interpreter::new();
+        let source = "match (int(\" 42 \") + int(3.9) + int(true)) { 46 => {} }
+            match (float(\"1.5\")) { 1.5 => {} }
+            match (str(12) + str(nil)) { \"12nil\" => {} }
+            match (bool(\"\")) { false => {} }
+            match (type([1]) + type(1.0) + type(clock)) { \"listfloatfunction\" => {} }
+            match (type(clock())) { \"float\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run("int(\"abc\");".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("cannot parse \"abc\" as Integer at line 1"));
+        let err = run("int([1]);".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("INVALID ARGUMENT : int() expected a number, string or bool"));
+        let err = run("str(1, 2);".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("EXPECTED 1 ARGUMENTS BUT GOT 2"));
+    }
+
+    #[test]
+    fn string_methods_transform_and_parse() {
+        let mut interpreter = Interpreter::new();
+        let source = "match (\"  Hello \".trim().upper()) { \"HELLO\" => {} }
+            match (\"hello\".len() + \"abc\".find(\"c\") + \"abc\".find(\"z\")) { 6 => {} }
+            var parts = \"a,b,c\".split(\",\");
+            match (\"-\".join(parts)) { \"a-b-c\" => {} }
+            match (\"banana\".replace(\"an\", \"AN\").lower()) { \"banana\" => {} }
+            match (\"banana\".substring(1, -1)) { \"anan\" => {} }
+            match (\"ab\".repeat(3).starts_with(\"aba\")) { true => {} }
+            match (\"ab\".ends_with(\"a\")) { false => {} }
+            match (\"xyz\".chars()[2]) { \"z\" => {} }
+            match (\" 12 \".parse_int() + \"0.5\".parse_float()) { 12.5 => {} }
+            match (\"héllo\".len()) { 5 => {} }
+            match (\"héllo\".upper() + \"héllo\".substring(1, 3) + \"日本\".chars()[1]) { \"HÉLLOél本\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run("\"1x\".parse_int();".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("cannot parse \"1x\" as Integer at line 1"));
+        let err = run("\"a\".split(1);".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("INVALID ARGUMENT : split() expected a string but got int 1"));
+        let err = run("\"a\".reverse();".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("reverse"));
+        let err = run("\"ab\".repeat(4000000000);".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("repeat() would build a string of 8000000000 bytes"));
+        let err = run("var s = \"ab\" * 4000000000;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("repeating with * would build a string of 8000000000 bytes"));
+        let source = "match (\"ab\" * 2 + 3 * \"c\" + \"d\" * -1) { \"ababccc\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let source = "var s = \"a\".repeat(1000).replace(\"a\", \"b\".repeat(1000000));";
+        let err = run(source.to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("replace() would build a string of 1000000000 bytes"));
+        let source = "var s = \"x\".repeat(1000000).join(\"1\".repeat(300).chars());";
+        let err = run(source.to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("join() would build a string of 299000300 bytes"));
+        let source = "match (\"-\".join(\" a  b \".split(\"\"))) { \"a-b\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+    }
+
+    #[test]
+    fn math_module_keeps_integers_exact() {
+        let mut interpreter = Interpreter::new();
+        let source = "match (math.abs(-3)) { 3 => {} }
+            match (math.abs(0 - 2.5)) { 2.5 => {} }
+            match (type(math.abs(-3)) + type(math.sqrt(4))) { \"intfloat\" => {} }
+            match (math.pow(2, 10) + math.floor(2.7) + math.ceil(2.1) + math.round(0 - 2.5)) { 1026 => {} }
+            match (math.pow(4, 0.5)) { 2.0 => {} }
+            match (math.min(3, 1.5)) { 1.5 => {} }
+            match (math.max(3, 1.5)) { 3 => {} }
+            match (math.gcd(-12, 18) + math.isqrt(99)) { 15 => {} }
+            match (math.floor(math.pi * 100)) { 314 => {} }
+            match (math.log(math.e) + math.cos(0)) { 2.0 => {} }
+            match (math.inf > 1000000) { true => {} }
+            match (type(math)) { \"module\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run("math.isqrt(-1);".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("isqrt() expected a non-negative integer but got int -1"));
+        let err = run("math.tau2;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("UNDEFINED PROPERTY : tau2 on <module math>"));
+    }
+
+    #[test]
+    fn fs_module_requires_permission_and_reads_lines() {
+        let mut interpreter = Interpreter::new();
+        let err = run("fs.exists(\".\");".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("PERMISSION DENIED : fs.exists needs file access"));
+
+        let dir = std::env::temp_dir().join(format!("timepass-fs-{}", std::process::id()));
+        let dir = dir.to_string_lossy().replace('\\', "/");
+        let mut interpreter = Interpreter::with_permissions(Permissions { file_access: true });
+        let source = format!(
+            "const dir = \"{}\";
+            fs.mkdir(dir + \"/sub\");
+            fs.write_text(dir + \"/a.txt\", \"one\ntwo\n\");
+            fs.append(dir + \"/a.txt\", \"three\");
+            match (fs.read_text(dir + \"/a.txt\")) {{ Result.Ok(text) => match (text.split(\"\n\")[2]) {{ \"three\" => {{}} }} }}
+            match (fs.list_dir(dir)) {{ Result.Ok(names) => match (names[0] + names[1]) {{ \"a.txtsub\" => {{}} }} }}
+            var seen = \"\";
+            match (fs.open(dir + \"/a.txt\")) {{ Result.Ok(file) => for (line in file) {{ seen = seen + line + \";\"; }} }}
+            match (seen) {{ \"one;two;three;\" => {{}} }}
+            match (fs.open(dir + \"/a.txt\")) {{
+                Result.Ok(file) => {{
+                    match (file.read_line()) {{ Result.Ok(\"one\") => {{}} }}
+                    file.close();
+                    match (file.read_line()) {{ Result.Ok(nil) => {{}} }}
+                }}
+            }}
+            fs.remove(dir + \"/a.txt\");
+            fs.remove(dir + \"/sub\");
+            match (fs.exists(dir + \"/a.txt\")) {{ false => {{}} }}
+            var reason = nil;
+            match (fs.read_text(dir + \"/missing.txt\")) {{ Result.Ok(text) => {{}} Result.Err(message) => reason = message; }}
+            match (fs.remove(dir + \"/missing.txt\")) {{ Result.Err(_) => {{}} }}
+            var after = reason.starts_with(\"fs.read_text(\");",
+            dir
+        );
+        let result = run(source, &mut interpreter);
+        let after = run("match (after) { true => {} }".to_string(), &mut interpreter);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(result.is_ok());
+        assert!(after.is_ok());
+    }
+
+    #[test]
+    fn integer_overflow_and_modulo_by_zero_are_errors() {
+        let mut interpreter = Interpreter::new();
+        let source = "const big = 170141183460469231731687303715884105727;
+            match (big - 1 + 1) { 170141183460469231731687303715884105727 => {} }
+            match (7 % 3 + -7 % 3) { 0 => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run(
+            "var a = 1;\nvar b = 2 **\n (2 ** 40);".to_string(),
+            &mut interpreter,
+        )
+        .unwrap_err();
+        assert!(
+            err.contains("OVERFLOW ERROR : 2 ** 1099511627776 is too large to compute, at line 2")
+        );
+        let err = run("var a = 5 % 0;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("ZERO DIVISION ERROR at line 1"));
+        let err = run("print 2 ** 3000000000;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("OVERFLOW ERROR : 2 ** 3000000000 is too large to compute"));
+        let err = run("print (2/3r) ** 100000000;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("OVERFLOW ERROR"));
+        let err = run("var a = 0 ** -1;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("ZERO DIVISION ERROR at line 1"));
+    }
+
+    #[test]
+    fn integers_promote_to_big_integers_transparently() {
+        let mut interpreter = Interpreter::new();
+        let source = "const big = 170141183460469231731687303715884105727;
+            match (str(big + 1)) { \"170141183460469231731687303715884105728\" => {} }
+            match (big + 1 - 1 == big) { true => {} }
+            match (type(big * big) + str(big * big > big) + str(big * 2 > 1.0)) { \"inttruetrue\" => {} }
+            match (str(2 ** 200 % 1000000007)) { \"499445072\" => {} }
+            match (str(-(0 - big - 1))) { \"170141183460469231731687303715884105728\" => {} }
+            match (2 ** 130 / 2 ** 129) { 2.0 => {} }
+            match (2 ** -1 + -2 ** 2 + 2 ** 3 ** 2) { 508.5 => {} }
+            match (0xff + 0X10) { 271 => {} }
+            match (hex(2 ** 128) + hex(0 - 255)) { \"0x100000000000000000000000000000000-0xff\" => {} }
+            match (int(\"0x1\" + \"0\" * 40) == 16 ** 40) { true => {} }
+            match (\"123456789012345678901234567890123456789\".parse_int() % 1000) { 789 => {} }
+            match (math.gcd(2 ** 200, 6 ** 100) + math.isqrt(10 ** 40) - 10 ** 20) { 1267650600228229401496703205376 => {} }
+            match (math.pow(10, 40) == 10 ** 40) { true => {} }
+            match (170141183460469231731687303715884105728) { 170141183460469231731687303715884105728 => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+    }
+
+    #[test]
+    fn decimals_and_rationals_stay_exact() {
+        let mut interpreter = Interpreter::new();
+        let source = "match (0.1d + 0.2d == 0.3d) { true => {} }
+            match (str(1/3r + 1/6r) + \" \" + type(1/3r + 1)) { \"1/2 rational\" => {} }
+            match (type(1/3r + 0.5d) + \" \" + str(1d / 3)) { \"decimal 0.3333333333333333333333333333\" => {} }
+            match (type(1/2r + 0.5)) { \"float\" => {} }
+            match (str(1.10d * 3) + \" \" + str(-1.5d) + \" \" + str((2/3r) ** 2)) { \"3.30 -1.5 4/9\" => {} }
+            match (str(rational(0.75) + rational(\"2/6\"))) { \"13/12\" => {} }
+            match (str(decimal(0.1) + decimal(\"1.25\") + decimal(1/4r))) { \"1.60\" => {} }
+            match (int(7/2r) + int(-2.9d)) { 1 => {} }
+            match (float(1/4r)) { 0.25 => {} }
+            match (1/2r < 0.6d) { true => {} }
+            match (0.30d) { 0.3d => {} }
+            match (str(2.5d ** -2) + \" \" + str(1.5d ** 3)) { \"0.16 3.375\" => {} }
+            match (str(math.abs(-1.5d)) + \" \" + str(math.abs(-7/2r))) { \"1.5 7/2\" => {} }
+            match (str(math.floor(7/2r)) + str(math.ceil(7/2r)) + str(math.round(-2.5d))) { \"34-3\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run("var a = 1d / 0;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("ZERO DIVISION ERROR at line 1"));
+        let err = run("rational(\"1/0\");".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("rational() expected a finite number or numeric string"));
+        let err = run("var a = 2.5d ** 100000000;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("2.5 ** 100000000 is too large to compute"));
+        let err = run(
+            "var a = 1e-1000000d ** 16000000;".to_string(),
+            &mut interpreter,
+        )
+        .unwrap_err();
+        assert!(err.contains("** 16000000 is too large to compute"));
+        let err = run("print 1e-4294967297r;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("cannot parse 1e-4294967297r as a number"));
+    }
+
+    #[test]
+    fn numbers_share_one_promotion_and_division_model() {
+        let mut interpreter = Interpreter::new();
+        let source = "match (-2.5 + 0.5) { -2.0 => {} }
+            match (str(3.0) + \" \" + str(3) + \" \" + str(0.1 + 0.2)) { \"3.0 3 0.30000000000000004\" => {} }
+            match (str(7 % 2.5) + \" \" + str(7.5 % 2)) { \"2.0 1.5\" => {} }
+            match (7 / 2) { 3.5 => {} }
+            match (str(7 ~/ 2) + \" \" + str(-7 ~/ 2) + \" \" + str(7.5 ~/ 2)) { \"3 -3 3.0\" => {} }
+            match (type(7/2r ~/ (1/2r)) + \" \" + str(7d ~/ 2)) { \"rational 3\" => {} }
+            match (-7 ~/ 2 * 2 + -7 % 2) { -7 => {} }
+            match ((2 ** 128) ~/ 3 > 2 ** 126) { true => {} }
+            match (str(1 == 1.0) + str(1/2r == 0.5) + str(0.5d == 1/2r)) { \"truetruetrue\" => {} }
+            match (str(10.0 ** 20.0) + \" \" + str(1.5E-7)) { \"1e20 1.5e-7\" => {} }
+            match (str(1e20 == 10.0 ** 20.0) + str(2.5e+3 == 2500) + str(1e3d == 1000)) { \"truetruetrue\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run("var a = 1 ~/ 0.0;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("ZERO DIVISION ERROR at line 1"));
+        let err = run("var a = 1 ~ 2;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("~"));
+        let err = run("print 1e-400000000000d;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("cannot parse 1e-400000000000d as a number at line 1"));
+        let err = run("decimal(\"1e400000000000\");".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("decimal() expected a finite number or numeric string"));
+    }
+
+    #[test]
+    fn equality_is_total_and_ordering_is_checked() {
+        let mut interpreter = Interpreter::new();
+        let source = "enum Shape { Circle(r), Square(s) }
+            match (str(nil == nil) + str(true == false) + str(1 == \"1\") + str(nil != false)) { \"truefalsefalsetrue\" => {} }
+            match (str([1, [2.0]] == [1.0, [2]]) + str([1] == [1, 2])) { \"truefalse\" => {} }
+            match (str({\"a\": 1, \"b\": 2} == {\"b\": 2.0, \"a\": 1})) { \"true\" => {} }
+            match (str(Shape.Circle(1) == Shape.Circle(1.0)) + str(Shape.Circle(1) == Shape.Square(1))) { \"truefalse\" => {} }
+            match (str(\"a\" >= \"b\") + str(\"a\" <= \"b\") + str(\"b\" >= \"b\")) { \"falsetruetrue\" => {} }
+            match (clock == clock) { true => {} }
+            match (str(2 ** 53 + 1 == 9007199254740992.0) + str(2 ** 53 + 1 > 9007199254740992.0)) { \"falsetrue\" => {} }
+            match (str(2 ** 2000 == math.inf) + str(2 ** 2000 < math.inf) + str(-(2 ** 2000) > -math.inf)) { \"falsetruetrue\" => {} }
+            match (str(1 == math.nan) + str(1 != math.nan) + str(1/3r == 1.0 / 3)) { \"falsetruefalse\" => {} }
+            match (math.max(9007199254740992.0, 2 ** 53 + 1)) { 9007199254740993 => {} }
+            match ({1: \"a\"}[1.0] + {1/2r: \"b\"}[0.5d]) { \"ab\" => {} }
+            var once = 0;
+            for (key in {1: \"a\", 1.0: \"b\"}) once = once + 1;
+            match (str(once) + {1: \"a\", 1.0: \"b\"}[1]) { \"1b\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run("var a = 1 < \"2\";".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains(
+            "UNORDERED COMPARISON : int 1 and string \"2\" can not be compared with <, at line 1"
+        ));
+        let err = run("var a = nil >= nil;".to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("UNORDERED COMPARISON"));
+        let err = run(
+            "var a = 1e-600000d * 1e-600000d;".to_string(),
+            &mut interpreter,
+        )
+        .unwrap_err();
+        assert!(err.contains("the result of * needs a decimal scale beyond 1000000"));
+        let source = "match (str(1e-600000d < 0.5) + str(1e-600000d > 0.0)) { \"truetrue\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+    }
+
+    #[test]
+    fn runtime_values_share_lists_and_maps() {
+        let mut interpreter = Interpreter::new();
+        let source = "var xs = [1, [2, 3]];
+            var ys = xs;
+            match (str(ys == xs) + str(ys[1][0]) + str(!xs) + str(![]) + str(!{}) + str(!{1: 2})) { \"true2falsetruetruefalse\" => {} }
+            match (str(!clock) + str(!math) + str(!(1..1)) + str(!0.0) + str(!\"\")) { \"falsefalsetruetruetrue\" => {} }
+            match (str([1, 2][0..1]) + str({1: [1]})) { \"[1]{1: [1]}\" => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+    }
+
+    #[test]
+    fn type_annotations_are_checked_before_running() {
+        let mut interpreter = Interpreter::new();
+        let source = "enum Shape { Circle(r), Square(s) }
+            fun add(a: int, b: int) -> int { return a + b; }
+            var total: float = add(1, 2);
+            var name: string? = nil;
+            const shape: Shape? = Shape.Circle(1);
+            fun label(s: string?) -> string { return \"label\"; }
+            match (label(name) + str(total)) { \"label3\" => {} }
+            var loose = 1;
+            loose = \"now a string\";";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let err = run(
+            "fun add(a: int, b: int) -> int { return a + b; }
+            var x: int = \"a\";
+            add(1, 2, 3);
+            add(\"a\", 1);
+            var n: int = 5;
+            n.upper();
+            var t: string = nil;
+            var u: Color = 1;
+            fun f() -> int { return \"x\"; }"
+                .to_string(),
+            &mut Interpreter::new(),
+        )
+        .unwrap_err();
+        assert!(
+            err.contains("TYPE ERROR : 'x' is declared int but initialized with string, at line 2")
+        );
+        assert!(err.contains("EXPECTED 2 ARGUMENTS BUT GOT 3, at line 3"));
+        assert!(
+            err.contains("TYPE ERROR : argument 1 of add() must be int but is string, at line 4")
+        );
+        assert!(err.contains("TYPE ERROR : int has no property 'upper', at line 6"));
+        assert!(
+            err.contains("TYPE ERROR : 't' is declared string but initialized with nil, at line 7")
+        );
+        assert!(err.contains("TYPE ERROR : unknown type 'Color', at line 8"));
+        assert!(err
+            .contains("TYPE ERROR : f() is declared to return int but returns string, at line 9"));
+        // Unannotated code is only checked when it runs.
+        let err = run("var y = 1; y.upper();".to_string(), &mut Interpreter::new()).unwrap_err();
+        assert!(!err.contains("TYPE ERROR"));
+    }
+    #[test]
+    fn inference_flags_unannotated_type_errors() {
+        let source = "fun add(a, b) { return a + b; }
+            fun id(x) { return x; }
+            var s = id(\"s\");
+            var n = id(3);
+            var total = add(n, 2);
+            var mixed = add(1, 2.0);
+            var count = 0;
+            count = \"reset\";
+            match (s + str(total)) { \"s5\" => {} }";
+        let mut interpreter = Interpreter::new();
+        interpreter.infer_types = true;
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut inferer = Inferer::new();
+        assert!(inferer.infer(&statements).is_empty());
+        let signatures = inferer.signatures();
+        assert!(signatures.contains(&"add : ('a, 'a) -> 'a".to_string()));
+        assert!(signatures.contains(&"id : ('a) -> 'a".to_string()));
+        assert!(signatures.contains(&"total : int".to_string()));
+        // Numbers of different kinds are promoted as they are at runtime.
+        assert!(signatures.contains(&"mixed : float".to_string()));
+        assert!(signatures.contains(&"count : any".to_string()));
+        let bad = "var a = \"a\" - 1;
+            fun inc(n) { return n + 1; }
+            inc(\"x\");
+            var f = 3;
+            f(1);";
+        // Without --infer these are only found at runtime.
+        let err = run(bad.to_string(), &mut Interpreter::new()).unwrap_err();
+        assert!(!err.contains("TYPE ERROR"));
+        let mut interpreter = Interpreter::new();
+        interpreter.infer_types = true;
+        let err = run(bad.to_string(), &mut interpreter).unwrap_err();
+        assert!(err.contains("TYPE ERROR : '-' is not defined for string and int, at line 1"));
+        assert!(err
+            .contains("TYPE ERROR : argument 1 of inc() must be number but is string, at line 3"));
+        assert!(err.contains("TYPE ERROR : int is not callable, at line 5"));
+    }
+    #[test]
+    fn resolver_reports_binding_errors_before_running() {
+        let mut interpreter = Interpreter::new();
+        let source =
+            "fun even(n) { match (n) { 0 => { return true; } _ => { return odd(n - 1); } } }
+            fun odd(n) { match (n) { 0 => { return false; } _ => { return even(n - 1); } } }
+            { fun f() { return g(); } fun g() { return 1; } match (f()) { 1 => {} } }
+            var a = 1;
+            match (even(4)) { true => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        // Each shell line may build on what earlier ones defined.
+        assert!(run(
+            "var a = a + 1; match (a) { 2 => {} }".to_string(),
+            &mut interpreter
+        )
+        .is_ok());
+        let err = run(
+            "print \"side effect\";
+            print c;
+            var c = 1;
+            { var b = b; }
+            fun f(x) { var x = 2; return y; }
+            var c = 3;
+            return 1;"
+                .to_string(),
+            &mut Interpreter::new(),
+        )
+        .unwrap_err();
+        assert!(err.contains("RESOLUTION ERROR : 'c' is used before its declaration, at line 2"));
+        assert!(
+            err.contains("RESOLUTION ERROR : can not read 'b' in its own initializer, at line 4")
+        );
+        assert!(err.contains("RESOLUTION ERROR : 'x' is already declared in this scope, at line 5"));
+        assert!(err.contains("RESOLUTION ERROR : 'y' is not declared, at line 5"));
+        assert!(err.contains("RESOLUTION ERROR : 'c' is already declared in this scope, at line 6"));
+        assert!(err.contains("RESOLUTION ERROR : can not return from top-level code, at line 7"));
+        let err = run(
+            "print \"side effect\"; fun f() { return this; }".to_string(),
+            &mut Interpreter::new(),
+        )
+        .unwrap_err();
+        assert!(err.contains("'this' is reserved and has no meaning without classes at line 1"));
+    }
+    #[test]
+    fn closures_read_the_variable_in_scope_where_they_are_declared() {
+        let source = "var a = \"global\";
+            var seen = \"\";
+            fun f() {
+                fun show() { seen = seen + a + \" \"; }
+                show();
+                var a = \"local\";
+                show();
+                return a;
+            }
+            match (f() + \" \" + seen) { \"local global global \" => {} }
+            fun counter() {
+                var n = 0;
+                fun inc() { { var n2 = n; n = n2 + 1; } return n; }
+                return inc;
+            }
+            var c = counter();
+            c();
+            match (c()) { 2 => {} }
+            var total = 0;
+            for (i in 1..4) { match (i) { k => { var twice = k * 2; total = total + twice; } } }
+            match (total) { 12 => {} }
+            fun echo() { var got = nil; got = yield 1; yield got; }
+            var g = echo();
+            g.next();
+            match (g.send(5)) { 5 => {} }";
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut interpreter = Interpreter::new();
+            interpreter.engine = engine;
+            assert!(run(source.to_string(), &mut interpreter).is_ok());
+        }
+        let err = run(
+            "fun f() { fun show() { return later; } var later = 1; return show(); }".to_string(),
+            &mut Interpreter::new(),
+        )
+        .unwrap_err();
+        assert!(err.contains("RESOLUTION ERROR : 'later' is not declared, at line 1"));
+    }
+    #[test]
+    fn optimizer_folds_constants_and_drops_dead_arms() {
+        let parse = |source: &str| {
+            let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+            return Parser::new(tokens).parse().unwrap();
+        };
+        let optimized = Optimizer::new()
+            .optimize(parse(
+                "var a = (2 * 3 + 4) ~/ 3; var s = \"ab\" + \"c\"; var b = !(1 < 2); var x = a * 2;
+                match (3) { 1 => print \"one\"; 0..5 => print \"small\"; _ => print \"big\"; }",
+            ))
+            .ok()
+            .unwrap();
+        let expected = parse(
+            "var a = 3; var s = \"abc\"; var b = false; var x = a * 2;
+            { print \"small\"; }",
+        );
+        assert!(optimized == expected);
+        // With no `if` in the language, a literal `match` stands in for a constant
+        // condition and collapses to the body of the arm that runs.
+        let optimized = Optimizer::new()
+            .optimize(parse(
+                "match (1 < 2) { false => print \"no\"; true => print \"x\" + \"y\" * 2; }
+                var long = \"ab\" * 1000;",
+            ))
+            .ok()
+            .unwrap();
+        let expected = parse("{ print \"xyy\"; }\nvar long = \"ab\" * 1000;");
+        assert!(optimized == expected);
+        let mut interpreter = Interpreter::new();
+        interpreter.optimize = true;
+        let source = "var a = 2 ** 10 - 24; match (a == 1000) { true => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        // Failures are reported before running, even in code that never runs.
+        let err = run(
+            "print \"side effect\";\nfun never() { return 1 / 0; }\nvar b = \"a\" - 1;".to_string(),
+            &mut interpreter,
+        )
+        .unwrap_err();
+        assert!(err.contains("ZERO DIVISION ERROR at line 2"));
+        assert!(err.contains("OPERATION MINUS is not defined"));
+        // Arms the optimizer drops are gone before their bodies are folded.
+        let source = "match (1) { 2 => { print 1 / 0; } _ => { print \"ok\"; } }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+    }
+    #[test]
+    fn vm_engine_matches_the_tree_walker() {
+        let source = "fun make_counter() {
+                var count = 0;
+                fun inc() { count = count + 1; return count; }
+                return inc;
+            }
+            var c = make_counter();
+            c();
+            match (c()) { 2 => {} }
+            fun running_total() {
+                var total = 0;
+                for (x in [1, 2, 3]) {
+                    var sent = yield total;
+                    match (sent) { nil => {} n => total = total + n; }
+                }
+            }
+            var g = running_total();
+            match (g.next()) { 0 => {} }
+            match (g.send(5)) { 5 => {} }
+            match (g.send(7)) { 12 => {} }
+            enum Shape { Circle(r), Square(s) }
+            fun area(s) { match (s) { Shape.Circle(r) => { return r * 3; } Shape.Square(x) => { return x * x; } } }
+            var sum = 0;
+            for (s in [Shape.Circle(2), Shape.Square(5)]) { sum = sum + area(s); }
+            match (sum) { 31 => {} }
+            fun fib(n) { match (n) { 0 => { return 0; } 1 => { return 1; } _ => { return fib(n - 1) + fib(n - 2); } } }
+            match (str(fib(10)) + str(c)) { \"55<fn inc>\" => {} }
+            print 1 / 0;
+            var after = [1, 2, 3][-1];
+            print \"a\" - 1;
+            match (after) { 3 => {} }";
+        let mut results = Vec::new();
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut interpreter = Interpreter::new();
+            interpreter.engine = engine;
+            let err = run(source.to_string(), &mut interpreter).unwrap_err();
+            assert!(err.contains("ZERO DIVISION ERROR at line 27"));
+            assert!(err.contains("OPERATION MINUS is not defined"));
+            let runtime = run("area(1);".to_string(), &mut interpreter).unwrap_err();
+            results.push((err, runtime));
+        }
+        assert!(results[0] == results[1]);
+    }
+    #[test]
+    fn runaway_recursion_is_a_stack_overflow_in_both_engines() {
+        // The tree-walker needs the stack the interpreter's thread gets in `main`.
+        let worker = std::thread::Builder::new()
+            .stack_size(crate::STACK_SIZE)
+            .spawn(|| {
+                for engine in [Engine::Tree, Engine::Vm] {
+                    let mut interpreter = Interpreter::new();
+                    interpreter.engine = engine;
+                    let err = run("fun r(n) { return r(n); }\nr(1);".to_string(), &mut interpreter)
+                        .unwrap_err();
+                    assert!(err.contains("STACK OVERFLOW : more than 1000 nested calls, at line 1"));
+                    let source = "fun depth(n) { match (n) { 0 => return 0; m => return 1 + depth(n - 1); } }
+                        match (depth(999)) { 999 => {} }
+                        fun nested(n) { match (n) { 0 => yield 0; m => yield nested(n - 1).next() + 1; } }
+                        match (nested(499).next()) { 499 => {} }";
+                    assert!(run(source.to_string(), &mut interpreter).is_ok());
+                    let err = run("depth(1000);".to_string(), &mut interpreter).unwrap_err();
+                    assert!(err.contains("STACK OVERFLOW"));
+                }
+            })
+            .unwrap();
+        assert!(worker.join().is_ok());
+    }
+    #[test]
+    fn disassembler_lists_offsets_lines_operands_and_nested_functions() {
+        let source = "var total = 0;\nfun add(n) {\n  total = total + n;\n  return total;\n}\nprint add(40 + 2);";
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let listing = disassemble(&Compiler::new().compile(&statements));
+        assert!(listing.contains("== <fn script> arity 0, 0 cells, captures [] =="));
+        assert!(listing.contains("0000    1 CONSTANT           0 '0'"));
+        assert!(listing.contains("0001    | DEFINE_GLOBAL      0 'total'"));
+        assert!(listing.contains("0002    2 CLOSURE            0 <fn add>"));
+        assert!(listing.contains("0004    6 GET_GLOBAL         2 'add'"));
+        assert!(listing.contains("0007    | BINARY             3 '+'"));
+        assert!(listing.contains("== <fn add> arity 1, 0 cells, captures [] =="));
+        assert!(listing.contains("0000    3 GET_GLOBAL         0 'total'"));
+        assert!(listing.contains("0001    | GET_LOCAL          1"));
+        assert!(listing.contains("0005    4 GET_GLOBAL         4 'total'"));
+    }
+    #[test]
+    fn compiled_files_round_trip_and_reject_other_versions() {
+        let source = "enum Shape { Circle(r), Square(s) }
+            const big = 170141183460469231731687303715884105727 + 1;
+            var exact = 1/3r + 1.10d;
+            fun area(s) { match (s) { Shape.Circle(r) => { return r * 3.5; } Shape.Square(x) => { return x * x; } } }
+            fun evens() { for (i in 0..=6 step 2) yield i; }
+            var total = 0;
+            for (e in evens()) total = total + e;
+            match (str(area(Shape.Circle(2))) + str(total) + str(big - 1)) { \"7.012170141183460469231731687303715884105727\" => {} }
+            match (1) { 0..5 => {} }";
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let program = Compiler::new().compile(&statements);
+        let bytes = tpc::write(&program, Some(tpc::source_hash(source)));
+        let compiled = tpc::read(&bytes).ok().unwrap();
+        assert!(compiled.source_hash == Some(tpc::source_hash(source)));
+        assert!(disassemble(&compiled.program) == disassemble(&program));
+        assert!(Interpreter::new()
+            .interpret_compiled(compiled.program)
+            .is_ok());
+        let mut other_version = bytes.clone();
+        other_version[4] = 99;
+        let err = tpc::read(&other_version).err().unwrap();
+        assert!(err.contains("format version 99, expected 2"));
+        let err = tpc::read(&bytes[..bytes.len() - 1]).err().unwrap();
+        assert!(err.contains("Compiled file is corrupt"));
+        // So is code reaching outside its tables, cells or stack.
+        for op in [
+            Op::Constant(99),
+            Op::GetLocal(216),
+            Op::PopN(3),
+            Op::GetCell(0, 0),
+            Op::Jump(9999),
+        ] {
+            let mut program = Compiler::new().compile(&statements);
+            program.chunk.code[0] = op;
+            let err = tpc::read(&tpc::write(&program, None)).err().unwrap();
+            assert!(err.contains("Compiled file is corrupt"));
+        }
+    }
+    #[test]
+    fn compile_cache_hits_only_for_the_same_source_and_options() {
+        let directory =
+            std::env::temp_dir().join(format!("timepass-cache-test-{}", std::process::id()));
+        let source = "var a = 40; print a + 2;";
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let program = Compiler::new().compile(&Parser::new(tokens).parse().unwrap());
+        let key = cache::key(source, &[false, false]);
+        assert!(key != cache::key(source, &[false, true]));
+        assert!(key != cache::key("var a = 41; print a + 2;", &[false, false]));
+        assert!(cache::load(&directory, key, source).is_none());
+        cache::store(&directory, key, source, &program);
+        let cached = cache::load(&directory, key, source).unwrap();
+        assert!(disassemble(&cached) == disassemble(&program));
+        // An entry is only trusted for the source it was compiled from.
+        assert!(cache::load(&directory, key, "print 1;").is_none());
+        assert!(cache::clear(&directory) == Ok(1));
+        assert!(cache::load(&directory, key, source).is_none());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+    #[test]
+    fn collector_frees_unreachable_cycles_and_keeps_live_values() {
+        let source = "fun leak() {
+                var table = {};
+                fun lookup() { return table; }
+                table = {\"lookup\": lookup};
+            }
+            for (i in 0..100) leak();
+            var kept = [1, 2];
+            fun counter() { var n = 0; fun inc() { n = n + 1; return n; } return inc; }
+            var c = counter();
+            c();
+            match (gc() >= 200) { true => {} }
+            match (gc()) { 0 => {} }
+            match (str(kept) + str(c())) { \"[1, 2]2\" => {} }
+            var stats = gc_stats();
+            match (stats[\"collections\"] >= 2) { true => {} }
+            match (stats[\"freed\"] >= 200) { true => {} }";
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut interpreter = Interpreter::new();
+            interpreter.engine = engine;
+            assert!(run(source.to_string(), &mut interpreter).is_ok());
+        }
+    }
+    #[test]
+    fn identifiers_are_interned_once_per_name() {
+        let tokens = Scanner::new("var total = 1; total = total + \"total\";".as_bytes())
+            .scan_tokens()
+            .unwrap();
+        let total = symbols::intern("total");
+        assert!(tokens[1].symbol == total && tokens[5].symbol == total);
+        assert!(tokens[7].symbol == total);
+        // Keywords and literals are not names, so they are not interned as one.
+        assert!(tokens[0].symbol == symbols::EMPTY && tokens[9].symbol == symbols::EMPTY);
+        assert!(symbols::intern("totals") != total);
+        assert!(&*total.name() == "total");
+        // The compiler interns string constants: "x" and the script's nil are stored once.
+        let tokens = Scanner::new("var a = \"x\"; var b = \"x\";".as_bytes())
+            .scan_tokens()
+            .unwrap();
+        let program = Compiler::new().compile(&Parser::new(tokens).parse().unwrap());
+        assert!(program.chunk.constants.len() == 2);
+        let mut interpreter = Interpreter::new();
+        let source =
+            "var x = 1; { var x = 2; x = x + 1; match (x) { 3 => {} } } match (x) { 1 => {} }";
+        assert!(run(source.to_string(), &mut interpreter).is_ok());
+        assert!(interpreter.defined_names().contains(&"x".to_string()));
+    }
 }
 
 // fn main() {
@@ -0,0 +1,719 @@
+use std::{rc::Rc, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+
+use crate::{
+    bytecode::{Capture, Chunk, Op, Prototype},
+    patterns::Pattern,
+    ranges::Range,
+    scanner::{LiteralValue, Token, TokenType},
+    symbols::{self, SymbolMap},
+    values::Value,
+};
+
+// Compiled programs saved by `timepass compile`. All numbers are little endian,
+// lengths and operands are u32, and strings are a length followed by UTF-8.
+//
+//   header     "TPC\0", format version (u16), flags (u8), source hash (u64, when
+//              flag bit 0 is set)
+//   function   name, arity, cell count, generator flag (u8), captures, chunk
+//   chunk      constant pool, token table, pattern table, code, line table (one
+//              line per instruction), recoverable ranges, function table
+//
+// The function table holds the nested functions, each laid out as a function.
+// Files written by another format version are rejected rather than guessed at.
+const MAGIC: &[u8; 4] = b"TPC\0";
+pub const VERSION: u16 = 2;
+const HAS_SOURCE_HASH: u8 = 1;
+
+// A compiled program and the hash of the source it was compiled from.
+pub struct Compiled {
+    pub source_hash: Option<u64>,
+    pub program: Prototype,
+}
+
+// FNV-1a, which unlike `DefaultHasher` is the same in every build.
+pub fn source_hash(source: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in source.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+pub fn write(program: &Prototype, source_hash: Option<u64>) -> Vec<u8> {
+    let mut writer = Writer { bytes: Vec::new() };
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.bytes.extend_from_slice(&VERSION.to_le_bytes());
+    match source_hash {
+        Some(hash) => {
+            writer.u8(HAS_SOURCE_HASH);
+            writer.bytes.extend_from_slice(&hash.to_le_bytes());
+        }
+        None => writer.u8(0),
+    }
+    writer.function(program);
+    return writer.bytes;
+}
+
+pub fn read(bytes: &[u8]) -> Result<Compiled, String> {
+    if !bytes.starts_with(MAGIC) {
+        return Err("Not a compiled timepass file".to_string());
+    }
+    let mut reader = Reader {
+        bytes: bytes,
+        at: MAGIC.len(),
+    };
+    let version = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+    if version != VERSION {
+        return Err(format!(
+            "Compiled file has format version {}, expected {} : recompile it",
+            version, VERSION
+        ));
+    }
+    let source_hash = match reader.u8()? & HAS_SOURCE_HASH {
+        0 => None,
+        _ => Some(reader.u64()?),
+    };
+    let program = reader.function()?;
+    // The script runs with nothing but itself on the stack and captures nothing.
+    if reader.at != bytes.len() || program.arity != 0 || program.captures.len() > 0 {
+        return Err(reader.corrupt());
+    }
+    return Ok(Compiled {
+        source_hash: source_hash,
+        program: program,
+    });
+}
+
+// Every token type, in the order their numbers are written. New types go at the
+// end so the numbers in existing files keep their meaning.
+const TOKEN_TYPES: [TokenType; 56] = [
+    TokenType::LEFT_PAREN,
+    TokenType::RIGHT_PAREN,
+    TokenType::LEFT_BRACE,
+    TokenType::RIGHT_BRACE,
+    TokenType::LEFT_BRACKET,
+    TokenType::RIGHT_BRACKET,
+    TokenType::COMMA,
+    TokenType::DOT,
+    TokenType::MINUS,
+    TokenType::PLUS,
+    TokenType::SEMICOLON,
+    TokenType::COLON,
+    TokenType::SLASH,
+    TokenType::STAR,
+    TokenType::MODULO,
+    TokenType::BANG,
+    TokenType::BANG_EQUAL,
+    TokenType::EQUAL,
+    TokenType::EQUAL_EQUAL,
+    TokenType::GREATER,
+    TokenType::GREATER_EQUAL,
+    TokenType::LESS,
+    TokenType::LESS_EQUAL,
+    TokenType::FAT_ARROW,
+    TokenType::ARROW,
+    TokenType::QUESTION,
+    TokenType::DOT_DOT,
+    TokenType::DOT_DOT_EQUAL,
+    TokenType::STAR_STAR,
+    TokenType::TILDE_SLASH,
+    TokenType::IDENTIFIER,
+    TokenType::STRING,
+    TokenType::NUMBER,
+    TokenType::AND,
+    TokenType::CLASS,
+    TokenType::CONST,
+    TokenType::ELSE,
+    TokenType::ENUM,
+    TokenType::FALSE,
+    TokenType::FUN,
+    TokenType::FOR,
+    TokenType::IF,
+    TokenType::IN,
+    TokenType::MATCH,
+    TokenType::NIL,
+    TokenType::OR,
+    TokenType::PRINT,
+    TokenType::RETURN,
+    TokenType::SUPER,
+    TokenType::THIS,
+    TokenType::TRUE,
+    TokenType::VAR,
+    TokenType::WHILE,
+    TokenType::YIELD,
+    TokenType::EOF,
+    TokenType::BAD_TOKEN,
+];
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(self: &mut Self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(self: &mut Self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn length(self: &mut Self, length: usize) {
+        self.u32(length as u32);
+    }
+
+    fn blob(self: &mut Self, blob: &[u8]) {
+        self.length(blob.len());
+        self.bytes.extend_from_slice(blob);
+    }
+
+    fn string(self: &mut Self, string: &str) {
+        self.blob(string.as_bytes());
+    }
+
+    fn big_int(self: &mut Self, value: &BigInt) {
+        self.blob(&value.to_signed_bytes_le());
+    }
+
+    fn function(self: &mut Self, prototype: &Prototype) {
+        self.string(&prototype.name);
+        self.length(prototype.arity);
+        self.length(prototype.cells);
+        self.u8(prototype.is_generator as u8);
+        self.length(prototype.captures.len());
+        for capture in prototype.captures.iter() {
+            match capture {
+                Capture::Cell(cell) => {
+                    self.u8(0);
+                    self.u32(*cell);
+                }
+                Capture::Upvalue(upvalue) => {
+                    self.u8(1);
+                    self.u32(*upvalue);
+                }
+            }
+        }
+        self.chunk(&prototype.chunk);
+    }
+
+    fn chunk(self: &mut Self, chunk: &Chunk) {
+        self.length(chunk.constants.len());
+        for constant in chunk.constants.iter() {
+            self.value(constant);
+        }
+        self.length(chunk.tokens.len());
+        for token in chunk.tokens.iter() {
+            self.token(token);
+        }
+        self.length(chunk.patterns.len());
+        for pattern in chunk.patterns.iter() {
+            self.pattern(pattern);
+        }
+        self.length(chunk.code.len());
+        for op in chunk.code.iter() {
+            self.op(*op);
+        }
+        for line in chunk.lines.iter() {
+            self.length(*line);
+        }
+        self.length(chunk.recoverable.len());
+        for (start, end) in chunk.recoverable.iter() {
+            self.length(*start);
+            self.length(*end);
+        }
+        self.length(chunk.functions.len());
+        for function in chunk.functions.iter() {
+            self.function(function);
+        }
+    }
+
+    // Only values the compiler puts in a constant pool can be written.
+    fn value(self: &mut Self, value: &Value) {
+        match value {
+            Value::Nil => self.u8(0),
+            Value::True => self.u8(1),
+            Value::False => self.u8(2),
+            Value::IntValue(x) => {
+                self.u8(3);
+                self.bytes.extend_from_slice(&x.to_le_bytes());
+            }
+            Value::BigIntValue(x) => {
+                self.u8(4);
+                self.big_int(x);
+            }
+            Value::RationalValue(x) => {
+                self.u8(5);
+                self.big_int(x.numer());
+                self.big_int(x.denom());
+            }
+            Value::DecimalValue(x) => {
+                self.u8(6);
+                self.string(&x.to_string());
+            }
+            Value::FValue(x) => {
+                self.u8(7);
+                self.bytes.extend_from_slice(&x.to_bits().to_le_bytes());
+            }
+            Value::StringValue(x) => {
+                self.u8(8);
+                self.string(x);
+            }
+            Value::EnumType(name, variants) => {
+                self.u8(9);
+                self.string(&name.name());
+                self.length(variants.len());
+                for (variant, arity) in variants {
+                    self.string(&variant.name());
+                    self.length(*arity);
+                }
+            }
+            other => unreachable!("{} is never a constant", other.to_repr()),
+        }
+    }
+
+    fn token(self: &mut Self, token: &Token) {
+        let token_type = TOKEN_TYPES.iter().position(|t| *t == token.token_type);
+        self.u8(token_type.unwrap() as u8);
+        self.string(&token.lexeme);
+        self.length(token.line_number);
+    }
+
+    fn pattern(self: &mut Self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard => self.u8(0),
+            Pattern::Binding(name) => {
+                self.u8(1);
+                self.token(name);
+            }
+            Pattern::Literal(value) => {
+                self.u8(2);
+                self.value(value);
+            }
+            Pattern::Range(range) => {
+                self.u8(3);
+                self.bytes.extend_from_slice(&range.start.to_le_bytes());
+                self.bytes.extend_from_slice(&range.end.to_le_bytes());
+                self.bytes.extend_from_slice(&range.step.to_le_bytes());
+                self.u8(range.inclusive as u8);
+            }
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                self.u8(4);
+                self.token(enum_name);
+                self.token(variant);
+                self.length(fields.len());
+                for field in fields {
+                    self.pattern(field);
+                }
+            }
+        }
+    }
+
+    fn op(self: &mut Self, op: Op) {
+        let (code, operands): (u8, Vec<u32>) = match op {
+            Op::Constant(a) => (0, vec![a]),
+            Op::Pop => (1, vec![]),
+            Op::PopN(a) => (2, vec![a]),
+            Op::GetLocal(a) => (3, vec![a]),
+            Op::SetLocal(a) => (4, vec![a]),
+            Op::NewCell(a) => (5, vec![a]),
+            Op::GetCell(a, b) => (6, vec![a, b]),
+            Op::SetCell(a) => (7, vec![a]),
+            Op::GetUpvalue(a, b) => (8, vec![a, b]),
+            Op::SetUpvalue(a) => (9, vec![a]),
+            Op::GetGlobal(a) => (10, vec![a]),
+            Op::SetGlobal(a) => (11, vec![a]),
+            Op::DefineGlobal(a) => (12, vec![a]),
+            Op::DefineConstant(a) => (13, vec![a]),
+            Op::Binary(a) => (14, vec![a]),
+            Op::Unary(a) => (15, vec![a]),
+            Op::GetProperty(a) => (16, vec![a]),
+            Op::Index(a) => (17, vec![a]),
+            Op::Call(a, b) => (18, vec![a, b]),
+            Op::Closure(a) => (19, vec![a]),
+            Op::List(a) => (20, vec![a]),
+            Op::Map(a) => (21, vec![a]),
+            Op::Range(a, b) => (22, vec![a, b as u32]),
+            Op::Print => (23, vec![]),
+            Op::Jump(a) => (24, vec![a]),
+            Op::Iterate(a) => (25, vec![a]),
+            Op::Next(a) => (26, vec![a]),
+            Op::Match(a, b, c, d) => (27, vec![a, b, c, d]),
+            Op::NoMatch(a, b) => (28, vec![a, b]),
+            Op::Return => (29, vec![]),
+            Op::Yield => (30, vec![]),
+        };
+        self.u8(code);
+        for operand in operands {
+            self.u32(operand);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn corrupt(self: &Self) -> String {
+        return format!("Compiled file is corrupt at byte {}", self.at);
+    }
+
+    fn take(self: &mut Self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.at < count {
+            return Err(self.corrupt());
+        }
+        self.at += count;
+        return Ok(&self.bytes[self.at - count..self.at]);
+    }
+
+    fn u8(self: &mut Self) -> Result<u8, String> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u32(self: &mut Self) -> Result<u32, String> {
+        return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+
+    fn u64(self: &mut Self) -> Result<u64, String> {
+        return Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()));
+    }
+
+    fn i128(self: &mut Self) -> Result<i128, String> {
+        return Ok(i128::from_le_bytes(self.take(16)?.try_into().unwrap()));
+    }
+
+    fn length(self: &mut Self) -> Result<usize, String> {
+        return Ok(self.u32()? as usize);
+    }
+
+    fn blob(self: &mut Self) -> Result<&'a [u8], String> {
+        let length = self.length()?;
+        return self.take(length);
+    }
+
+    fn string(self: &mut Self) -> Result<String, String> {
+        let blob = self.blob()?;
+        match std::str::from_utf8(blob) {
+            Ok(string) => return Ok(string.to_string()),
+            Err(_) => return Err(self.corrupt()),
+        }
+    }
+
+    fn big_int(self: &mut Self) -> Result<BigInt, String> {
+        return Ok(BigInt::from_signed_bytes_le(self.blob()?));
+    }
+
+    // Reads `count` items, so a corrupt count fails on the data it claims rather
+    // than on allocating room for it.
+    fn many<T>(
+        self: &mut Self,
+        read: fn(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let count = self.length()?;
+        let mut items = Vec::new();
+        for _ in 0..count {
+            items.push(read(self)?);
+        }
+        return Ok(items);
+    }
+
+    fn function(self: &mut Self) -> Result<Prototype, String> {
+        let name = self.string()?;
+        let arity = self.length()?;
+        let cells = self.length()?;
+        let is_generator = self.u8()? != 0;
+        let captures = self.many(|reader| match reader.u8()? {
+            0 => return Ok(Capture::Cell(reader.u32()?)),
+            1 => return Ok(Capture::Upvalue(reader.u32()?)),
+            _ => return Err(reader.corrupt()),
+        })?;
+        let chunk = self.chunk()?;
+        let prototype = Prototype {
+            name: name,
+            arity: arity,
+            chunk: chunk,
+            cells: cells,
+            captures: captures,
+            is_generator: is_generator,
+        };
+        if !verify(&prototype) {
+            return Err(self.corrupt());
+        }
+        return Ok(prototype);
+    }
+
+    fn chunk(self: &mut Self) -> Result<Chunk, String> {
+        let constants = self.many(Reader::value)?;
+        let tokens = self.many(Reader::token)?;
+        let patterns = self.many(Reader::pattern)?;
+        let code = self.many(Reader::op)?;
+        let mut lines = Vec::new();
+        for _ in 0..code.len() {
+            lines.push(self.length()?);
+        }
+        let recoverable = self.many(|reader| return Ok((reader.length()?, reader.length()?)))?;
+        let functions = self.many(|reader| return Ok(Rc::new(reader.function()?)))?;
+        let chunk = Chunk {
+            code: code,
+            lines: lines,
+            constants: constants,
+            tokens: tokens,
+            patterns: patterns,
+            functions: functions,
+            recoverable: recoverable,
+            strings: SymbolMap::default(),
+        };
+        return Ok(chunk);
+    }
+
+    fn value(self: &mut Self) -> Result<Value, String> {
+        match self.u8()? {
+            0 => return Ok(Value::Nil),
+            1 => return Ok(Value::True),
+            2 => return Ok(Value::False),
+            3 => return Ok(Value::IntValue(self.i128()?)),
+            4 => return Ok(Value::BigIntValue(self.big_int()?)),
+            5 => {
+                let numer = self.big_int()?;
+                let denom = self.big_int()?;
+                if denom == BigInt::from(0) {
+                    return Err(self.corrupt());
+                }
+                return Ok(Value::RationalValue(BigRational::new(numer, denom)));
+            }
+            6 => match BigDecimal::from_str(&self.string()?) {
+                Ok(x) => return Ok(Value::DecimalValue(x)),
+                Err(_) => return Err(self.corrupt()),
+            },
+            7 => return Ok(Value::FValue(f64::from_bits(self.u64()?))),
+            8 => return Ok(Value::StringValue(self.string()?)),
+            9 => {
+                let name = symbols::intern(&self.string()?);
+                let variants = self.many(|reader| {
+                    return Ok((symbols::intern(&reader.string()?), reader.length()?));
+                })?;
+                return Ok(Value::EnumType(name, variants));
+            }
+            _ => return Err(self.corrupt()),
+        }
+    }
+
+    fn token(self: &mut Self) -> Result<Token, String> {
+        let token_type = match TOKEN_TYPES.get(self.u8()? as usize) {
+            Some(token_type) => *token_type,
+            None => return Err(self.corrupt()),
+        };
+        let lexeme = self.string()?;
+        let line_number = self.length()?;
+        // Identifiers are the only tokens in a chunk that carry a literal.
+        let literal = match token_type {
+            TokenType::IDENTIFIER => Some(LiteralValue::IdentifierValue(lexeme.clone())),
+            _ => None,
+        };
+        return Ok(Token::new(token_type, lexeme, literal, line_number));
+    }
+
+    fn pattern(self: &mut Self) -> Result<Pattern, String> {
+        match self.u8()? {
+            0 => return Ok(Pattern::Wildcard),
+            1 => return Ok(Pattern::Binding(self.token()?)),
+            2 => return Ok(Pattern::Literal(self.value()?)),
+            3 => {
+                let start = self.i128()?;
+                let end = self.i128()?;
+                let step = self.i128()?;
+                let inclusive = self.u8()? != 0;
+                if step == 0 {
+                    return Err(self.corrupt());
+                }
+                return Ok(Pattern::Range(Range::new(start, end, step, inclusive)));
+            }
+            4 => {
+                let enum_name = self.token()?;
+                let variant = self.token()?;
+                let fields = self.many(Reader::pattern)?;
+                return Ok(Pattern::Variant {
+                    enum_name: enum_name,
+                    variant: variant,
+                    fields: fields,
+                });
+            }
+            _ => return Err(self.corrupt()),
+        }
+    }
+
+    fn op(self: &mut Self) -> Result<Op, String> {
+        let code = self.u8()?;
+        let op = match code {
+            0 => Op::Constant(self.u32()?),
+            1 => Op::Pop,
+            2 => Op::PopN(self.u32()?),
+            3 => Op::GetLocal(self.u32()?),
+            4 => Op::SetLocal(self.u32()?),
+            5 => Op::NewCell(self.u32()?),
+            6 => Op::GetCell(self.u32()?, self.u32()?),
+            7 => Op::SetCell(self.u32()?),
+            8 => Op::GetUpvalue(self.u32()?, self.u32()?),
+            9 => Op::SetUpvalue(self.u32()?),
+            10 => Op::GetGlobal(self.u32()?),
+            11 => Op::SetGlobal(self.u32()?),
+            12 => Op::DefineGlobal(self.u32()?),
+            13 => Op::DefineConstant(self.u32()?),
+            14 => Op::Binary(self.u32()?),
+            15 => Op::Unary(self.u32()?),
+            16 => Op::GetProperty(self.u32()?),
+            17 => Op::Index(self.u32()?),
+            18 => Op::Call(self.u32()?, self.u32()?),
+            19 => Op::Closure(self.u32()?),
+            20 => Op::List(self.u32()?),
+            21 => Op::Map(self.u32()?),
+            22 => Op::Range(self.u32()?, self.u32()? != 0),
+            23 => Op::Print,
+            24 => Op::Jump(self.u32()?),
+            25 => Op::Iterate(self.u32()?),
+            26 => Op::Next(self.u32()?),
+            27 => Op::Match(self.u32()?, self.u32()?, self.u32()?, self.u32()?),
+            28 => Op::NoMatch(self.u32()?, self.u32()?),
+            29 => Op::Return,
+            30 => Op::Yield,
+            _ => return Err(self.corrupt()),
+        };
+        return Ok(op);
+    }
+}
+
+// Whether `prototype` can run without reaching outside its tables, cells, captures
+// or stack, so a damaged file is rejected before the VM runs it. Every reachable
+// instruction is visited with the stack height and number of open loops it runs
+// with; an instruction reached with two different ones is corrupt, as is one that
+// takes more values than the stack holds or reads a slot above it.
+fn verify(prototype: &Prototype) -> bool {
+    let chunk = &prototype.chunk;
+    let code = &chunk.code;
+    // Every function ends by returning, so running can not fall off the end, and
+    // every cell is made by a `NEW_CELL`, so there can not be more than instructions.
+    if code.last() != Some(&Op::Return) || prototype.cells > code.len() {
+        return false;
+    }
+    let mut states: Vec<Option<(usize, usize)>> = vec![None; code.len()];
+    let mut pending = vec![(0, prototype.arity + 1, 0)];
+    // After a failed top level `print` the VM goes on at its end with only the
+    // script on the stack.
+    for (start, end) in chunk.recoverable.iter() {
+        if start > end || *end >= code.len() {
+            return false;
+        }
+        pending.push((*end, 1, 0));
+    }
+    while let Some((offset, height, loops)) = pending.pop() {
+        match states[offset] {
+            Some(state) if state == (height, loops) => continue,
+            Some(_) => return false,
+            None => states[offset] = Some((height, loops)),
+        }
+        match successors(prototype, offset, height, loops) {
+            Some(next) => pending.extend(next),
+            None => return false,
+        }
+    }
+    return true;
+}
+
+// Where running `prototype.chunk.code[offset]` can go next, with the stack height
+// and loop count it leaves, or `None` if the instruction is not valid there.
+fn successors(
+    prototype: &Prototype,
+    offset: usize,
+    height: usize,
+    loops: usize,
+) -> Option<Vec<(usize, usize, usize)>> {
+    let chunk = &prototype.chunk;
+    let index = |index: u32, length: usize| (index as usize) < length;
+    let token = |name: u32| index(name, chunk.tokens.len());
+    let cell = |cell: u32| index(cell, prototype.cells);
+    let upvalue = |upvalue: u32| index(upvalue, prototype.captures.len());
+    let target = |offset: u32| index(offset, chunk.code.len());
+    let captures = |function: &Prototype| {
+        function.captures.iter().all(|capture| match capture {
+            Capture::Cell(c) => cell(*c),
+            Capture::Upvalue(u) => upvalue(*u),
+        })
+    };
+    // Whether the instruction is valid, how many values it pops and then pushes.
+    let (valid, pops, pushes) = match chunk.code[offset] {
+        Op::Constant(constant) => (index(constant, chunk.constants.len()), 0, 1),
+        Op::Pop | Op::Print => (true, 1, 0),
+        Op::PopN(count) => (true, count as usize, 0),
+        Op::GetLocal(slot) => (index(slot, height), 0, 1),
+        Op::SetLocal(slot) => (index(slot, height), 1, 1),
+        Op::NewCell(c) => (cell(c), 0, 0),
+        Op::GetCell(c, name) => (cell(c) && token(name), 0, 1),
+        Op::SetCell(c) => (cell(c), 1, 1),
+        Op::GetUpvalue(u, name) => (upvalue(u) && token(name), 0, 1),
+        Op::SetUpvalue(u) => (upvalue(u), 1, 1),
+        Op::GetGlobal(name) => (token(name), 0, 1),
+        Op::SetGlobal(name) => (token(name), 1, 1),
+        Op::DefineGlobal(name) | Op::DefineConstant(name) => (token(name), 1, 0),
+        Op::Binary(operator) => (token(operator), 2, 1),
+        Op::Unary(operator) => (token(operator), 1, 1),
+        Op::GetProperty(name) => (token(name), 1, 1),
+        Op::Index(bracket) => (token(bracket), 2, 1),
+        Op::Call(count, paren) => (token(paren), count as usize + 1, 1),
+        Op::Closure(function) => match chunk.functions.get(function as usize) {
+            Some(function) => (captures(function), 0, 1),
+            None => (false, 0, 0),
+        },
+        Op::List(count) => (true, count as usize, 1),
+        Op::Map(count) => (true, 2 * count as usize, 1),
+        Op::Range(operator, has_step) => (token(operator), 2 + has_step as usize, 1),
+        Op::Jump(offset) => {
+            return target(offset).then(|| vec![(offset as usize, height, loops)]);
+        }
+        Op::Iterate(keyword) => {
+            let after = height.checked_sub(1)?;
+            return token(keyword).then(|| vec![(offset + 1, after, loops + 1)]);
+        }
+        Op::Next(exit) => {
+            if !target(exit) || loops == 0 {
+                return None;
+            }
+            return Some(vec![
+                (offset + 1, height + 1, loops),
+                (exit as usize, height, loops - 1),
+            ]);
+        }
+        Op::Match(slot, pattern, enums, next) => {
+            let pattern = chunk.patterns.get(pattern as usize)?;
+            let after = height.checked_sub(enums as usize)?;
+            if !target(next) || !index(slot, after) || pattern.enum_names().len() != enums as usize
+            {
+                return None;
+            }
+            return Some(vec![
+                (offset + 1, after + pattern.bindings().len(), loops),
+                (next as usize, after, loops),
+            ]);
+        }
+        Op::NoMatch(keyword, slot) => {
+            return (token(keyword) && index(slot, height)).then(Vec::new);
+        }
+        Op::Return => return (height >= 1).then(Vec::new),
+        // The value yielded is replaced by the one sent when the generator resumes.
+        Op::Yield => (true, 1, 1),
+    };
+    if !valid || offset + 1 >= chunk.code.len() {
+        return None;
+    }
+    let after = height.checked_sub(pops)? + pushes;
+    return Some(vec![(offset + 1, after, loops)]);
+}
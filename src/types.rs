@@ -0,0 +1,212 @@
+use crate::{
+    natives::Module,
+    numbers::Rank,
+    scanner::{Token, TokenType},
+    values::Value,
+};
+
+// A static type, as written in annotations (`var x: int?`) and as worked out by
+// the checker. `Any` is the type of everything the checker knows nothing about,
+// which is what keeps unannotated code dynamic.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    Int,
+    Rational,
+    Decimal,
+    Float,
+    // Any kind of number.
+    Number,
+    String,
+    Bool,
+    Nil,
+    List,
+    Map,
+    Range,
+    Function(Option<Box<Signature>>),
+    Module(Module),
+    File,
+    Generator,
+    // An enum declared in the program, or an unknown name the checker reports.
+    Named(String),
+    // `T?`: a `T` or nil.
+    Nullable(Box<Type>),
+}
+
+// The parameter and return types of a function; unannotated ones are `Any`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub returns: Type,
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Type {
+        match name {
+            "any" => return Type::Any,
+            "int" => return Type::Int,
+            "rational" => return Type::Rational,
+            "decimal" => return Type::Decimal,
+            "float" => return Type::Float,
+            "number" => return Type::Number,
+            "string" => return Type::String,
+            "bool" => return Type::Bool,
+            "nil" => return Type::Nil,
+            "list" => return Type::List,
+            "map" => return Type::Map,
+            "range" => return Type::Range,
+            "function" => return Type::Function(None),
+            "file" => return Type::File,
+            "generator" => return Type::Generator,
+            other => return Type::Named(other.to_string()),
+        }
+    }
+
+    pub fn of(value: &Value) -> Type {
+        match value {
+            Value::IntValue(_) | Value::BigIntValue(_) => return Type::Int,
+            Value::RationalValue(_) => return Type::Rational,
+            Value::DecimalValue(_) => return Type::Decimal,
+            Value::FValue(_) => return Type::Float,
+            Value::StringValue(_) => return Type::String,
+            Value::True | Value::False => return Type::Bool,
+            Value::Nil => return Type::Nil,
+            Value::ListValue(_) => return Type::List,
+            Value::MapValue(_) => return Type::Map,
+            Value::RangeValue(_) => return Type::Range,
+            Value::Module(module) => return Type::Module(module.clone()),
+            Value::File(_) => return Type::File,
+            Value::Generator(_) => return Type::Generator,
+            Value::EnumValue(name, _, _) => return Type::Named(name.to_string()),
+            Value::Function(function) => {
+                return Type::Function(Some(Box::new(Signature {
+                    params: vec![Type::Any; function.params.len()],
+                    returns: Type::Any,
+                })))
+            }
+            Value::Closure(closure) => {
+                return Type::Function(Some(Box::new(Signature {
+                    params: vec![Type::Any; closure.prototype.arity],
+                    returns: Type::Any,
+                })))
+            }
+            Value::NativeFunction(native) => {
+                return Type::Function(Some(Box::new(Signature {
+                    params: vec![Type::Any; native.arity],
+                    returns: Type::Any,
+                })))
+            }
+            Value::EnumType(..) | Value::EnumConstructor(..) | Value::BoundMethod(..) => {
+                return Type::Any
+            }
+        }
+    }
+
+    pub fn to_string(self: &Self) -> String {
+        match self {
+            Type::Any => return "any".to_string(),
+            Type::Int => return "int".to_string(),
+            Type::Rational => return "rational".to_string(),
+            Type::Decimal => return "decimal".to_string(),
+            Type::Float => return "float".to_string(),
+            Type::Number => return "number".to_string(),
+            Type::String => return "string".to_string(),
+            Type::Bool => return "bool".to_string(),
+            Type::Nil => return "nil".to_string(),
+            Type::List => return "list".to_string(),
+            Type::Map => return "map".to_string(),
+            Type::Range => return "range".to_string(),
+            Type::Function(_) => return "function".to_string(),
+            Type::Module(module) => return format!("module {}", module.name),
+            Type::File => return "file".to_string(),
+            Type::Generator => return "generator".to_string(),
+            Type::Named(name) => return name.clone(),
+            Type::Nullable(inner) => return format!("{}?", inner.to_string()),
+        }
+    }
+
+    fn rank(self: &Self) -> Option<Rank> {
+        match self {
+            Type::Int => return Some(Rank::Integer),
+            Type::Rational => return Some(Rank::Rational),
+            Type::Decimal => return Some(Rank::Decimal),
+            Type::Float => return Some(Rank::Float),
+            _ => return None,
+        }
+    }
+
+    fn from_rank(rank: Rank) -> Type {
+        match rank {
+            Rank::Integer => return Type::Int,
+            Rank::Rational => return Type::Rational,
+            Rank::Decimal => return Type::Decimal,
+            Rank::Float => return Type::Float,
+        }
+    }
+
+    // The kind two numbers are converted to when they are mixed, so int and float
+    // make float. A `number` of unknown kind mixed with anything stays `number`.
+    pub fn promote(self: &Self, other: &Type) -> Type {
+        match (self.rank(), other.rank()) {
+            (Some(x), Some(y)) => return Type::from_rank(x.max(y)),
+            _ => return Type::Number,
+        }
+    }
+
+    pub fn is_numeric(self: &Self) -> bool {
+        return *self == Type::Number || self.rank().is_some();
+    }
+
+    // Whether a value of type `actual` may be stored where `self` is expected.
+    // Numbers widen along the numeric tower, so an int is accepted as a float.
+    pub fn accepts(self: &Self, actual: &Type) -> bool {
+        match (self, actual) {
+            (Type::Any, _) | (_, Type::Any) => return true,
+            (Type::Nullable(_), Type::Nil) => return true,
+            (Type::Nullable(expected), Type::Nullable(actual)) => return expected.accepts(actual),
+            (Type::Nullable(expected), actual) => return expected.accepts(actual),
+            (_, Type::Nullable(_)) => return false,
+            (Type::Number, actual) => return actual.is_numeric(),
+            (expected, Type::Number) => return expected.is_numeric(),
+            (Type::Function(_), Type::Function(_)) => return true,
+            (Type::Module(x), Type::Module(y)) => return x == y,
+            (expected, actual) => match (expected.rank(), actual.rank()) {
+                (Some(x), Some(y)) => return x >= y,
+                _ => return expected == actual,
+            },
+        }
+    }
+
+    // The type of `left operator right`, following the same promotion rules as
+    // `numbers::binary`, or `Any` when it depends on the values.
+    pub fn of_binary(left: &Type, operator: &Token, right: &Type) -> Type {
+        match operator.token_type {
+            TokenType::EQUAL_EQUAL
+            | TokenType::BANG_EQUAL
+            | TokenType::GREATER
+            | TokenType::GREATER_EQUAL
+            | TokenType::LESS
+            | TokenType::LESS_EQUAL => return Type::Bool,
+            TokenType::PLUS if *left == Type::String && *right == Type::String => {
+                return Type::String
+            }
+            TokenType::STAR_STAR if left.is_numeric() && right.is_numeric() => return Type::Number,
+            _ => {}
+        }
+        let rank = match (left.rank(), right.rank()) {
+            (Some(x), Some(y)) => x.max(y),
+            _ if left.is_numeric() && right.is_numeric() => return Type::Number,
+            _ => return Type::Any,
+        };
+        match operator.token_type {
+            TokenType::SLASH if rank == Rank::Integer => return Type::Float,
+            TokenType::PLUS
+            | TokenType::MINUS
+            | TokenType::STAR
+            | TokenType::SLASH
+            | TokenType::TILDE_SLASH
+            | TokenType::MODULO => return Type::from_rank(rank),
+            _ => return Type::Any,
+        }
+    }
+}
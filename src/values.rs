@@ -0,0 +1,288 @@
+use std::{cell::RefCell, rc::Rc};
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+
+use crate::{
+    errors::Error,
+    filesystem::FileHandle,
+    functions::Function,
+    gc,
+    generators::GeneratorRef,
+    integers,
+    natives::{Module, NativeFunction},
+    numbers,
+    ranges::Range,
+    scanner::{LiteralValue, NumberKind},
+    symbols::Symbol,
+    vm::Closure,
+};
+
+// What expressions evaluate to. Literals in the source are scanned as
+// `LiteralValue`s and only become `Value`s once the parser builds the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    IntValue(i128),
+    // Only for integers outside the i128 range; see `integers::normalize`.
+    BigIntValue(BigInt),
+    // Exact numbers written `1/3r` and `1.10d`.
+    RationalValue(BigRational),
+    DecimalValue(BigDecimal),
+    FValue(f64),
+    StringValue(String),
+    True,
+    False,
+    Nil,
+
+    // Enum name and its (variant, payload arity) pairs.
+    EnumType(Symbol, Vec<(Symbol, usize)>),
+    // Enum name, variant and arity of a variant that still needs its payload.
+    EnumConstructor(Symbol, Symbol, usize),
+    // Enum name, variant and payload of a constructed variant.
+    EnumValue(Symbol, Symbol, Vec<Value>),
+    // Lists and maps are shared: copies of a value refer to the same elements.
+    ListValue(Rc<RefCell<Vec<Value>>>),
+    RangeValue(Range),
+    // Entries are kept in insertion order, which is also the iteration order.
+    MapValue(Rc<RefCell<Vec<(Value, Value)>>>),
+    Function(Rc<Function>),
+    // A function compiled for the bytecode VM (`--engine=vm`).
+    Closure(Rc<Closure>),
+    NativeFunction(NativeFunction),
+    Module(Module),
+    File(FileHandle),
+    Generator(GeneratorRef),
+    // A built-in method looked up on a receiver, waiting to be called.
+    BoundMethod(Box<Value>, Symbol),
+}
+
+impl Value {
+    pub(crate) fn is_falsy(self: &mut Self) -> Result<Value, Error> {
+        match self {
+            Value::IntValue(x) => {
+                if x.clone() == 0 {
+                    return Ok(Value::True);
+                } else {
+                    return Ok(Value::False);
+                }
+            }
+            Value::FValue(x) => {
+                if x.clone() == 0.0 {
+                    return Ok(Value::True);
+                } else {
+                    return Ok(Value::False);
+                }
+            }
+            Value::StringValue(x) => {
+                if x.len() == (0 as usize) {
+                    return Ok(Value::True);
+                } else {
+                    return Ok(Value::False);
+                }
+            }
+            Value::True => {
+                return Ok(Value::False);
+            }
+            Value::False => {
+                return Ok(Value::True);
+            }
+            Value::Nil => {
+                return Ok(Value::True);
+            }
+            // Big integers are never zero.
+            Value::BigIntValue(_) => {
+                return Ok(Value::False);
+            }
+            Value::RationalValue(_) | Value::DecimalValue(_) => {
+                return Ok(Value::from_bool(numbers::is_zero(self)));
+            }
+            Value::EnumType(..)
+            | Value::EnumConstructor(..)
+            | Value::EnumValue(..)
+            | Value::Function(_)
+            | Value::Closure(_)
+            | Value::NativeFunction(_)
+            | Value::Module(_)
+            | Value::File(_)
+            | Value::Generator(_)
+            | Value::BoundMethod(..) => {
+                return Ok(Value::False);
+            }
+            Value::ListValue(items) => {
+                if items.borrow().len() == 0 {
+                    return Ok(Value::True);
+                } else {
+                    return Ok(Value::False);
+                }
+            }
+            Value::RangeValue(range) => {
+                if range.len() == 0 {
+                    return Ok(Value::True);
+                } else {
+                    return Ok(Value::False);
+                }
+            }
+            Value::MapValue(entries) => {
+                if entries.borrow().len() == 0 {
+                    return Ok(Value::True);
+                } else {
+                    return Ok(Value::False);
+                }
+            }
+        }
+    }
+
+    pub fn to_string(self: &Self) -> String {
+        match self {
+            Value::IntValue(value) => {
+                return value.to_string();
+            }
+            Value::BigIntValue(value) => {
+                return value.to_string();
+            }
+            Value::RationalValue(value) => {
+                return numbers::rational_to_string(value);
+            }
+            Value::DecimalValue(value) => {
+                return value.to_plain_string();
+            }
+            Value::FValue(value) => {
+                return numbers::float_to_string(*value);
+            }
+            Value::StringValue(value) => {
+                return value.to_string();
+            }
+            Value::True => {
+                return "true".to_string();
+            }
+            Value::False => {
+                return "false".to_string();
+            }
+            Value::Nil => {
+                return "nil".to_string();
+            }
+            Value::EnumType(name, _) => {
+                return format!("<enum {}>", name);
+            }
+            Value::EnumConstructor(name, variant, _) => {
+                return format!("<variant {}.{}>", name, variant);
+            }
+            Value::EnumValue(name, variant, payload) => {
+                if payload.len() == 0 {
+                    return format!("{}.{}", name, variant);
+                }
+                let fields: Vec<String> = payload.iter().map(|value| value.to_repr()).collect();
+                return format!("{}.{}({})", name, variant, fields.join(", "));
+            }
+            Value::ListValue(items) => {
+                let items: Vec<String> =
+                    items.borrow().iter().map(|value| value.to_repr()).collect();
+                return format!("[{}]", items.join(", "));
+            }
+            Value::RangeValue(range) => {
+                return range.to_string();
+            }
+            Value::MapValue(entries) => {
+                let entries: Vec<String> = entries
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key.to_repr(), value.to_repr()))
+                    .collect();
+                return format!("{{{}}}", entries.join(", "));
+            }
+            Value::Function(function) => {
+                return format!("<fn {}>", function.name.lexeme);
+            }
+            Value::Closure(closure) => {
+                return format!("<fn {}>", closure.prototype.name);
+            }
+            Value::NativeFunction(native) => {
+                return format!("<native fn {}>", native.name);
+            }
+            Value::Module(module) => {
+                return format!("<module {}>", module.name);
+            }
+            Value::File(file) => {
+                return format!("<file {}>", file.path);
+            }
+            Value::Generator(generator) => match generator.0.try_borrow() {
+                Ok(generator) => return format!("<generator {}>", generator.name()),
+                Err(_) => return "<generator>".to_string(),
+            },
+            Value::BoundMethod(receiver, name) => {
+                return format!("<method {} of {}>", name, receiver.to_string());
+            }
+        }
+    }
+
+    pub fn list(items: Vec<Value>) -> Value {
+        return Value::ListValue(gc::list(items));
+    }
+
+    pub fn map(entries: Vec<(Value, Value)>) -> Value {
+        return Value::MapValue(gc::map(entries));
+    }
+
+    // The runtime value of a number or string literal; identifiers have none, and
+    // neither does a number too large to represent, like `1e-400000000000d`.
+    pub fn from_literal(literal: &LiteralValue) -> Option<Value> {
+        match literal {
+            LiteralValue::NumberValue(NumberKind::Integer, digits) => {
+                return integers::parse(digits)
+            }
+            LiteralValue::NumberValue(NumberKind::Float, digits) => {
+                return digits.parse().ok().map(Value::FValue)
+            }
+            LiteralValue::NumberValue(NumberKind::Decimal, digits) => {
+                return numbers::parse_decimal(digits).map(Value::DecimalValue)
+            }
+            LiteralValue::NumberValue(NumberKind::Rational, digits) => {
+                return numbers::parse_rational(digits).map(Value::RationalValue)
+            }
+            LiteralValue::StringValue(x) => return Some(Value::StringValue(x.clone())),
+            LiteralValue::IdentifierValue(_) => return None,
+        }
+    }
+
+    pub fn from_bool(value: bool) -> Value {
+        if value {
+            return Value::True;
+        }
+        return Value::False;
+    }
+
+    // The name `type()` reports; enum values report the enum they belong to.
+    pub fn type_name(self: &Self) -> String {
+        match self {
+            Value::IntValue(_) | Value::BigIntValue(_) => return "int".to_string(),
+            Value::RationalValue(_) => return "rational".to_string(),
+            Value::DecimalValue(_) => return "decimal".to_string(),
+            Value::FValue(_) => return "float".to_string(),
+            Value::StringValue(_) => return "string".to_string(),
+            Value::True | Value::False => return "bool".to_string(),
+            Value::Nil => return "nil".to_string(),
+            Value::EnumType(..) => return "enum".to_string(),
+            Value::EnumConstructor(..) => return "function".to_string(),
+            Value::EnumValue(name, _, _) => return name.to_string(),
+            Value::ListValue(_) => return "list".to_string(),
+            Value::RangeValue(_) => return "range".to_string(),
+            Value::MapValue(_) => return "map".to_string(),
+            Value::Function(_)
+            | Value::Closure(_)
+            | Value::NativeFunction(_)
+            | Value::BoundMethod(..) => return "function".to_string(),
+            Value::Module(_) => return "module".to_string(),
+            Value::File(_) => return "file".to_string(),
+            Value::Generator(_) => return "generator".to_string(),
+        }
+    }
+
+    // Like `to_string`, but quotes strings so they read unambiguously inside containers.
+    pub fn to_repr(self: &Self) -> String {
+        match self {
+            Value::StringValue(value) => return format!("\"{}\"", value),
+            other => return other.to_string(),
+        }
+    }
+}
@@ -0,0 +1,446 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    bytecode::{Capture, Op, Prototype},
+    compiler::Compiler,
+    environment::Environment,
+    errors::Error,
+    expressions::{
+        binary_operation, call_value, get_property, index_value, map_value, range_value,
+        unary_operation,
+    },
+    functions,
+    gc::{self, Cell},
+    generators::Generator,
+    iteration::{iterate, ValueIterator},
+    scanner::Token,
+    statements::Statement,
+    values::Value,
+};
+
+// A compiled function together with the variables it captured.
+pub struct Closure {
+    pub(crate) prototype: Rc<Prototype>,
+    upvalues: Vec<Cell>,
+    globals: Rc<RefCell<Environment>>,
+}
+
+impl Closure {
+    pub(crate) fn trace(self: &Self, visit: &mut dyn FnMut(usize)) {
+        for upvalue in self.upvalues.iter() {
+            gc::trace_cell(upvalue, visit);
+        }
+        gc::trace_environment(&self.globals, visit);
+    }
+}
+
+impl std::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "<fn {}>", self.prototype.name);
+    }
+}
+
+// Closures are only ever equal to themselves.
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        return std::ptr::eq(self, other);
+    }
+}
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // Where the frame's slot 0, the closure itself, is on the stack.
+    base: usize,
+    cells: Vec<Cell>,
+    // The iterators of the `for` loops running in this frame, innermost last.
+    iterators: Vec<ValueIterator>,
+}
+
+impl Frame {
+    fn new(closure: Rc<Closure>, base: usize) -> Self {
+        let cells = (0..closure.prototype.cells).map(|_| gc::cell()).collect();
+        return Frame {
+            closure: closure,
+            ip: 0,
+            base: base,
+            cells: cells,
+            iterators: Vec::new(),
+        };
+    }
+}
+
+enum Exit {
+    Return(Value),
+    Yield(Value),
+}
+
+struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+// A suspended generator body. It keeps its own VM, so its stack and frames survive
+// between calls to `next`.
+pub struct Coroutine {
+    vm: Vm,
+}
+
+impl Coroutine {
+    // Runs to the next `yield`; once started, `sent` is what the last `yield` gives.
+    pub fn resume(self: &mut Self, sent: Value, started: bool) -> Result<Option<Value>, Error> {
+        if started {
+            self.vm.stack.push(sent);
+        }
+        match self.vm.run()? {
+            Exit::Yield(value) => return Ok(Some(value)),
+            Exit::Return(_) => return Ok(None),
+        }
+    }
+
+    pub fn close(self: &mut Self) {
+        self.vm.stack.clear();
+        self.vm.frames.clear();
+    }
+
+    pub(crate) fn trace(self: &Self, visit: &mut dyn FnMut(usize)) {
+        for value in self.vm.stack.iter() {
+            gc::trace_value(value, visit);
+        }
+        for frame in self.vm.frames.iter() {
+            gc::trace_closure(&frame.closure, visit);
+            for cell in frame.cells.iter() {
+                gc::trace_cell(cell, visit);
+            }
+            for iterator in frame.iterators.iter() {
+                gc::trace_iterator(iterator, visit);
+            }
+        }
+    }
+}
+
+// Compiles and runs a program with `globals` as its global environment.
+pub fn run(statements: &[Statement], globals: &Rc<RefCell<Environment>>) -> Result<(), String> {
+    return execute(Compiler::new().compile(statements), globals);
+}
+
+// Runs a compiled program, reporting errors the way `Interpreter::interpret` does.
+pub fn execute(program: Prototype, globals: &Rc<RefCell<Environment>>) -> Result<(), String> {
+    let script = gc::closure(Closure {
+        prototype: Rc::new(program),
+        upvalues: Vec::new(),
+        globals: globals.clone(),
+    });
+    let mut vm = Vm {
+        stack: vec![Value::Closure(script.clone())],
+        frames: vec![Frame::new(script.clone(), 0)],
+    };
+    let mut errors: Vec<Error> = Vec::new();
+    loop {
+        let err = match vm.run() {
+            Ok(_) => break,
+            Err(err) => err,
+        };
+        // A failed top level `print` is reported at the end; the program goes on
+        // with the statement after it.
+        let at = vm.frames[0].ip - 1;
+        let recoverable = script.prototype.chunk.recoverable.iter();
+        match recoverable
+            .into_iter()
+            .find(|(start, end)| *start <= at && at < *end)
+        {
+            Some((_, end)) => {
+                errors.push(err);
+                vm.frames.truncate(1);
+                vm.stack.truncate(1);
+                vm.frames[0].ip = *end;
+            }
+            None => return Err(err.to_string()),
+        }
+    }
+    if errors.len() == 0 {
+        return Ok(());
+    }
+    let mut err_str = String::new();
+    for e in errors {
+        err_str += &format!("{}\n", e.to_string());
+    }
+    return Err(err_str);
+}
+
+// Calls a closure from outside the VM, as natives and the tree-walker do.
+pub fn call(closure: Rc<Closure>, paren: &Token, arguments: Vec<Value>) -> Result<Value, Error> {
+    if arguments.len() != closure.prototype.arity {
+        return Err(Error::ArityMismatch(
+            paren.clone(),
+            closure.prototype.arity,
+            arguments.len(),
+        ));
+    }
+    let mut stack = vec![Value::Closure(closure.clone())];
+    stack.extend(arguments);
+    let vm = Vm {
+        stack: stack,
+        frames: vec![Frame::new(closure.clone(), 0)],
+    };
+    if closure.prototype.is_generator {
+        let generator = Generator::compiled(closure.prototype.name.clone(), Coroutine { vm: vm });
+        return Ok(Value::Generator(gc::generator(generator)));
+    }
+    let mut vm = vm;
+    let depth = functions::call_depth();
+    functions::enter_call(paren)?;
+    let exit = vm.run();
+    functions::set_call_depth(depth);
+    match exit? {
+        Exit::Return(value) | Exit::Yield(value) => return Ok(value),
+    }
+}
+
+// The value of a captured variable. An empty cell belongs to a function or enum whose declaration has not run yet.
+fn read_cell(cell: &Cell, name: &Token) -> Result<Value, Error> {
+    match &*cell.borrow() {
+        Some(value) => return Ok(value.clone()),
+        None => return Err(Error::InvalidToken(name.clone())),
+    }
+}
+
+impl Vm {
+    // Runs until the bottom frame returns or yields. The frames above the bottom one
+    // count as calls in progress, until `run` returns whichever way it does; whoever
+    // runs the VM counts the bottom frame.
+    fn run(self: &mut Self) -> Result<Exit, Error> {
+        let depth = functions::call_depth();
+        functions::set_call_depth(depth + self.frames.len().saturating_sub(1));
+        let exit = self.run_frames();
+        functions::set_call_depth(depth);
+        return exit;
+    }
+
+    fn run_frames(self: &mut Self) -> Result<Exit, Error> {
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let op = frame.closure.prototype.chunk.code[frame.ip];
+            frame.ip += 1;
+            let frame = self.frames.last().unwrap();
+            let chunk = &frame.closure.prototype.chunk;
+            match op {
+                Op::Constant(index) => self.stack.push(chunk.constants[index as usize].clone()),
+                Op::Pop => {
+                    self.stack.pop();
+                }
+                Op::PopN(count) => {
+                    let length = self.stack.len() - count as usize;
+                    self.stack.truncate(length);
+                }
+                Op::GetLocal(slot) => {
+                    let value = self.stack[frame.base + slot as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetLocal(slot) => {
+                    let value = self.stack.last().unwrap().clone();
+                    self.stack[frame.base + slot as usize] = value;
+                }
+                Op::NewCell(cell) => {
+                    self.frames.last_mut().unwrap().cells[cell as usize] = gc::cell();
+                }
+                Op::GetCell(cell, name) => {
+                    let value =
+                        read_cell(&frame.cells[cell as usize], &chunk.tokens[name as usize])?;
+                    self.stack.push(value);
+                }
+                Op::SetCell(cell) => {
+                    let value = self.stack.last().unwrap().clone();
+                    *frame.cells[cell as usize].borrow_mut() = Some(value);
+                }
+                Op::GetUpvalue(upvalue, name) => {
+                    let value = read_cell(
+                        &frame.closure.upvalues[upvalue as usize],
+                        &chunk.tokens[name as usize],
+                    )?;
+                    self.stack.push(value);
+                }
+                Op::SetUpvalue(upvalue) => {
+                    let value = self.stack.last().unwrap().clone();
+                    *frame.closure.upvalues[upvalue as usize].borrow_mut() = Some(value);
+                }
+                Op::GetGlobal(name) => {
+                    let name = &chunk.tokens[name as usize];
+                    match frame.closure.globals.borrow().get(name.symbol) {
+                        Ok(value) => self.stack.push(value),
+                        Err(_) => return Err(Error::InvalidToken(name.clone())),
+                    }
+                }
+                Op::SetGlobal(name) => {
+                    let value = self.stack.last().unwrap().clone();
+                    frame
+                        .closure
+                        .globals
+                        .borrow_mut()
+                        .assign(&chunk.tokens[name as usize], value)?;
+                }
+                Op::DefineGlobal(name) => {
+                    let value = self.stack.pop().unwrap();
+                    frame
+                        .closure
+                        .globals
+                        .borrow_mut()
+                        .define(&chunk.tokens[name as usize], value)?;
+                }
+                Op::DefineConstant(name) => {
+                    let value = self.stack.pop().unwrap();
+                    frame
+                        .closure
+                        .globals
+                        .borrow_mut()
+                        .define_constant(&chunk.tokens[name as usize], value)?;
+                }
+                Op::Binary(operator) => {
+                    let right = self.stack.pop().unwrap();
+                    let left = self.stack.pop().unwrap();
+                    let value = binary_operation(left, &chunk.tokens[operator as usize], right)?;
+                    self.stack.push(value);
+                }
+                Op::Unary(operator) => {
+                    let right = self.stack.pop().unwrap();
+                    let value = unary_operation(&chunk.tokens[operator as usize], right)?;
+                    self.stack.push(value);
+                }
+                Op::GetProperty(name) => {
+                    let object = self.stack.pop().unwrap();
+                    let value = get_property(object, &chunk.tokens[name as usize])?;
+                    self.stack.push(value);
+                }
+                Op::Index(bracket) => {
+                    let index = self.stack.pop().unwrap();
+                    let object = self.stack.pop().unwrap();
+                    let value = index_value(object, &chunk.tokens[bracket as usize], index)?;
+                    self.stack.push(value);
+                }
+                Op::Call(count, paren) => {
+                    let callee = self.stack.len() - count as usize - 1;
+                    match &self.stack[callee] {
+                        Value::Closure(closure) if !closure.prototype.is_generator => {
+                            if count as usize != closure.prototype.arity {
+                                return Err(Error::ArityMismatch(
+                                    chunk.tokens[paren as usize].clone(),
+                                    closure.prototype.arity,
+                                    count as usize,
+                                ));
+                            }
+                            functions::enter_call(&chunk.tokens[paren as usize])?;
+                            let frame = Frame::new(closure.clone(), callee);
+                            self.frames.push(frame);
+                        }
+                        _ => {
+                            let arguments = self.stack.split_off(callee + 1);
+                            let function = self.stack.pop().unwrap();
+                            let paren = &chunk.tokens[paren as usize];
+                            let value = call_value(function, paren, arguments)?;
+                            self.stack.push(value);
+                        }
+                    }
+                }
+                Op::Closure(index) => {
+                    let prototype = chunk.functions[index as usize].clone();
+                    let upvalues = prototype
+                        .captures
+                        .iter()
+                        .map(|capture| match capture {
+                            Capture::Cell(cell) => frame.cells[*cell as usize].clone(),
+                            Capture::Upvalue(upvalue) => {
+                                frame.closure.upvalues[*upvalue as usize].clone()
+                            }
+                        })
+                        .collect();
+                    let closure = Closure {
+                        prototype: prototype,
+                        upvalues: upvalues,
+                        globals: frame.closure.globals.clone(),
+                    };
+                    self.stack.push(Value::Closure(gc::closure(closure)));
+                }
+                Op::List(count) => {
+                    let items = self.stack.split_off(self.stack.len() - count as usize);
+                    self.stack.push(Value::list(items));
+                }
+                Op::Map(count) => {
+                    let flat = self.stack.split_off(self.stack.len() - 2 * count as usize);
+                    let mut pairs = Vec::new();
+                    let mut flat = flat.into_iter();
+                    while let (Some(key), Some(value)) = (flat.next(), flat.next()) {
+                        pairs.push((key, value));
+                    }
+                    self.stack.push(map_value(pairs));
+                }
+                Op::Range(operator, has_step) => {
+                    let step = match has_step {
+                        true => self.stack.pop(),
+                        false => None,
+                    };
+                    let end = self.stack.pop().unwrap();
+                    let start = self.stack.pop().unwrap();
+                    let operator = &chunk.tokens[operator as usize];
+                    self.stack.push(range_value(start, operator, end, step)?);
+                }
+                Op::Print => {
+                    let value = self.stack.pop().unwrap();
+                    print!("{}", value.to_string());
+                }
+                Op::Jump(target) => self.frames.last_mut().unwrap().ip = target as usize,
+                Op::Iterate(keyword) => {
+                    let iterable = self.stack.pop().unwrap();
+                    let iterator = iterate(iterable, &chunk.tokens[keyword as usize])?;
+                    self.frames.last_mut().unwrap().iterators.push(iterator);
+                }
+                Op::Next(exit) => {
+                    let frame = self.frames.last_mut().unwrap();
+                    match frame.iterators.last_mut().unwrap().next()? {
+                        Some(item) => self.stack.push(item),
+                        None => {
+                            frame.iterators.pop();
+                            frame.ip = exit as usize;
+                        }
+                    }
+                }
+                Op::Match(slot, pattern, enums, next) => {
+                    let enums = self.stack.split_off(self.stack.len() - enums as usize);
+                    let pattern = &chunk.patterns[pattern as usize];
+                    let names = pattern.enum_names();
+                    let lookup = |name: &Token| {
+                        let position = names.iter().position(|n| n.lexeme == name.lexeme);
+                        return Ok(enums[position.unwrap()].clone());
+                    };
+                    let subject = &self.stack[frame.base + slot as usize];
+                    let mut bindings = Vec::new();
+                    if pattern.matches_with(subject, &lookup, &mut bindings)? {
+                        self.stack
+                            .extend(bindings.into_iter().map(|(_, value)| value));
+                    } else {
+                        self.frames.last_mut().unwrap().ip = next as usize;
+                    }
+                }
+                Op::NoMatch(keyword, slot) => {
+                    return Err(Error::NoMatchingArm(
+                        chunk.tokens[keyword as usize].clone(),
+                        self.stack[frame.base + slot as usize].clone(),
+                    ));
+                }
+                Op::Return => {
+                    let value = self.stack.pop().unwrap();
+                    let frame = self.frames.pop().unwrap();
+                    self.stack.truncate(frame.base);
+                    if self.frames.len() == 0 {
+                        return Ok(Exit::Return(value));
+                    }
+                    functions::set_call_depth(functions::call_depth() - 1);
+                    self.stack.push(value);
+                }
+                Op::Yield => {
+                    let value = self.stack.pop().unwrap();
+                    return Ok(Exit::Yield(value));
+                }
+            }
+        }
+    }
+}